@@ -1,12 +1,16 @@
+use std::sync::Arc;
+
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 
-use super::CsrfCipher;
+use crate::rand_source::random_bytes;
+use crate::{CsrfCipher, OsRandSource, RandSource};
 
 /// CSRF protection implementation that uses bcrypt.
 pub struct BcryptCipher {
     cost: u32,
     token_size: usize,
+    rand_source: Arc<dyn RandSource>,
 }
 impl Default for BcryptCipher {
     fn default() -> Self {
@@ -21,9 +25,18 @@ impl BcryptCipher {
         Self {
             cost: 8,
             token_size: 32,
+            rand_source: Arc::new(OsRandSource),
         }
     }
 
+    /// Sets the source of the random bytes of tokens, proofs and nonces. Defaults to
+    /// [`OsRandSource`].
+    #[inline]
+    pub fn rand_source(mut self, rand_source: impl RandSource) -> Self {
+        self.rand_source = Arc::new(rand_source);
+        self
+    }
+
     /// Sets the length of the token.
     #[inline]
     pub fn token_size(mut self, token_size: usize) -> Self {
@@ -42,6 +55,9 @@ impl BcryptCipher {
 }
 
 impl CsrfCipher for BcryptCipher {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        random_bytes(&*self.rand_source, len)
+    }
     fn verify(&self, token: &str, proof: &str) -> bool {
         if let Ok(token) = URL_SAFE_NO_PAD.decode(token.as_bytes()) {
             let proof = proof.replace('_', "/").replace('-', "+");
@@ -52,7 +68,13 @@ impl CsrfCipher for BcryptCipher {
     }
     fn generate(&self) -> (String, String) {
         let token = self.random_bytes(self.token_size);
-        let proof = bcrypt::hash(&token, self.cost).expect("Call bcrypt hash get error result.").replace('+', "/").replace('/', "_");
+        let mut salt = [0; 16];
+        self.rand_source.fill_bytes(&mut salt);
+        let proof = bcrypt::hash_with_salt(&token, self.cost, salt)
+            .expect("Call bcrypt hash get error result.")
+            .format_for_version(bcrypt::Version::TwoB)
+            .replace('+', "/")
+            .replace('/', "_");
 
         (URL_SAFE_NO_PAD.encode(token), proof)
     }
@@ -110,4 +132,17 @@ mod tests {
         let invalid_token = URL_SAFE_NO_PAD.encode(vec![0; token.len()]);
         assert!(!cipher.verify(&invalid_token, &proof));
     }
+
+    #[test]
+    fn test_bcrypt_cipher_rand_source() {
+        use std::sync::Mutex;
+
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let seeded = || BcryptCipher::new().rand_source(Mutex::new(StdRng::seed_from_u64(7)));
+        let (token, proof) = seeded().generate();
+        assert_eq!(seeded().generate(), (token.clone(), proof.clone()));
+        assert!(BcryptCipher::new().verify(&token, &proof));
+    }
 }
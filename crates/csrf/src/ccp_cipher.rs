@@ -1,15 +1,19 @@
+use std::sync::Arc;
+
 use aead::generic_array::GenericArray;
 use aead::{Aead, KeyInit};
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 use chacha20poly1305::ChaCha20Poly1305;
 
-use super::CsrfCipher;
+use crate::rand_source::random_bytes;
+use crate::{CsrfCipher, OsRandSource, RandSource};
 
 /// CcpCipher is a CSRF protection implementation that uses [`ChaCha20Poly1305`](https://datatracker.ietf.org/doc/html/rfc8439).
 pub struct CcpCipher {
     aead_key: [u8; 32],
     token_size: usize,
+    rand_source: Arc<dyn RandSource>,
 }
 
 impl CcpCipher {
@@ -19,9 +23,18 @@ impl CcpCipher {
         Self {
             aead_key,
             token_size: 32,
+            rand_source: Arc::new(OsRandSource),
         }
     }
 
+    /// Sets the source of the random bytes of tokens, proofs and nonces. Defaults to
+    /// [`OsRandSource`].
+    #[inline]
+    pub fn rand_source(mut self, rand_source: impl RandSource) -> Self {
+        self.rand_source = Arc::new(rand_source);
+        self
+    }
+
     /// Sets the length of the token.
     #[inline]
     pub fn token_size(mut self, token_size: usize) -> Self {
@@ -38,6 +51,9 @@ impl CcpCipher {
 }
 
 impl CsrfCipher for CcpCipher {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        random_bytes(&*self.rand_source, len)
+    }
     fn verify(&self, token: &str, proof: &str) -> bool {
         if let (Ok(token), Ok(proof)) = (
             URL_SAFE_NO_PAD.decode(token.as_bytes()),
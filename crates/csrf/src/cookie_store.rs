@@ -60,6 +60,23 @@ impl CookieStore {
         self.domain = Some(domain.into());
         self
     }
+
+    /// Builds the CSRF cookie holding `value` with the settings of this store.
+    pub(crate) fn build_cookie(&self, req: &Request, value: String) -> Cookie<'static> {
+        let secure = req.uri().scheme() == Some(&Scheme::HTTPS);
+        let expires = cookie::time::OffsetDateTime::now_utc() + self.ttl;
+        let cookie_builder = Cookie::build((self.name.clone(), value))
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .path(self.path.clone())
+            .secure(secure)
+            .expires(Expiration::DateTime(expires));
+        if let Some(domain) = &self.domain {
+            cookie_builder.domain(domain.clone()).build()
+        } else {
+            cookie_builder.build()
+        }
+    }
 }
 impl CsrfStore for CookieStore {
     type Error = Error;
@@ -82,20 +99,7 @@ impl CsrfStore for CookieStore {
         token: &str,
         proof: &str,
     ) -> Result<(), Self::Error> {
-        let secure = req.uri().scheme() == Some(&Scheme::HTTPS);
-        let expires = cookie::time::OffsetDateTime::now_utc() + self.ttl;
-        let cookie_builder = Cookie::build((self.name.clone(), format!("{token}.{proof}")))
-            .http_only(true)
-            .same_site(SameSite::Strict)
-            .path(self.path.clone())
-            .secure(secure)
-            .expires(Expiration::DateTime(expires));
-        let cookie = if let Some(domain) = &self.domain {
-            cookie_builder.domain(domain.clone()).build()
-        } else {
-            cookie_builder.build()
-        };
-        res.add_cookie(cookie);
+        res.add_cookie(self.build_cookie(req, format!("{token}.{proof}")));
         Ok(())
     }
 }
@@ -0,0 +1,92 @@
+use cookie::time::Duration;
+use cookie::{CookieJar, Key};
+use salvo_core::{Depot, Error, Request, Response};
+
+use super::{CookieStore, CsrfCipher, CsrfStore};
+
+/// A `CsrfStore` implementation for the double submit cookie pattern, which needs no session.
+///
+/// The proof is kept in a cookie signed with `key`, so the token submitted with a form or header
+/// is checked against the signed cookie alone. It suits forms served to anonymous users, such as
+/// contact forms, and still works next to a `SessionHandler`, which it doesn't look into.
+/// Cookies whose signature doesn't verify are ignored, as if there were no cookie.
+pub struct DoubleSubmitStore {
+    cookie: CookieStore,
+    key: Key,
+}
+
+impl std::fmt::Debug for DoubleSubmitStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DoubleSubmitStore")
+            .field("cookie", &self.cookie)
+            .field("key", &"..")
+            .finish()
+    }
+}
+
+impl DoubleSubmitStore {
+    /// Create a new `DoubleSubmitStore` signing its cookie with `key`.
+    pub fn new(key: &Key) -> Self {
+        Self {
+            cookie: CookieStore::new(),
+            key: key.clone(),
+        }
+    }
+
+    /// Sets cookie name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.cookie = self.cookie.name(name);
+        self
+    }
+
+    /// Sets cookie ttl.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.cookie = self.cookie.ttl(ttl);
+        self
+    }
+
+    /// Sets cookie path.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.cookie = self.cookie.path(path);
+        self
+    }
+
+    /// Sets cookie domain.
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.cookie = self.cookie.domain(domain);
+        self
+    }
+}
+
+impl CsrfStore for DoubleSubmitStore {
+    type Error = Error;
+    async fn load<C: CsrfCipher>(&self, req: &mut Request, _depot: &mut Depot, cipher: &C) -> Option<(String, String)> {
+        let cookie = req.cookie(&self.cookie.name)?.clone().into_owned();
+        let verified = CookieJar::new().signed(&self.key).verify(cookie)?;
+        let (token, proof) = verified.value().split_once('.')?;
+        if cipher.verify(token, proof) {
+            Some((token.into(), proof.into()))
+        } else {
+            None
+        }
+    }
+    async fn save(
+        &self,
+        req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        token: &str,
+        proof: &str,
+    ) -> Result<(), Self::Error> {
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&self.key)
+            .add(self.cookie.build_cookie(req, format!("{token}.{proof}")));
+        let cookie = jar
+            .delta()
+            .next()
+            .cloned()
+            .ok_or_else(|| Error::Other("unable to sign csrf cookie".into()))?;
+        res.add_cookie(cookie);
+        Ok(())
+    }
+}
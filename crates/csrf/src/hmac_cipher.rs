@@ -1,14 +1,18 @@
+use std::sync::Arc;
+
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 
-use super::CsrfCipher;
+use crate::rand_source::random_bytes;
+use crate::{CsrfCipher, OsRandSource, RandSource};
 
 /// A CSRF protection implementation that uses HMAC.
 pub struct HmacCipher {
     hmac_key: [u8; 32],
     token_size: usize,
+    rand_source: Arc<dyn RandSource>,
 }
 
 impl HmacCipher {
@@ -18,9 +22,18 @@ impl HmacCipher {
         Self {
             hmac_key,
             token_size: 32,
+            rand_source: Arc::new(OsRandSource),
         }
     }
 
+    /// Sets the source of the random bytes of tokens, proofs and nonces. Defaults to
+    /// [`OsRandSource`].
+    #[inline]
+    pub fn rand_source(mut self, rand_source: impl RandSource) -> Self {
+        self.rand_source = Arc::new(rand_source);
+        self
+    }
+
     /// Sets the length of the token.
     #[inline]
     pub fn token_size(mut self, token_size: usize) -> Self {
@@ -36,6 +49,9 @@ impl HmacCipher {
 }
 
 impl CsrfCipher for HmacCipher {
+    fn random_bytes(&self, len: usize) -> Vec<u8> {
+        random_bytes(&*self.rand_source, len)
+    }
     fn verify(&self, token: &str, proof: &str) -> bool {
         if let (Ok(token), Ok(proof)) = (
             URL_SAFE_NO_PAD.decode(token.as_bytes()),
@@ -108,4 +124,19 @@ mod tests {
         let (token, proof) = hmac_cipher.generate();
         assert!(hmac_cipher.verify(&token, &proof));
     }
+
+    #[test]
+    fn test_rand_source() {
+        use std::sync::Mutex;
+
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let hmac_key = [0u8; 32];
+        let seeded = || HmacCipher::new(hmac_key).rand_source(Mutex::new(StdRng::seed_from_u64(7)));
+        let (token, proof) = seeded().generate();
+        assert_eq!(seeded().generate(), (token.clone(), proof.clone()));
+        assert_ne!(HmacCipher::new(hmac_key).generate().0, token);
+        assert!(HmacCipher::new(hmac_key).verify(&token, &proof));
+    }
 }
@@ -4,6 +4,8 @@
 //!
 //! Data can be saved in Cookies via [`CookieStore`](struct.CookieStore.html) or in session
 //! via [`SessionStore`](struct.SessionStore.html). [`SessionStore`](struct.SessionStore.html) need to work with `salvo-session` crate.
+//! [`DoubleSubmitStore`](struct.DoubleSubmitStore.html) keeps the proof in a signed cookie, for forms
+//! served to anonymous users without a session.
 //!
 //! Read more: <https://salvo.rs>
 #![doc(html_favicon_url = "https://salvo.rs/favicon-32x32.png")]
@@ -14,11 +16,11 @@ use std::error::Error as StdError;
 use std::future::Future;
 
 mod finder;
+mod rand_source;
 
 pub use finder::{CsrfTokenFinder, FormFinder, HeaderFinder, JsonFinder};
+pub use rand_source::{OsRandSource, RandSource};
 
-use rand::distributions::Standard;
-use rand::Rng;
 use salvo_core::handler::Skipper;
 use salvo_core::http::{Method, StatusCode};
 use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
@@ -36,6 +38,14 @@ cfg_feature! {
     pub fn cookie_store<>() -> CookieStore {
         CookieStore::new()
     }
+
+    mod double_submit_store;
+    pub use double_submit_store::DoubleSubmitStore;
+
+    /// Helper function to create a `DoubleSubmitStore` signing its cookie with `key`.
+    pub fn double_submit_store(key: &cookie::Key) -> DoubleSubmitStore {
+        DoubleSubmitStore::new(key)
+    }
 }
 cfg_feature! {
     #![feature = "session-store"]
@@ -170,6 +180,18 @@ pub trait CsrfStore: Send + Sync + 'static {
         depot: &mut Depot,
         cipher: &C,
     ) -> impl Future<Output = Option<(String, String)>> + Send;
+    /// Get the proof replaced by the last rotation, if tokens of it are still accepted.
+    ///
+    /// It is checked when a token doesn't verify against the proof returned by
+    /// [`load`](Self::load). Stores that don't rotate proofs keep the default, which returns
+    /// `None`.
+    fn load_previous(
+        &self,
+        _req: &mut Request,
+        _depot: &mut Depot,
+    ) -> impl Future<Output = Option<(String, String)>> + Send {
+        async { None }
+    }
     /// Save the proof from the store.
     fn save(
         &self,
@@ -189,8 +211,11 @@ pub trait CsrfCipher: Send + Sync + 'static {
     fn generate(&self) -> (String, String);
 
     /// Generate a random bytes.
+    ///
+    /// The default draws them from [`OsRandSource`]. The built-in ciphers draw them from the
+    /// [`RandSource`] set with their `rand_source` method.
     fn random_bytes(&self, len: usize) -> Vec<u8> {
-        rand::thread_rng().sample_iter(Standard).take(len).collect()
+        rand_source::random_bytes(&OsRandSource, len)
     }
 }
 
@@ -198,6 +223,13 @@ pub trait CsrfCipher: Send + Sync + 'static {
 pub trait CsrfDepotExt {
     /// Get csrf token reference from depot.
     fn csrf_token(&self) -> Option<&str>;
+
+    /// Renders a hidden form input named `field_name` holding the csrf token, to be read back
+    /// with a [`FormFinder`] using the same field name.
+    fn csrf_input(&self, field_name: &str) -> Option<String> {
+        self.csrf_token()
+            .map(|token| format!(r#"<input type="hidden" name="{field_name}" value="{token}">"#))
+    }
 }
 
 impl CsrfDepotExt for Depot {
@@ -274,7 +306,12 @@ impl<C: CsrfCipher, S: CsrfStore> Handler for Csrf<C, S> {
                 if !self.skipper.skipped(req, depot) {
                     if let Some(token) = &self.find_token(req).await {
                         tracing::debug!("csrf token: {token}");
-                        if !self.cipher.verify(token, &proof) {
+                        let valid = self.cipher.verify(token, &proof)
+                            || match self.store.load_previous(req, depot).await {
+                                Some((_, previous)) => self.cipher.verify(token, &previous),
+                                None => false,
+                            };
+                        if !valid {
                             tracing::debug!(
                                 "rejecting request due to invalid or expired CSRF token"
                             );
@@ -515,6 +552,50 @@ mod tests {
         assert_eq!(res.status_code.unwrap(), StatusCode::OK);
         assert_eq!(res.take_string().await.unwrap(), "POST");
     }
+    #[tokio::test]
+    async fn test_double_submit_without_session() {
+        #[handler]
+        async fn get_form(depot: &mut Depot) -> String {
+            depot.csrf_input("csrf-token").unwrap()
+        }
+        let key = cookie::Key::generate();
+        let csrf = Csrf::new(
+            BcryptCipher::new(),
+            DoubleSubmitStore::new(&key),
+            FormFinder::new("csrf-token"),
+        );
+        let router = Router::new().hoop(csrf).get(get_form).post(post_index);
+        let service = Service::new(router);
+
+        let mut res = TestClient::get("http://127.0.0.1:5801")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        let input = res.take_string().await.unwrap();
+        let csrf_token = input
+            .split_once(r#"value=""#)
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(token, _)| token.to_owned())
+            .unwrap();
+        let cookie = res.cookie("salvo.csrf").unwrap();
+        let (_, unsigned) = cookie.value().split_at(44);
+
+        let res = TestClient::post("http://127.0.0.1:5801")
+            .add_header("cookie", format!("salvo.csrf={unsigned}"), true)
+            .form(&[("csrf-token", &*csrf_token)])
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::FORBIDDEN);
+
+        let mut res = TestClient::post("http://127.0.0.1:5801")
+            .add_header("cookie", format!("salvo.csrf={}", cookie.value()), true)
+            .form(&[("csrf-token", &*csrf_token)])
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        assert_eq!(res.take_string().await.unwrap(), "POST");
+    }
+
     #[tokio::test]
     async fn test_validates_token_in_alternate_form() {
         let csrf = Csrf::new(
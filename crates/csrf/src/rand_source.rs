@@ -0,0 +1,44 @@
+use std::sync::Mutex;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// Source of the random bytes of CSRF tokens, proofs and nonces, see the `rand_source` method
+/// of the ciphers, such as [`HmacCipher::rand_source`](crate::HmacCipher::rand_source).
+///
+/// Implement it to draw entropy from a hardware or certified RNG. Any [`RngCore`] behind a
+/// [`Mutex`] is a `RandSource` too, so a seeded RNG makes tokens reproducible in tests.
+pub trait RandSource: Send + Sync + 'static {
+    /// Fills `dest` with random bytes.
+    fn fill_bytes(&self, dest: &mut [u8]);
+}
+
+/// The random number generator of the operating system, the default [`RandSource`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsRandSource;
+
+impl RandSource for OsRandSource {
+    #[inline]
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest);
+    }
+}
+
+impl<R> RandSource for Mutex<R>
+where
+    R: RngCore + Send + 'static,
+{
+    #[inline]
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .fill_bytes(dest);
+    }
+}
+
+/// Returns `len` bytes from `rand_source`.
+pub(crate) fn random_bytes(rand_source: &dyn RandSource, len: usize) -> Vec<u8> {
+    let mut bytes = vec![0; len];
+    rand_source.fill_bytes(&mut bytes);
+    bytes
+}
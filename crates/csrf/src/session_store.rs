@@ -1,12 +1,20 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use salvo_core::{Depot, Error, Request, Response};
 use salvo_session::SessionDepotExt;
 
 use super::{CsrfCipher, CsrfStore};
 
 /// A `CsrfStore` implementation that stores the CSRF proof in a session.
+///
+/// With [`rotate_every`](Self::rotate_every), the proof of long-lived sessions is replaced
+/// periodically, and tokens of the previous proof are still accepted for a
+/// [`grace_period`](Self::grace_period), so pages opened before the rotation keep working.
 #[derive(Debug)]
 pub struct SessionStore {
     name: String,
+    rotate_every: Option<Duration>,
+    grace_period: Duration,
 }
 impl Default for SessionStore {
     fn default() -> Self {
@@ -19,17 +27,83 @@ impl SessionStore {
     pub fn new() -> Self {
         Self {
             name: "salvo.csrf".into(),
+            rotate_every: None,
+            grace_period: Duration::from_secs(60 * 60),
         }
     }
+
+    /// Replaces the proof stored in the session once it is older than `interval`. Not set by
+    /// default, so a session keeps the same proof for its whole life.
+    pub fn rotate_every(mut self, interval: Duration) -> Self {
+        self.rotate_every = Some(interval);
+        self
+    }
+
+    /// Sets how long after a rotation tokens of the previous proof are still accepted, one hour
+    /// by default.
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    fn rotated_at_key(&self) -> String {
+        format!("{}.rotated_at", self.name)
+    }
+
+    fn previous_key(&self) -> String {
+        format!("{}.previous", self.name)
+    }
+}
+
+/// Returns the current unix timestamp in seconds.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn split_proof(value: String) -> Option<(String, String)> {
+    value
+        .split_once('.')
+        .map(|(t, p)| (t.into(), p.into()))
 }
 
 impl CsrfStore for SessionStore {
     type Error = Error;
-    async fn load<C: CsrfCipher>(&self, _req: &mut Request, depot: &mut Depot, _cipher: &C) -> Option<(String, String)> {
-        depot
-            .session()
-            .and_then(|s| s.get::<String>(&self.name))
-            .and_then(|s| s.split_once('.').map(|(t, p)| (t.into(), p.into())))
+    async fn load<C: CsrfCipher>(&self, _req: &mut Request, depot: &mut Depot, cipher: &C) -> Option<(String, String)> {
+        let session = depot.session_mut()?;
+        let current = session.get::<String>(&self.name)?;
+        let Some(interval) = self.rotate_every else {
+            return split_proof(current);
+        };
+        let rotated_at = session.get::<u64>(&self.rotated_at_key());
+        if matches!(rotated_at, Some(rotated_at) if now().saturating_sub(rotated_at) < interval.as_secs()) {
+            return split_proof(current);
+        }
+        let (token, proof) = cipher.generate();
+        let rotated = session
+            .insert(&self.previous_key(), current.clone())
+            .and_then(|_| session.insert(&self.rotated_at_key(), now()))
+            .and_then(|_| session.insert(&self.name, format!("{token}.{proof}")));
+        match rotated {
+            Ok(()) => {
+                tracing::debug!("csrf proof rotated");
+                Some((token, proof))
+            }
+            Err(e) => {
+                tracing::error!(error = ?e, "unable to rotate csrf proof");
+                split_proof(current)
+            }
+        }
+    }
+    async fn load_previous(&self, _req: &mut Request, depot: &mut Depot) -> Option<(String, String)> {
+        let session = depot.session()?;
+        let rotated_at = session.get::<u64>(&self.rotated_at_key())?;
+        if now().saturating_sub(rotated_at) >= self.grace_period.as_secs() {
+            return None;
+        }
+        session.get::<String>(&self.previous_key()).and_then(split_proof)
     }
     async fn save(
         &self,
@@ -39,10 +113,111 @@ impl CsrfStore for SessionStore {
         token: &str,
         proof: &str,
     ) -> Result<(), Self::Error> {
-        depot
-            .session_mut()
-            .expect("session must be exist")
-            .insert(&self.name, format!("{token}.{proof}"))?;
+        let session = depot.session_mut().expect("session must be exist");
+        session.insert(&self.name, format!("{token}.{proof}"))?;
+        if self.rotate_every.is_some() {
+            session.insert(&self.rotated_at_key(), now())?;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_extra::test_util::SessionTestHarness;
+    use salvo_session::{MemoryStore, Session, SessionHandler};
+
+    use crate::{Csrf, CsrfDepotExt, HeaderFinder};
+
+    use super::*;
+
+    struct PlainCipher;
+    impl CsrfCipher for PlainCipher {
+        fn verify(&self, token: &str, proof: &str) -> bool {
+            token == proof
+        }
+        fn generate(&self) -> (String, String) {
+            let token = URL_SAFE_NO_PAD.encode(self.random_bytes(16));
+            (token.clone(), token)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotation() {
+        let store = SessionStore::new().rotate_every(Duration::ZERO);
+        let mut req = Request::new();
+        let mut res = Response::new();
+        let mut depot = Depot::new();
+        depot.set_session(Session::new());
+        store.save(&mut req, &mut depot, &mut res, "first", "first").await.unwrap();
+        assert!(store.load_previous(&mut req, &mut depot).await.is_none());
+
+        let (token, _) = store.load(&mut req, &mut depot, &PlainCipher).await.unwrap();
+        assert_ne!(token, "first");
+        let (previous, _) = store.load_previous(&mut req, &mut depot).await.unwrap();
+        assert_eq!(previous, "first");
+
+        let store = store.grace_period(Duration::ZERO);
+        assert!(store.load_previous(&mut req, &mut depot).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_grace_period() {
+        #[handler]
+        async fn get_token(depot: &mut Depot) -> String {
+            depot.csrf_token().unwrap().to_owned()
+        }
+        #[handler]
+        async fn submit() -> &'static str {
+            "submitted"
+        }
+        let harness = |grace_period: Duration| {
+            let session_handler = SessionHandler::builder(
+                MemoryStore::new(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .build()
+            .unwrap();
+            // Every request rotates the proof, so a token is always from the previous proof.
+            let store = SessionStore::new()
+                .rotate_every(Duration::ZERO)
+                .grace_period(grace_period);
+            let csrf = Csrf::new(PlainCipher, store, HeaderFinder::new("x-csrf-token"));
+            let router = Router::new()
+                .hoop(session_handler)
+                .hoop(csrf)
+                .get(get_token)
+                .post(submit);
+            SessionTestHarness::new(router)
+        };
+
+        let mut harness_within = harness(Duration::from_secs(60 * 60));
+        let token = harness_within
+            .send(TestClient::get("http://127.0.0.1:5801/"))
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let mut res = harness_within
+            .send(TestClient::post("http://127.0.0.1:5801/").add_header("x-csrf-token", token, true))
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::OK);
+        assert_eq!(res.take_string().await.unwrap(), "submitted");
+
+        let mut harness_after = harness(Duration::ZERO);
+        let token = harness_after
+            .send(TestClient::get("http://127.0.0.1:5801/"))
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        let res = harness_after
+            .send(TestClient::post("http://127.0.0.1:5801/").add_header("x-csrf-token", token, true))
+            .await;
+        assert_eq!(res.status_code.unwrap(), StatusCode::FORBIDDEN);
+    }
+}
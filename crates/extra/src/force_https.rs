@@ -43,20 +43,99 @@
 //! }
 //! ```
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::IpAddr;
 
 use salvo_core::handler::Skipper;
-use salvo_core::http::header;
+use salvo_core::http::header::{self, HeaderValue};
+use salvo_core::http::headers::{HeaderMapExt, ReferrerPolicy};
 use salvo_core::http::uri::{Scheme, Uri};
-use salvo_core::http::{Request, ResBody, Response};
-use salvo_core::writing::Redirect;
-use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+use salvo_core::http::{Request, ResBody, Response, StatusCode};
+use salvo_core::writing::{Redirect, Text};
+use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler};
 
 /// Middleware for force redirect to http uri.
-#[derive(Default)]
+///
+/// By default requests are redirected with `308 Permanent Redirect`, which tells clients to
+/// repeat the request with the same method and body. Use
+/// [`method_preserving`](Self::method_preserving) and [`permanent`](Self::permanent) to pick
+/// another status code:
+///
+/// | `method_preserving` | `permanent` | Status code |
+/// | --- | --- | --- |
+/// | `true` | `true` | `308 Permanent Redirect` |
+/// | `true` | `false` | `307 Temporary Redirect` |
+/// | `false` | `true` | `301 Moved Permanently` |
+/// | `false` | `false` | `302 Found` |
+///
+/// With `301` and `302`, browsers and most HTTP clients change a `POST` into a `GET` and drop
+/// the request body when following the redirect; `307` and `308` resend the body unchanged.
+///
+/// Requests that already use https pass through, optionally with the
+/// [`upgrade_insecure_requests`](Self::upgrade_insecure_requests) and
+/// [`referrer_policy`](Self::referrer_policy) headers added to their responses.
+///
+/// Set [`max_redirects`](Self::max_redirects) to catch redirect loops, which happen when a
+/// proxy terminating TLS forwards https requests as http.
+///
+/// With [`api_response`](Self::api_response), API clients get `426 Upgrade Required` instead of
+/// a redirect.
+///
+/// When added to a [`Service`](salvo_core::Service) serving several hosts,
+/// [`host_policy`](Self::host_policy) picks an [`HttpsPolicy`] per host.
 pub struct ForceHttps {
     https_port: Option<u16>,
+    external_base: Option<Uri>,
+    method_preserving: bool,
+    permanent: bool,
+    upgrade_insecure_requests: bool,
+    referrer_policy: Option<ReferrerPolicy>,
+    skip_local: bool,
+    max_redirects: Option<u32>,
+    canonicalize_host_on_secure: bool,
+    api_response: bool,
+    host_policies: HashMap<String, HttpsPolicy>,
     skipper: Option<Box<dyn Skipper>>,
 }
+
+/// How [`ForceHttps`] treats the requests of a host, see [`ForceHttps::host_policy`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HttpsPolicy {
+    /// Http requests are redirected to https, as configured on the middleware.
+    Redirect,
+    /// Requests pass through untouched, for example for internal health checks over http.
+    Ignore,
+    /// Http requests pass through, and https responses get a `Strict-Transport-Security`
+    /// header with a `max-age` of one year.
+    HstsOnly,
+}
+
+/// Name of the cookie counting the recent redirects of a client, see
+/// [`ForceHttps::max_redirects`].
+pub const REDIRECT_COUNT_COOKIE: &str = "salvo.force_https.redirects";
+/// How long, in seconds, the redirect count is kept by the client.
+const REDIRECT_COUNT_MAX_AGE: u32 = 60;
+/// `Strict-Transport-Security` header sent for hosts with [`HttpsPolicy::HstsOnly`].
+const HSTS_HEADER: &str = "max-age=31536000";
+impl Default for ForceHttps {
+    fn default() -> Self {
+        Self {
+            https_port: None,
+            external_base: None,
+            method_preserving: true,
+            permanent: true,
+            upgrade_insecure_requests: false,
+            referrer_policy: None,
+            skip_local: false,
+            max_redirects: None,
+            canonicalize_host_on_secure: false,
+            api_response: false,
+            host_policies: HashMap::new(),
+            skipper: None,
+        }
+    }
+}
 impl ForceHttps {
     /// Create new `ForceHttps` middleware.
     pub fn new() -> Self {
@@ -71,6 +150,154 @@ impl ForceHttps {
         }
     }
 
+    /// Specify the public base url that requests are redirected to, such as
+    /// `https://public.example.com`.
+    ///
+    /// The redirect keeps the request path and query but takes both host and port from
+    /// this url, ignoring the incoming `Host` header and [`https_port`](Self::https_port).
+    /// This is useful behind proxies that rewrite `Host` to an internal name. A path in
+    /// the base url is used as a prefix of the request path.
+    ///
+    /// Returns an error if `base` is not an absolute `https` url.
+    pub fn external_base(self, base: impl AsRef<str>) -> Result<Self, Error> {
+        let base = base.as_ref();
+        let uri = base.parse::<Uri>()?;
+        if uri.scheme() != Some(&Scheme::HTTPS) || uri.authority().is_none() {
+            return Err(Error::other(format!(
+                "external base url `{base}` must be an absolute https url"
+            )));
+        }
+        Ok(Self {
+            external_base: Some(uri),
+            ..self
+        })
+    }
+
+    /// Whether the redirect must keep the request method and body, `true` by default.
+    ///
+    /// When `true`, `308` or `307` is used, otherwise `301` or `302`.
+    pub fn method_preserving(self, method_preserving: bool) -> Self {
+        Self {
+            method_preserving,
+            ..self
+        }
+    }
+
+    /// Whether the redirect is permanent and may be cached by clients, `true` by default.
+    ///
+    /// When `true`, `308` or `301` is used, otherwise `307` or `302`.
+    pub fn permanent(self, permanent: bool) -> Self {
+        Self { permanent, ..self }
+    }
+
+    /// Whether https responses get a `Content-Security-Policy: upgrade-insecure-requests`
+    /// header, `false` by default.
+    ///
+    /// It makes browsers load the http resources of a page over https, which avoids mixed
+    /// content. Handlers called after this middleware can still set their own
+    /// `Content-Security-Policy` header, which replaces this one.
+    pub fn upgrade_insecure_requests(self, upgrade_insecure_requests: bool) -> Self {
+        Self {
+            upgrade_insecure_requests,
+            ..self
+        }
+    }
+
+    /// Sets the `Referrer-Policy` header added to https responses, such as
+    /// [`ReferrerPolicy::STRICT_ORIGIN_WHEN_CROSS_ORIGIN`]. No header is added by default.
+    pub fn referrer_policy(self, policy: ReferrerPolicy) -> Self {
+        Self {
+            referrer_policy: Some(policy),
+            ..self
+        }
+    }
+
+    /// Whether requests to local hosts are not redirected, `false` by default.
+    ///
+    /// Local hosts are `localhost` and its subdomains, loopback addresses such as `127.0.0.1`
+    /// and `::1`, and the private IPv4 ranges of RFC 1918, such as `192.168.0.1`. This is
+    /// meant for development, without a separate configuration or a [`skipper`](Self::skipper).
+    pub fn skip_local(self, skip_local: bool) -> Self {
+        Self { skip_local, ..self }
+    }
+
+    /// Responds with `400 Bad Request` instead of redirecting once a client has been redirected
+    /// `max` times within a minute without reaching https. Not set by default.
+    ///
+    /// Redirects are counted with the short-lived [`REDIRECT_COUNT_COOKIE`] cookie, which is
+    /// removed when the client reaches https. A client still sent over http after `max`
+    /// redirects is caught in a loop, usually because a proxy terminating TLS forwards requests
+    /// as http: the response explains this instead of letting the client redirect forever.
+    pub fn max_redirects(self, max: u32) -> Self {
+        Self {
+            max_redirects: Some(max),
+            ..self
+        }
+    }
+
+    /// Whether https requests to another host than the one of
+    /// [`external_base`](Self::external_base) are redirected to it too, `false` by default.
+    ///
+    /// This canonicalizes the host, such as `www.example.com` to `example.com`, in the same
+    /// middleware as the scheme. Requests already using the host of the external base, with or
+    /// without the default port, are not redirected, so this can't loop. Without an external
+    /// base, this has no effect.
+    pub fn canonicalize_host_on_secure(self, canonicalize_host_on_secure: bool) -> Self {
+        Self {
+            canonicalize_host_on_secure,
+            ..self
+        }
+    }
+
+    /// Whether API requests over http get `426 Upgrade Required` instead of a redirect, `false`
+    /// by default.
+    ///
+    /// A request is an API request when its `Accept` header asks for `application/json` but not
+    /// for `text/html`. Non-browser clients usually don't follow redirects, or follow them
+    /// after sending credentials over http: the `426` response, with `Upgrade: TLS/1.2, HTTP/1.1`
+    /// and `Connection: Upgrade` headers and a JSON body holding the https url, tells them
+    /// explicitly to reconnect over TLS. Browsers are still redirected.
+    pub fn api_response(self, api_response: bool) -> Self {
+        Self { api_response, ..self }
+    }
+
+    /// Sets the policy for requests to `host`. Hosts without a policy are redirected.
+    ///
+    /// The host is taken from the request url or its `Host` header and compared without port and
+    /// case, so `Example.com:8080` matches the `example.com` policy.
+    pub fn host_policy(mut self, host: impl AsRef<str>, policy: HttpsPolicy) -> Self {
+        self.host_policies.insert(normalize_host(host.as_ref()), policy);
+        self
+    }
+
+    /// Sets the policies for several hosts at once, see [`host_policy`](Self::host_policy).
+    pub fn host_policies<H: AsRef<str>>(self, policies: impl IntoIterator<Item = (H, HttpsPolicy)>) -> Self {
+        policies
+            .into_iter()
+            .fold(self, |force_https, (host, policy)| force_https.host_policy(host, policy))
+    }
+
+    fn policy_for(&self, req: &Request) -> HttpsPolicy {
+        if self.host_policies.is_empty() {
+            return HttpsPolicy::Redirect;
+        }
+        req.uri()
+            .host()
+            .map(ToOwned::to_owned)
+            .or_else(|| req.header::<String>(header::HOST))
+            .and_then(|host| self.host_policies.get(&normalize_host(&host)).copied())
+            .unwrap_or(HttpsPolicy::Redirect)
+    }
+
+    fn status_code(&self) -> StatusCode {
+        match (self.method_preserving, self.permanent) {
+            (true, true) => StatusCode::PERMANENT_REDIRECT,
+            (true, false) => StatusCode::TEMPORARY_REDIRECT,
+            (false, true) => StatusCode::MOVED_PERMANENTLY,
+            (false, false) => StatusCode::FOUND,
+        }
+    }
+
     /// Uses a closure to determine if a request should be redirect.
     pub fn skipper(self, skipper: impl Skipper) -> Self {
         Self {
@@ -83,27 +310,199 @@ impl ForceHttps {
 #[async_trait]
 impl Handler for ForceHttps {
     async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
-        if req.uri().scheme() == Some(&Scheme::HTTPS)
-            || self
-                .skipper
-                .as_ref()
-                .map(|skipper| skipper.skipped(req, depot))
-                .unwrap_or(false)
+        if self
+            .skipper
+            .as_ref()
+            .map(|skipper| skipper.skipped(req, depot))
+            .unwrap_or(false)
         {
             return;
         }
-        if let Some(host) = req.header::<String>(header::HOST) {
+        let secure = req.uri().scheme() == Some(&Scheme::HTTPS);
+        match self.policy_for(req) {
+            HttpsPolicy::Redirect => {}
+            HttpsPolicy::Ignore => return,
+            HttpsPolicy::HstsOnly if secure => {
+                res.headers_mut().insert(
+                    header::STRICT_TRANSPORT_SECURITY,
+                    HeaderValue::from_static(HSTS_HEADER),
+                );
+            }
+            HttpsPolicy::HstsOnly => return,
+        }
+        if secure {
+            if let (true, Some(base)) = (self.canonicalize_host_on_secure, &self.external_base) {
+                let host = req
+                    .uri()
+                    .authority()
+                    .map(|authority| authority.as_str().to_owned())
+                    .or_else(|| req.header::<String>(header::HOST));
+                if host.is_some_and(|host| !is_canonical_host(&host, base)) {
+                    if let Some(redirect) = external_redirect_uri(base, req.uri())
+                        .and_then(|uri| Redirect::with_status_code(self.status_code(), uri).ok())
+                    {
+                        res.body(ResBody::None);
+                        res.render(redirect);
+                        ctrl.skip_rest();
+                        return;
+                    }
+                }
+            }
+            if self.upgrade_insecure_requests {
+                res.headers_mut().insert(
+                    header::CONTENT_SECURITY_POLICY,
+                    HeaderValue::from_static("upgrade-insecure-requests"),
+                );
+            }
+            if let Some(policy) = &self.referrer_policy {
+                res.headers_mut().typed_insert(policy.clone());
+            }
+            if self.max_redirects.is_some() && redirect_count(req).is_some() {
+                let removal = format!("{REDIRECT_COUNT_COOKIE}=; Max-Age=0; Path=/");
+                if let Ok(removal) = HeaderValue::from_str(&removal) {
+                    res.headers_mut().append(header::SET_COOKIE, removal);
+                }
+            }
+            return;
+        }
+        if self.skip_local {
+            let host = req
+                .uri()
+                .host()
+                .map(ToOwned::to_owned)
+                .or_else(|| req.header::<String>(header::HOST));
+            if host.is_some_and(|host| is_local_host(&host)) {
+                return;
+            }
+        }
+        let uri = if let Some(base) = &self.external_base {
+            external_redirect_uri(base, req.uri())
+        } else if let Some(host) = req.header::<String>(header::HOST) {
             let host = redirect_host(&host, self.https_port);
             let uri_parts = std::mem::take(req.uri_mut()).into_parts();
             let mut builder = Uri::builder().scheme(Scheme::HTTPS).authority(&*host);
             if let Some(path_and_query) = uri_parts.path_and_query {
                 builder = builder.path_and_query(path_and_query);
             }
-            if let Ok(uri) = builder.build() {
-                res.body(ResBody::None);
-                res.render(Redirect::permanent(uri));
-                ctrl.skip_rest();
+            builder.build().ok()
+        } else {
+            None
+        };
+        if self.api_response && is_api_request(req) {
+            res.status_code(StatusCode::UPGRADE_REQUIRED);
+            res.headers_mut()
+                .insert(header::UPGRADE, HeaderValue::from_static("TLS/1.2, HTTP/1.1"));
+            res.headers_mut()
+                .insert(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+            let body = serde_json::json!({
+                "error": "https required",
+                "location": uri.map(|uri| uri.to_string()),
+            });
+            res.render(Text::Json(body.to_string()));
+            ctrl.skip_rest();
+            return;
+        }
+        let redirects = redirect_count(req).unwrap_or_default();
+        if matches!(self.max_redirects, Some(max) if redirects >= max) {
+            tracing::warn!(redirects, "redirect loop to https detected");
+            res.status_code(StatusCode::BAD_REQUEST);
+            res.render(Text::Plain(
+                "The request was redirected to https but keeps arriving over http. \
+                 If the server is behind a proxy that terminates TLS, the proxy must forward \
+                 the original scheme, for example with the X-Forwarded-Proto header.",
+            ));
+            ctrl.skip_rest();
+            return;
+        }
+        if let Some(redirect) = uri.and_then(|uri| Redirect::with_status_code(self.status_code(), uri).ok()) {
+            res.body(ResBody::None);
+            res.render(redirect);
+            if self.max_redirects.is_some() {
+                let cookie = format!(
+                    "{REDIRECT_COUNT_COOKIE}={}; Max-Age={REDIRECT_COUNT_MAX_AGE}; Path=/; HttpOnly; SameSite=Lax",
+                    redirects + 1
+                );
+                if let Ok(cookie) = HeaderValue::from_str(&cookie) {
+                    res.headers_mut().append(header::SET_COOKIE, cookie);
+                }
             }
+            ctrl.skip_rest();
+        }
+    }
+}
+
+fn external_redirect_uri(base: &Uri, uri: &Uri) -> Option<Uri> {
+    let prefix = base.path().trim_end_matches('/');
+    let path_and_query = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let mut builder = Uri::builder().scheme(Scheme::HTTPS);
+    if let Some(authority) = base.authority() {
+        builder = builder.authority(authority.clone());
+    }
+    builder.path_and_query(format!("{prefix}{path_and_query}")).build().ok()
+}
+
+/// Whether `host`, with an optional port, is the host of `base`.
+fn is_canonical_host(host: &str, base: &Uri) -> bool {
+    let Some(authority) = base.authority() else {
+        return true;
+    };
+    let host = host.strip_suffix(":443").unwrap_or(host);
+    let canonical = authority.as_str();
+    let canonical = canonical.strip_suffix(":443").unwrap_or(canonical);
+    host.eq_ignore_ascii_case(canonical)
+}
+
+/// Whether the `Accept` header of `req` asks for JSON but not for HTML.
+fn is_api_request(req: &Request) -> bool {
+    let accept = req
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|item| item.split(';').next())
+        .map(|mime| mime.trim().to_ascii_lowercase())
+        .collect::<Vec<_>>();
+    accept.iter().any(|mime| mime == "application/json") && !accept.iter().any(|mime| mime == "text/html")
+}
+
+/// Returns the redirect count sent in the [`REDIRECT_COUNT_COOKIE`] cookie.
+fn redirect_count(req: &Request) -> Option<u32> {
+    req.headers()
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == REDIRECT_COUNT_COOKIE)
+        .and_then(|(_, count)| count.parse().ok())
+}
+
+/// Returns `host` without its port.
+fn strip_port(host: &str) -> &str {
+    if let Some(rest) = host.strip_prefix('[') {
+        rest.split(']').next().unwrap_or_default()
+    } else if host.matches(':').count() == 1 {
+        host.split(':').next().unwrap_or_default()
+    } else {
+        host
+    }
+}
+
+/// Returns `host` without its port and trailing dot, in lowercase.
+fn normalize_host(host: &str) -> String {
+    strip_port(host).trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `host`, with an optional port, is a loopback or private host.
+fn is_local_host(host: &str) -> bool {
+    let hostname = strip_port(host);
+    match hostname.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private(),
+        Ok(IpAddr::V6(ip)) => ip.is_loopback(),
+        Err(_) => {
+            let hostname = hostname.trim_end_matches('.').to_ascii_lowercase();
+            hostname == "localhost" || hostname.ends_with(".localhost")
         }
     }
 }
@@ -118,9 +517,12 @@ fn redirect_host(host: &str, https_port: Option<u16>) -> Cow<'_, str> {
 
 #[cfg(test)]
 mod tests {
-    use salvo_core::http::header::{HOST, LOCATION};
+    use salvo_core::http::header::{
+        ACCEPT, CONNECTION, CONTENT_SECURITY_POLICY, COOKIE, HOST, LOCATION, REFERRER_POLICY, SET_COOKIE,
+        STRICT_TRANSPORT_SECURITY, UPGRADE,
+    };
     use salvo_core::prelude::*;
-    use salvo_core::test::TestClient;
+    use salvo_core::test::{ResponseExt, TestClient};
 
     use super::*;
 
@@ -133,6 +535,171 @@ mod tests {
         assert_eq!(redirect_host("example.com", None), "example.com");
     }
 
+    #[test]
+    fn test_is_local_host() {
+        for host in [
+            "localhost",
+            "localhost:5800",
+            "app.localhost",
+            "127.0.0.1:5800",
+            "[::1]:5800",
+            "::1",
+            "10.1.2.3",
+            "192.168.1.10:8080",
+            "172.16.0.1",
+        ] {
+            assert!(is_local_host(host), "{host}");
+        }
+        for host in ["example.com", "8.8.8.8:80", "172.32.0.1", "[2001:db8::1]:443", "localhost.example.com"] {
+            assert!(!is_local_host(host), "{host}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_local() {
+        let router = Router::with_hoop(ForceHttps::new().skip_local(true)).goal(hello);
+        let response = TestClient::get("http://localhost:5800/")
+            .add_header(HOST, "localhost:5800", true)
+            .send(router)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+
+        let router = Router::with_hoop(ForceHttps::new().skip_local(true)).goal(hello);
+        let response = TestClient::get("http://example.com/")
+            .add_header(HOST, "example.com", true)
+            .send(router)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::PERMANENT_REDIRECT));
+    }
+
+    #[tokio::test]
+    async fn test_host_policies() {
+        let service = Service::new(Router::new().goal(hello)).hoop(ForceHttps::new().host_policies([
+            ("health.internal", HttpsPolicy::Ignore),
+            ("Legacy.example.com", HttpsPolicy::HstsOnly),
+        ]));
+        let response = TestClient::get("http://health.internal:8080/")
+            .add_header(HOST, "health.internal:8080", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+
+        let response = TestClient::get("http://legacy.example.com/")
+            .add_header(HOST, "legacy.example.com", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+        assert!(response.headers().get(STRICT_TRANSPORT_SECURITY).is_none());
+        let response = TestClient::get("https://legacy.example.com/").send(&service).await;
+        assert_eq!(
+            response.headers().get(STRICT_TRANSPORT_SECURITY).unwrap(),
+            "max-age=31536000"
+        );
+
+        let response = TestClient::get("http://example.com/")
+            .add_header(HOST, "example.com", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::PERMANENT_REDIRECT));
+    }
+
+    #[tokio::test]
+    async fn test_max_redirects() {
+        let service = Service::new(Router::with_hoop(ForceHttps::new().max_redirects(2)).goal(hello));
+        let mut cookie = String::new();
+        for _ in 0..2 {
+            let response = TestClient::get("http://example.com/")
+                .add_header(HOST, "example.com", true)
+                .add_header(COOKIE, &cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(response.status_code, Some(StatusCode::PERMANENT_REDIRECT));
+            let set_cookie = response.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+            cookie = set_cookie.split(';').next().unwrap().to_owned();
+        }
+        assert_eq!(cookie, "salvo.force_https.redirects=2");
+        let response = TestClient::get("http://example.com/")
+            .add_header(HOST, "example.com", true)
+            .add_header(COOKIE, &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::BAD_REQUEST));
+
+        let response = TestClient::get("https://example.com/")
+            .add_header(COOKIE, &cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+        assert!(response.headers().get(SET_COOKIE).unwrap().to_str().unwrap().contains("Max-Age=0"));
+    }
+
+    #[tokio::test]
+    async fn test_canonicalize_host_on_secure() {
+        let force_https = || {
+            ForceHttps::new()
+                .external_base("https://example.com")
+                .unwrap()
+                .canonicalize_host_on_secure(true)
+        };
+        let router = Router::with_hoop(force_https()).goal(hello);
+        let response = TestClient::get("https://www.example.com/?b=c").send(router).await;
+        assert_eq!(response.status_code, Some(StatusCode::PERMANENT_REDIRECT));
+        assert_eq!(
+            response.headers().get(LOCATION),
+            Some(&"https://example.com/?b=c".parse().unwrap())
+        );
+
+        for url in ["https://example.com/", "https://EXAMPLE.com:443/"] {
+            let router = Router::with_hoop(force_https()).goal(hello);
+            let response = TestClient::get(url).send(router).await;
+            assert_eq!(response.status_code, Some(StatusCode::OK), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_response() {
+        let service = Service::new(Router::with_hoop(ForceHttps::new().api_response(true)).goal(hello));
+        let mut response = TestClient::get("http://example.com/?a=b")
+            .add_header(HOST, "example.com", true)
+            .add_header(ACCEPT, "application/json", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::UPGRADE_REQUIRED));
+        assert_eq!(response.headers().get(UPGRADE).unwrap(), "TLS/1.2, HTTP/1.1");
+        assert_eq!(response.headers().get(CONNECTION).unwrap(), "Upgrade");
+        let body: serde_json::Value = serde_json::from_str(&response.take_string().await.unwrap()).unwrap();
+        assert_eq!(body["location"], "https://example.com/?a=b");
+
+        let response = TestClient::get("http://example.com/")
+            .add_header(HOST, "example.com", true)
+            .add_header(ACCEPT, "text/html,application/json;q=0.9", true)
+            .send(&service)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::PERMANENT_REDIRECT));
+    }
+
+    #[test]
+    fn test_external_redirect_uri() {
+        let base: Uri = "https://public.example.com".parse().unwrap();
+        let uri: Uri = "http://backend.local:8080/a/b?c=d".parse().unwrap();
+        assert_eq!(
+            external_redirect_uri(&base, &uri).unwrap(),
+            "https://public.example.com/a/b?c=d"
+        );
+        let base: Uri = "https://public.example.com:8443/app/".parse().unwrap();
+        assert_eq!(
+            external_redirect_uri(&base, &uri).unwrap(),
+            "https://public.example.com:8443/app/a/b?c=d"
+        );
+    }
+
+    #[test]
+    fn test_external_base_requires_https() {
+        assert!(ForceHttps::new().external_base("http://public.example.com").is_err());
+        assert!(ForceHttps::new().external_base("/app").is_err());
+        assert!(ForceHttps::new().external_base("https://public.example.com").is_ok());
+    }
+
     #[handler]
     async fn hello() -> &'static str {
         "Hello World"
@@ -150,4 +717,60 @@ mod tests {
             Some(&"https://127.0.0.1:1234/".parse().unwrap())
         );
     }
+
+    #[tokio::test]
+    async fn test_redirect_status_code() {
+        let router = Router::with_hoop(ForceHttps::new().method_preserving(false).permanent(false)).goal(hello);
+        let response = TestClient::post("http://127.0.0.1:5800/")
+            .add_header(HOST, "127.0.0.1:5800", true)
+            .send(router)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::FOUND));
+
+        let router = Router::with_hoop(ForceHttps::new().permanent(false)).goal(hello);
+        let response = TestClient::post("http://127.0.0.1:5800/")
+            .add_header(HOST, "127.0.0.1:5800", true)
+            .send(router)
+            .await;
+        assert_eq!(response.status_code, Some(StatusCode::TEMPORARY_REDIRECT));
+    }
+
+    #[tokio::test]
+    async fn test_https_response_headers() {
+        let force_https = ForceHttps::new()
+            .upgrade_insecure_requests(true)
+            .referrer_policy(ReferrerPolicy::STRICT_ORIGIN_WHEN_CROSS_ORIGIN);
+        let router = Router::with_hoop(force_https).goal(hello);
+        let response = TestClient::get("https://127.0.0.1:5800/").send(router).await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+        assert_eq!(
+            response.headers().get(CONTENT_SECURITY_POLICY).unwrap(),
+            "upgrade-insecure-requests"
+        );
+        assert_eq!(
+            response.headers().get(REFERRER_POLICY).unwrap(),
+            "strict-origin-when-cross-origin"
+        );
+
+        let router = Router::with_hoop(ForceHttps::new()).goal(hello);
+        let response = TestClient::get("https://127.0.0.1:5800/").send(router).await;
+        assert!(response.headers().get(CONTENT_SECURITY_POLICY).is_none());
+        assert!(response.headers().get(REFERRER_POLICY).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_skipper_on_secure() {
+        let force_https = ForceHttps::new()
+            .external_base("https://example.com")
+                .unwrap()
+            .canonicalize_host_on_secure(true)
+            .upgrade_insecure_requests(true)
+            .referrer_policy(ReferrerPolicy::STRICT_ORIGIN_WHEN_CROSS_ORIGIN)
+            .skipper(|_: &mut Request, _: &Depot| true);
+        let router = Router::with_hoop(force_https).goal(hello);
+        let response = TestClient::get("https://www.example.com/").send(router).await;
+        assert_eq!(response.status_code, Some(StatusCode::OK));
+        assert!(response.headers().get(CONTENT_SECURITY_POLICY).is_none());
+        assert!(response.headers().get(REFERRER_POLICY).is_none());
+    }
 }
@@ -16,9 +16,12 @@
 //! | [`concurrency-limiter`](concurrency_limiter) | Middleware for limiting concurrency |
 //! | [`force-https`](force_https) | Middleware for forcing HTTPS |
 //! | [`logging`] | Middleware for logging requests and responses |
+//! | [`logout`] | Handler destroying the session of `salvo_session`, not part of `full` |
 //! | [`request-id`](request_id) | Middleware for setting a request ID |
+//! | [`security-headers`](security_headers) | Middleware for adding security headers |
 //! | [`size-limiter`](size_limiter) | Middleware for limiting request size |
 //! | [`sse`] | Server-Sent Events (SSE) middleware |
+//! | [`test-util`](test_util) | Helpers for testing routes, not part of `full` |
 //! | [`timeout`] | Middleware for setting a timeout |
 //! | [`trailing-slash`](trailing_slash) | Middleware for handling trailing slashes |
 //! | [`tower-compat`](tower_compat) | Adapters for `tower::Layer` and `tower::Service` |
@@ -53,6 +56,10 @@ cfg_feature! {
     #![feature = "logging"]
     pub mod logging;
 }
+cfg_feature! {
+    #![feature = "logout"]
+    pub mod logout;
+}
 cfg_feature! {
     #![feature = "sse"]
     pub mod sse;
@@ -85,6 +92,14 @@ cfg_feature! {
     #![feature = "request-id"]
     pub mod request_id;
 }
+cfg_feature! {
+    #![feature = "security-headers"]
+    pub mod security_headers;
+}
+cfg_feature! {
+    #![feature = "test-util"]
+    pub mod test_util;
+}
 cfg_feature! {
     #![feature ="tower-compat"]
     pub mod tower_compat;
@@ -0,0 +1,107 @@
+//! Logout handler for routes using [`salvo_session`].
+//!
+//! [`Logout`] destroys the current session and redirects, which is what most logout routes do.
+//!
+//! # Example
+//!
+//! ```
+//! use salvo_core::prelude::*;
+//! use salvo_extra::logout::logout_handler;
+//! use salvo_session::{MemoryStore, SessionHandler};
+//!
+//! let session_handler = SessionHandler::builder(
+//!     MemoryStore::new(),
+//!     b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+//! )
+//! .build()
+//! .unwrap();
+//! let router = Router::new()
+//!     .hoop(session_handler)
+//!     .push(Router::with_path("logout").post(logout_handler("/")));
+//! ```
+use salvo_core::writing::Redirect;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+use salvo_session::SessionDepotExt;
+
+/// Handler that destroys the current session and redirects, for logout routes.
+///
+/// The redirect uses `303 See Other`, so a logout form posted to this handler lands on a `GET`
+/// of the target. Requests without a session are redirected too. It must be added after
+/// [`SessionHandler`](salvo_session::SessionHandler), which removes the session cookie and the
+/// stored session once the session is destroyed.
+#[derive(Clone, Debug)]
+pub struct Logout {
+    redirect_to: String,
+}
+
+impl Logout {
+    /// Create a new `Logout` redirecting to `redirect_to`.
+    #[inline]
+    pub fn new(redirect_to: impl Into<String>) -> Self {
+        Self {
+            redirect_to: redirect_to.into(),
+        }
+    }
+}
+
+/// Create a [`Logout`] handler redirecting to `redirect_to`.
+#[inline]
+pub fn logout_handler(redirect_to: impl Into<String>) -> Logout {
+    Logout::new(redirect_to)
+}
+
+#[async_trait]
+impl Handler for Logout {
+    async fn handle(&self, _req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        if !depot.destroy_session() {
+            tracing::debug!("no session to log out of");
+        }
+        res.render(Redirect::other(self.redirect_to.clone()));
+        ctrl.skip_rest();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::{LOCATION, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+    use salvo_session::{MemoryStore, SessionHandler};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_logout_handler() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_on_empty(false)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("logout").post(logout_handler("/")));
+        let service = Service::new(router);
+
+        let res = TestClient::post("http://127.0.0.1:5800/login").send(&service).await;
+        let cookie = res.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap().to_owned();
+        let res = TestClient::post("http://127.0.0.1:5800/logout")
+            .add_header("cookie", cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::SEE_OTHER));
+        assert_eq!(res.headers().get(LOCATION).unwrap(), "/");
+        let removal = res.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(removal.starts_with("salvo.session.id=;"));
+
+        let res = TestClient::post("http://127.0.0.1:5800/logout").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::SEE_OTHER));
+    }
+}
@@ -0,0 +1,221 @@
+//! Middleware for adding common security headers to responses.
+//!
+//! By default [`SecurityHeaders`] adds `X-Content-Type-Options: nosniff`,
+//! `X-Frame-Options: DENY` and `Referrer-Policy: strict-origin-when-cross-origin`. Each header
+//! can be turned off or changed, and a `Content-Security-Policy` can be added.
+//!
+//! Headers already set by a handler are kept, so a route can still use its own policy.
+//!
+//! Example:
+//!
+//! ```no_run
+//! use salvo_core::prelude::*;
+//! use salvo_extra::force_https::ForceHttps;
+//! use salvo_extra::security_headers::SecurityHeaders;
+//!
+//! #[handler]
+//! async fn hello() -> &'static str {
+//!     "hello"
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let router = Router::new().get(hello);
+//!     let service = Service::new(router)
+//!         .hoop(ForceHttps::new().https_port(5443))
+//!         .hoop(SecurityHeaders::new().content_security_policy(Some("default-src 'self'")));
+//!
+//!     let acceptor = TcpListener::new("0.0.0.0:5800").bind().await;
+//!     Server::new(acceptor).serve(service).await;
+//! }
+//! ```
+use salvo_core::http::header::{self, HeaderName, HeaderValue};
+use salvo_core::http::{Request, Response};
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler};
+
+/// Value of the `X-Frame-Options` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameOptions {
+    /// The page can't be displayed in a frame.
+    Deny,
+    /// The page can only be displayed in a frame on the same origin.
+    SameOrigin,
+}
+
+impl FrameOptions {
+    fn header_value(self) -> HeaderValue {
+        match self {
+            Self::Deny => HeaderValue::from_static("DENY"),
+            Self::SameOrigin => HeaderValue::from_static("SAMEORIGIN"),
+        }
+    }
+}
+
+/// Middleware that adds security headers to responses.
+///
+/// The headers are added after the rest of the request is handled and only if the response
+/// doesn't have them yet.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    content_type_options: bool,
+    frame_options: Option<FrameOptions>,
+    referrer_policy: Option<HeaderValue>,
+    content_security_policy: Option<HeaderValue>,
+}
+impl Default for SecurityHeaders {
+    fn default() -> Self {
+        Self {
+            content_type_options: true,
+            frame_options: Some(FrameOptions::Deny),
+            referrer_policy: Some(HeaderValue::from_static("strict-origin-when-cross-origin")),
+            content_security_policy: None,
+        }
+    }
+}
+impl SecurityHeaders {
+    /// Create new `SecurityHeaders` middleware.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Whether to add `X-Content-Type-Options: nosniff`, `true` by default.
+    ///
+    /// It stops browsers from guessing a content type other than the declared one.
+    pub fn content_type_options(self, content_type_options: bool) -> Self {
+        Self {
+            content_type_options,
+            ..self
+        }
+    }
+
+    /// Sets the `X-Frame-Options` header, `DENY` by default. `None` doesn't add it.
+    ///
+    /// Browsers that support the `frame-ancestors` directive of
+    /// [`content_security_policy`](Self::content_security_policy) ignore this header when both
+    /// are present, so set both the same way.
+    pub fn frame_options(self, frame_options: Option<FrameOptions>) -> Self {
+        Self { frame_options, ..self }
+    }
+
+    /// Sets the `Referrer-Policy` header, `strict-origin-when-cross-origin` by default. `None`
+    /// doesn't add it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` is not a valid header value.
+    pub fn referrer_policy(self, policy: Option<&str>) -> Self {
+        Self {
+            referrer_policy: policy.map(|policy| header_value("referrer policy", policy)),
+            ..self
+        }
+    }
+
+    /// Sets the `Content-Security-Policy` header, such as `default-src 'self'`. No header is
+    /// added by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `policy` is not a valid header value.
+    pub fn content_security_policy(self, policy: Option<&str>) -> Self {
+        Self {
+            content_security_policy: policy.map(|policy| header_value("content security policy", policy)),
+            ..self
+        }
+    }
+
+    fn headers(&self) -> impl Iterator<Item = (HeaderName, HeaderValue)> + '_ {
+        let content_type_options = self
+            .content_type_options
+            .then(|| (header::X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")));
+        let frame_options = self
+            .frame_options
+            .map(|options| (header::X_FRAME_OPTIONS, options.header_value()));
+        let referrer_policy = self
+            .referrer_policy
+            .clone()
+            .map(|policy| (header::REFERRER_POLICY, policy));
+        let content_security_policy = self
+            .content_security_policy
+            .clone()
+            .map(|policy| (header::CONTENT_SECURITY_POLICY, policy));
+        content_type_options
+            .into_iter()
+            .chain(frame_options)
+            .chain(referrer_policy)
+            .chain(content_security_policy)
+    }
+}
+
+fn header_value(what: &str, value: &str) -> HeaderValue {
+    HeaderValue::from_str(value).unwrap_or_else(|e| panic!("invalid {what} `{value}`: {e}"))
+}
+
+#[async_trait]
+impl Handler for SecurityHeaders {
+    async fn handle(&self, req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+        ctrl.call_next(req, depot, res).await;
+        let headers = res.headers_mut();
+        for (name, value) in self.headers() {
+            headers.entry(name).or_insert(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::{CONTENT_SECURITY_POLICY, REFERRER_POLICY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS};
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+
+    #[handler]
+    async fn hello() -> &'static str {
+        "Hello World"
+    }
+    #[handler]
+    async fn embeddable(res: &mut Response) -> &'static str {
+        res.headers_mut()
+            .insert(X_FRAME_OPTIONS, HeaderValue::from_static("SAMEORIGIN"));
+        "Hello World"
+    }
+
+    #[tokio::test]
+    async fn test_default_headers() {
+        let router = Router::with_hoop(SecurityHeaders::new()).goal(hello);
+        let response = TestClient::get("http://127.0.0.1:5800/").send(router).await;
+        assert_eq!(response.headers().get(X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+        assert_eq!(response.headers().get(X_FRAME_OPTIONS).unwrap(), "DENY");
+        assert_eq!(
+            response.headers().get(REFERRER_POLICY).unwrap(),
+            "strict-origin-when-cross-origin"
+        );
+        assert!(response.headers().get(CONTENT_SECURITY_POLICY).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_configured_headers() {
+        let security_headers = SecurityHeaders::new()
+            .content_type_options(false)
+            .frame_options(Some(FrameOptions::SameOrigin))
+            .referrer_policy(None)
+            .content_security_policy(Some("default-src 'self'"));
+        let router = Router::with_hoop(security_headers).goal(hello);
+        let response = TestClient::get("http://127.0.0.1:5800/").send(router).await;
+        assert!(response.headers().get(X_CONTENT_TYPE_OPTIONS).is_none());
+        assert_eq!(response.headers().get(X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+        assert!(response.headers().get(REFERRER_POLICY).is_none());
+        assert_eq!(
+            response.headers().get(CONTENT_SECURITY_POLICY).unwrap(),
+            "default-src 'self'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_keep_handler_headers() {
+        let router = Router::with_hoop(SecurityHeaders::new()).goal(embeddable);
+        let response = TestClient::get("http://127.0.0.1:5800/").send(router).await;
+        assert_eq!(response.headers().get(X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+        assert_eq!(response.headers().get_all(X_FRAME_OPTIONS).iter().count(), 1);
+    }
+}
@@ -0,0 +1,166 @@
+//! Helpers for testing routes.
+//!
+//! [`SessionTestHarness`] sends test requests to a [`Service`] while keeping its cookies, like a
+//! browser, so routes that depend on a session can be tested without extracting `Set-Cookie`
+//! headers by hand.
+//!
+//! # Example
+//!
+//! ```
+//! use salvo_core::prelude::*;
+//! use salvo_core::test::{ResponseExt, TestClient};
+//! use salvo_extra::test_util::SessionTestHarness;
+//! use salvo_session::{MemoryStore, SessionDepotExt, SessionHandler};
+//!
+//! #[handler]
+//! async fn count_visits(depot: &mut Depot) -> String {
+//!     let session = depot.session_mut().unwrap();
+//!     let visits = session.get::<u32>("visits").unwrap_or_default() + 1;
+//!     session.insert("visits", visits).unwrap();
+//!     visits.to_string()
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let session_handler = SessionHandler::builder(
+//!     MemoryStore::new(),
+//!     b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+//! )
+//! .build()
+//! .unwrap();
+//! let mut harness = SessionTestHarness::new(Router::new().hoop(session_handler).get(count_visits));
+//!
+//! harness.send(TestClient::get("http://127.0.0.1:5800/")).await;
+//! let mut res = harness.send(TestClient::get("http://127.0.0.1:5800/")).await;
+//! assert_eq!(res.take_string().await.unwrap(), "2");
+//! # }
+//! ```
+use std::fmt::{self, Debug, Formatter};
+
+use salvo_core::http::cookie::{time::OffsetDateTime, Cookie, CookieJar};
+use salvo_core::http::header::{COOKIE, SET_COOKIE};
+use salvo_core::test::RequestBuilder;
+use salvo_core::{Response, Service};
+
+/// Sends test requests to a [`Service`] while keeping its cookies, like a browser.
+///
+/// Cookies set by a response, such as the session cookie, are sent with the following requests
+/// until they are removed or expire.
+pub struct SessionTestHarness {
+    service: Service,
+    cookies: CookieJar,
+}
+
+impl Debug for SessionTestHarness {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionTestHarness")
+            .field("cookies", &self.cookies)
+            .finish()
+    }
+}
+
+impl SessionTestHarness {
+    /// Create a new `SessionTestHarness` sending requests to `service`.
+    pub fn new(service: impl Into<Service>) -> Self {
+        Self {
+            service: service.into(),
+            cookies: CookieJar::new(),
+        }
+    }
+
+    /// Sends `req` with the kept cookies and keeps the cookies set by the response.
+    pub async fn send(&mut self, req: RequestBuilder) -> Response {
+        let cookie_header = self
+            .cookies
+            .iter()
+            .map(|cookie| cookie.stripped().encoded().to_string())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let req = if cookie_header.is_empty() {
+            req
+        } else {
+            req.add_header(COOKIE, cookie_header, true)
+        };
+        let res = req.send(&self.service).await;
+        // The test client only keeps the last cookie of the response jar in the headers, so
+        // the jar is read too.
+        let header_cookies = res
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| Cookie::parse_encoded(value.to_owned()).ok());
+        let jar_cookies = res.cookies().delta().cloned().collect::<Vec<_>>();
+        for cookie in header_cookies.chain(jar_cookies) {
+            if is_removal(&cookie) {
+                self.cookies.force_remove(cookie.name());
+            } else {
+                self.cookies.add_original(cookie);
+            }
+        }
+        res
+    }
+
+    /// Returns the kept cookie called `name`.
+    pub fn cookie(&self, name: &str) -> Option<&Cookie<'static>> {
+        self.cookies.get(name)
+    }
+
+    /// Forgets all the kept cookies, like a new browser session.
+    pub fn clear_cookies(&mut self) {
+        self.cookies = CookieJar::new();
+    }
+}
+
+/// Whether `cookie` tells the client to remove the cookie.
+fn is_removal(cookie: &Cookie<'_>) -> bool {
+    let expired = cookie
+        .expires_datetime()
+        .is_some_and(|expires| expires <= OffsetDateTime::now_utc());
+    expired || matches!(cookie.max_age(), Some(max_age) if max_age.is_zero() || max_age.is_negative())
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn login(res: &mut Response) {
+        res.add_cookie(Cookie::new("user_id", "1"));
+    }
+    #[handler]
+    async fn logout(res: &mut Response) {
+        res.remove_cookie("user_id");
+    }
+    #[handler]
+    async fn current_user(req: &mut Request) -> String {
+        format!("{:?}", req.cookie("user_id").map(|cookie| cookie.value().to_owned()))
+    }
+
+    #[tokio::test]
+    async fn test_session_test_harness() {
+        let router = Router::new()
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("logout").post(logout))
+            .get(current_user);
+        let mut harness = SessionTestHarness::new(router);
+
+        harness.send(TestClient::post("http://127.0.0.1:5800/login")).await;
+        assert!(harness.cookie("user_id").is_some());
+        let mut res = harness.send(TestClient::get("http://127.0.0.1:5800/")).await;
+        assert_eq!(res.take_string().await.unwrap(), "Some(\"1\")");
+
+        harness.send(TestClient::post("http://127.0.0.1:5800/logout")).await;
+        assert!(harness.cookie("user_id").is_none());
+        let mut res = harness.send(TestClient::get("http://127.0.0.1:5800/")).await;
+        assert_eq!(res.take_string().await.unwrap(), "None");
+
+        harness.send(TestClient::post("http://127.0.0.1:5800/login")).await;
+        harness.clear_cookies();
+        let mut res = harness.send(TestClient::get("http://127.0.0.1:5800/")).await;
+        assert_eq!(res.take_string().await.unwrap(), "None");
+    }
+}
@@ -5,6 +5,7 @@
 #![doc(html_logo_url = "https://salvo.rs/images/logo.svg")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+use std::collections::BTreeMap;
 use std::fmt::{self, Debug, Display, Formatter};
 use std::future::Future;
 use std::ops::Deref;
@@ -79,6 +80,12 @@ impl Flash {
         self.0.push(FlashMessage::error(message));
         self
     }
+    /// Add a message built with [`FlashMessage`], for example one carrying meta fields.
+    #[inline]
+    pub fn add(&mut self, message: FlashMessage) -> &mut Self {
+        self.0.push(message);
+        self
+    }
 }
 
 impl Deref for Flash {
@@ -97,6 +104,9 @@ pub struct FlashMessage {
     pub level: FlashLevel,
     /// Flash message content.
     pub value: String,
+    /// Extra fields of the message, such as the form field a validation error belongs to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta: BTreeMap<String, String>,
 }
 impl FlashMessage {
     /// Create a new `FlashMessage` with `FlashLevel::Debug`.
@@ -105,6 +115,7 @@ impl FlashMessage {
         Self {
             level: FlashLevel::Debug,
             value: message.into(),
+            meta: BTreeMap::new(),
         }
     }
     /// Create a new `FlashMessage` with `FlashLevel::Info`.
@@ -113,6 +124,7 @@ impl FlashMessage {
         Self {
             level: FlashLevel::Info,
             value: message.into(),
+            meta: BTreeMap::new(),
         }
     }
     /// Create a new `FlashMessage` with `FlashLevel::Success`.
@@ -121,6 +133,7 @@ impl FlashMessage {
         Self {
             level: FlashLevel::Success,
             value: message.into(),
+            meta: BTreeMap::new(),
         }
     }
     /// Create a new `FlashMessage` with `FlashLevel::Warning`.
@@ -129,6 +142,7 @@ impl FlashMessage {
         Self {
             level: FlashLevel::Warning,
             value: message.into(),
+            meta: BTreeMap::new(),
         }
     }
     /// create a new `FlashMessage` with `FlashLevel::Error`.
@@ -137,8 +151,21 @@ impl FlashMessage {
         Self {
             level: FlashLevel::Error,
             value: message.into(),
+            meta: BTreeMap::new(),
         }
     }
+    /// Sets the meta field `key` to `value`.
+    ///
+    /// ```
+    /// # use salvo_flash::FlashMessage;
+    /// let message = FlashMessage::error("Too short").with_meta("field", "password");
+    /// assert_eq!(message.meta["field"], "password");
+    /// ```
+    #[inline]
+    pub fn with_meta(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.meta.insert(key.into(), value.into());
+        self
+    }
 }
 
 /// Verbosity level of a flash message.
@@ -208,6 +235,11 @@ pub trait FlashDepotExt {
     fn outgoing_flash(&self) -> &Flash;
     /// Get mutable outgoing flash.
     fn outgoing_flash_mut(&mut self) -> &mut Flash;
+    /// Take the incoming flash messages, so they are rendered only once.
+    ///
+    /// Returns an empty list if there are none. The messages are cleared from the store at the
+    /// end of the request, unless new messages are flashed.
+    fn take_flashes(&mut self) -> Vec<FlashMessage>;
 }
 
 impl FlashDepotExt for Depot {
@@ -227,6 +259,13 @@ impl FlashDepotExt for Depot {
         self.get_mut::<Flash>(OUTGOING_FLASH_KEY)
             .expect("Flash should be initialized")
     }
+
+    #[inline]
+    fn take_flashes(&mut self) -> Vec<FlashMessage> {
+        self.remove::<Flash>(INCOMING_FLASH_KEY)
+            .map(|flash| flash.0)
+            .unwrap_or_default()
+    }
 }
 
 /// `FlashHandler` is a middleware for flash messages.
@@ -398,4 +437,55 @@ mod tests {
             .await;
         assert!(respone.take_string().await.unwrap().is_empty());
     }
+
+    #[cfg(feature = "cookie-store")]
+    #[tokio::test]
+    async fn test_take_flashes() {
+        #[handler]
+        async fn submit(depot: &mut Depot, res: &mut Response) {
+            depot
+                .outgoing_flash_mut()
+                .add(FlashMessage::error("Too short").with_meta("field", "password"));
+            res.render(Redirect::other("/form"));
+        }
+        #[handler]
+        async fn form(depot: &mut Depot) -> String {
+            let mut body = String::new();
+            for message in depot.take_flashes() {
+                writeln!(
+                    body,
+                    "{}: {} - {}",
+                    message.meta["field"], message.value, message.level
+                )
+                .unwrap();
+            }
+            assert!(depot.incoming_flash().is_none());
+            body
+        }
+        let router = Router::new()
+            .hoop(CookieStore::new().into_handler())
+            .push(Router::with_path("submit").post(submit))
+            .push(Router::with_path("form").get(form));
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/submit")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/form")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(
+            respone.take_string().await.unwrap(),
+            "password: Too short - error\n"
+        );
+
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/form")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert!(respone.take_string().await.unwrap().is_empty());
+    }
 }
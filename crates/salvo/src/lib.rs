@@ -30,6 +30,7 @@
 //! | `force-https` | Middleware for forcing HTTPS | ❌ |
 //! | `logging` | Middleware for logging requests and responses | ❌ |
 //! | `request-id` | Middleware for setting a request ID | ❌ |
+//! | `security-headers` | Middleware for adding security headers | ❌ |
 //! | `size-limiter` | Middleware for limiting request size | ❌ |
 //! | `sse` | Server-Sent Events (SSE) middleware | ❌ |
 //! | `timeout` | Middleware for setting a timeout | ❌ |
@@ -112,6 +113,11 @@ cfg_feature! {
     // #[doc(no_inline)]
     pub use salvo_extra::request_id;
 }
+cfg_feature! {
+    #![feature ="security-headers"]
+    // #[doc(no_inline)]
+    pub use salvo_extra::security_headers;
+}
 cfg_feature! {
     #![feature ="cache"]
     #[doc(no_inline)]
@@ -252,6 +258,10 @@ pub mod prelude {
         #![feature ="request-id"]
         pub use salvo_extra::request_id::RequestId;
     }
+    cfg_feature! {
+        #![feature ="security-headers"]
+        pub use salvo_extra::security_headers::SecurityHeaders;
+    }
     cfg_feature! {
         #![feature ="serve-static"]
         pub use salvo_serve_static::{StaticFile, StaticDir};
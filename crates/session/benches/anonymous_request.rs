@@ -0,0 +1,46 @@
+//! Measures the session handler on requests without a session cookie, as sent by anonymous
+//! visitors of public pages.
+#![allow(missing_docs)]
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use salvo_core::prelude::*;
+use salvo_core::test::TestClient;
+use salvo_session::{MemoryStore, SessionHandler};
+
+#[handler]
+async fn hello() -> &'static str {
+    "hello"
+}
+
+fn service() -> Service {
+    let session_handler = SessionHandler::builder(
+        MemoryStore::new(),
+        b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+    )
+    .save_unchanged(false)
+    .build()
+    .expect("build session handler");
+    Service::new(Router::new().hoop(session_handler).get(hello))
+}
+
+fn bench_anonymous_request(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("build runtime");
+    let with_session = service();
+    let without_session = Service::new(Router::new().get(hello));
+    let mut group = c.benchmark_group("anonymous_request");
+    group.bench_function("without_session_handler", |b| {
+        b.to_async(&rt)
+            .iter(|| TestClient::get("http://127.0.0.1:5800/").send(&without_session))
+    });
+    group.bench_function("session_handler", |b| {
+        b.to_async(&rt)
+            .iter(|| TestClient::get("http://127.0.0.1:5800/").send(&with_session))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_anonymous_request);
+criterion_main!(benches);
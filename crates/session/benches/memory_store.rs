@@ -0,0 +1,72 @@
+//! Compares `MemoryStore` and `ShardedMemoryStore` under concurrent load.
+#![allow(missing_docs)]
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use salvo_session::{MemoryStore, Session, SessionStore, ShardedMemoryStore};
+
+const SESSIONS: usize = 1024;
+const TASKS: usize = 64;
+const OPS_PER_TASK: usize = 64;
+
+async fn populate<S: SessionStore>(store: &S) -> Vec<String> {
+    let mut cookie_values = Vec::with_capacity(SESSIONS);
+    for _ in 0..SESSIONS {
+        let mut session = Session::new();
+        session.insert("user_id", 1).expect("insert user_id");
+        let cookie_value = store.store_session(session).await.expect("store session");
+        cookie_values.push(cookie_value.expect("new session should have a cookie value"));
+    }
+    cookie_values
+}
+
+async fn concurrent_load_store<S: SessionStore>(store: S, cookie_values: Vec<String>) {
+    let mut handles = Vec::with_capacity(TASKS);
+    for task in 0..TASKS {
+        let store = store.clone();
+        let cookie_values = cookie_values.clone();
+        handles.push(tokio::spawn(async move {
+            for op in 0..OPS_PER_TASK {
+                let cookie_value =
+                    cookie_values[(task * OPS_PER_TASK + op) % cookie_values.len()].clone();
+                if let Some(mut session) = store
+                    .load_session(cookie_value)
+                    .await
+                    .expect("load session")
+                {
+                    session.insert("op", op).expect("insert op");
+                    store.store_session(session).await.expect("store session");
+                }
+            }
+        }));
+    }
+    for handle in handles {
+        handle.await.expect("task panicked");
+    }
+}
+
+fn bench_stores(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("build runtime");
+    let mut group = c.benchmark_group("concurrent_load_store");
+
+    let store = MemoryStore::new();
+    let cookie_values = rt.block_on(populate(&store));
+    group.bench_function("memory_store", |b| {
+        b.to_async(&rt)
+            .iter(|| concurrent_load_store(store.clone(), cookie_values.clone()))
+    });
+
+    let store = ShardedMemoryStore::new();
+    let cookie_values = rt.block_on(populate(&store));
+    group.bench_function("sharded_memory_store", |b| {
+        b.to_async(&rt)
+            .iter(|| concurrent_load_store(store.clone(), cookie_values.clone()))
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_stores);
+criterion_main!(benches);
@@ -0,0 +1,232 @@
+use salvo_core::writing::Redirect;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response, Router};
+
+use crate::{RequireSession, SessionDepotExt};
+
+/// Wires the common login flow onto a router, built from [`RequireSession`] and a logout route.
+///
+/// [`into_router`](Self::into_router) returns a router that runs the session handler first,
+/// serves the login routes at the login path and a logout route at the logout path, and guards
+/// the protected routes under the protected prefix. Unauthenticated requests to protected routes
+/// are redirected to the login page with a `next` parameter, and the login handler can send the
+/// user back with [`SessionDepotExt::login_return_to`](crate::SessionDepotExt::login_return_to).
+///
+/// For other setups, use [`RequireSession`] directly, and the `Logout` handler of the
+/// `salvo_extra` crate for logout routes.
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_session::{AuthFlow, MemoryStore, SessionDepotExt, SessionHandler};
+///
+/// #[handler]
+/// async fn login_page() -> &'static str {
+///     "login"
+/// }
+/// #[handler]
+/// async fn login(depot: &mut Depot, res: &mut Response) {
+///     let next = depot.login_return_to().unwrap_or_else(|| "/app".into());
+///     depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+///     res.render(Redirect::other(next));
+/// }
+/// #[handler]
+/// async fn dashboard() -> &'static str {
+///     "dashboard"
+/// }
+///
+/// let session_handler = SessionHandler::builder(
+///     MemoryStore::new(),
+///     b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+/// )
+/// .build()
+/// .unwrap();
+/// let router = AuthFlow::new().protected_prefix("/app").into_router(
+///     session_handler,
+///     Router::new().get(login_page).post(login),
+///     Router::with_path("dashboard").get(dashboard),
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct AuthFlow {
+    key: String,
+    protected_prefix: String,
+    login_path: String,
+    logout_path: String,
+    logout_redirect: String,
+}
+impl Default for AuthFlow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthFlow {
+    /// Create a new `AuthFlow` checking `user_id`, with the login page at `/login`, logout at
+    /// `/logout` and every other route protected.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            key: "user_id".into(),
+            protected_prefix: "/".into(),
+            login_path: "/login".into(),
+            logout_path: "/logout".into(),
+            logout_redirect: "/".into(),
+        }
+    }
+
+    /// Sets the session key that marks a session as authenticated.
+    #[inline]
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Sets the path under which routes require an authenticated session, `/` by default.
+    #[inline]
+    pub fn protected_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.protected_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the path of the login routes, which unauthenticated requests are redirected to.
+    #[inline]
+    pub fn login_path(mut self, path: impl Into<String>) -> Self {
+        self.login_path = path.into();
+        self
+    }
+
+    /// Sets the path of the logout route, which destroys the session on `POST`.
+    #[inline]
+    pub fn logout_path(mut self, path: impl Into<String>) -> Self {
+        self.logout_path = path.into();
+        self
+    }
+
+    /// Sets where the logout route redirects to, `/` by default.
+    #[inline]
+    pub fn logout_redirect(mut self, uri: impl Into<String>) -> Self {
+        self.logout_redirect = uri.into();
+        self
+    }
+
+    /// Builds the router. `session_handler` runs first, `login` is served at the login path and
+    /// `protected` under the protected prefix.
+    pub fn into_router(
+        self,
+        session_handler: impl Handler,
+        login: Router,
+        protected: Router,
+    ) -> Router {
+        let login_url = format!("/{}", self.login_path.trim_start_matches('/'));
+        let guard = RequireSession::new(self.key).login_redirect(login_url);
+        // The login and logout routes are pushed first, so they stay reachable when they are
+        // under the protected prefix.
+        Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path(self.login_path.trim_start_matches('/')).push(login))
+            .push(
+                Router::with_path(self.logout_path.trim_start_matches('/')).post(Logout {
+                    redirect_to: self.logout_redirect,
+                }),
+            )
+            .push(
+                Router::with_path(self.protected_prefix.trim_start_matches('/'))
+                    .hoop(guard)
+                    .push(protected),
+            )
+    }
+}
+
+/// Destroys the session and redirects, for the logout route of [`AuthFlow`].
+#[derive(Clone, Debug)]
+struct Logout {
+    redirect_to: String,
+}
+
+#[async_trait]
+impl Handler for Logout {
+    async fn handle(
+        &self,
+        _req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        depot.destroy_session();
+        res.render(Redirect::other(self.redirect_to.clone()));
+        ctrl.skip_rest();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::LOCATION;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_extra::test_util::SessionTestHarness;
+
+    use super::*;
+    use crate::{MemoryStore, SessionDepotExt, SessionHandler};
+
+    #[tokio::test]
+    async fn test_auth_flow() {
+        #[handler]
+        async fn login_page() -> &'static str {
+            "login"
+        }
+        #[handler]
+        async fn login(depot: &mut Depot, res: &mut Response) {
+            let next = depot.login_return_to().unwrap_or_else(|| "/".into());
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+            res.render(Redirect::other(next));
+        }
+        #[handler]
+        async fn dashboard() -> &'static str {
+            "dashboard"
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = AuthFlow::new().logout_redirect("/login").into_router(
+            session_handler,
+            Router::new().get(login_page).post(login),
+            Router::with_path("dashboard").get(dashboard),
+        );
+        let mut harness = SessionTestHarness::new(router);
+
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/dashboard"))
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::SEE_OTHER));
+        assert_eq!(
+            res.headers().get(LOCATION).unwrap(),
+            "/login?next=%2Fdashboard"
+        );
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/login"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "login");
+
+        let res = harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        assert_eq!(res.headers().get(LOCATION).unwrap(), "/dashboard");
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/dashboard"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "dashboard");
+
+        let res = harness
+            .send(TestClient::post("http://127.0.0.1:5800/logout"))
+            .await;
+        assert_eq!(res.headers().get(LOCATION).unwrap(), "/login");
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/dashboard"))
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::SEE_OTHER));
+    }
+}
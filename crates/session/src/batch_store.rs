@@ -0,0 +1,70 @@
+#[cfg(feature = "cookie-store")]
+use async_session::CookieStore;
+#[cfg(feature = "memory-store")]
+use async_session::MemoryStore;
+use async_session::{async_trait, Result, Session, SessionStore};
+
+use crate::CachingStore;
+
+/// Session stores that can load several sessions at once, used by
+/// [`SessionHandler::load_many`](crate::SessionHandler::load_many).
+///
+/// The default implementation loads the sessions one by one. Stores that can fetch several
+/// keys in a single round-trip, such as `MemcachedStore`, override it.
+#[async_trait]
+pub trait BatchSessionStore: SessionStore {
+    /// Loads the sessions for `cookie_values`, in the same order. Sessions that don't exist are
+    /// `None`.
+    async fn load_sessions(&self, cookie_values: Vec<String>) -> Result<Vec<Option<Session>>> {
+        let mut sessions = Vec::with_capacity(cookie_values.len());
+        for cookie_value in cookie_values {
+            sessions.push(self.load_session(cookie_value).await?);
+        }
+        Ok(sessions)
+    }
+}
+
+#[cfg(feature = "memory-store")]
+impl BatchSessionStore for MemoryStore {}
+#[cfg(feature = "cookie-store")]
+impl BatchSessionStore for CookieStore {}
+impl<S: SessionStore> BatchSessionStore for CachingStore<S> {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MemoryStore, Session, SessionHandler};
+
+    #[tokio::test]
+    async fn test_load_many() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let mut signed_values = vec![];
+        for user_id in 1..=3 {
+            let mut session = Session::new();
+            session.insert("user_id", user_id).unwrap();
+            signed_values.push(handler.save(session).await.unwrap().unwrap());
+        }
+        let session = handler
+            .load_by_cookie_value(&signed_values[1])
+            .await
+            .unwrap();
+        handler.destroy(session).await.unwrap();
+        signed_values.insert(1, "invalid".into());
+
+        let user_ids = handler
+            .load_many(&signed_values)
+            .await
+            .iter()
+            .map(|session| {
+                session
+                    .as_ref()
+                    .and_then(|session| session.get::<u32>("user_id"))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(user_ids, vec![Some(1), None, None, Some(3)]);
+    }
+}
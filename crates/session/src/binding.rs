@@ -0,0 +1,129 @@
+use std::net::IpAddr;
+
+use async_session::base64;
+use async_session::sha2::{Digest, Sha256};
+
+/// What a session is bound to with [`HandlerBuilder::bind_to`](crate::HandlerBuilder::bind_to).
+///
+/// A bound session is only loaded for requests that come from the same client address and/or
+/// user agent as the request that created it, which limits the use of a stolen session cookie.
+///
+/// Binding to the address logs out clients whose address changes during a session, which
+/// happens often with mobile networks and some corporate proxies. Binding to the user agent
+/// logs out clients when their browser updates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionBinding {
+    /// Bind the session to the client address.
+    Ip,
+    /// Bind the session to the `User-Agent` header.
+    UserAgent,
+    /// Bind the session to both the client address and the `User-Agent` header.
+    IpAndUserAgent,
+}
+
+impl SessionBinding {
+    /// Returns the hash of the request attributes covered by this binding, so the raw address and
+    /// user agent are not kept in the store.
+    pub(crate) fn fingerprint(self, ip: Option<IpAddr>, user_agent: Option<&[u8]>) -> String {
+        let mut hasher = Sha256::new();
+        if matches!(self, Self::Ip | Self::IpAndUserAgent) {
+            hasher.update(b"ip:");
+            if let Some(ip) = ip {
+                hasher.update(ip.to_string().as_bytes());
+            }
+            hasher.update(b"\n");
+        }
+        if matches!(self, Self::UserAgent | Self::IpAndUserAgent) {
+            hasher.update(b"ua:");
+            hasher.update(user_agent.unwrap_or_default());
+            hasher.update(b"\n");
+        }
+        base64::encode(hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use salvo_core::http::header::{HeaderName, COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+    use crate::{MemoryStore, SessionDepotExt, SessionHandler};
+
+    #[test]
+    fn test_fingerprint() {
+        let ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let other_ip = Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        let ua = Some(&b"curl/8.0"[..]);
+
+        let binding = SessionBinding::Ip;
+        assert_eq!(binding.fingerprint(ip, ua), binding.fingerprint(ip, None));
+        assert_ne!(
+            binding.fingerprint(ip, ua),
+            binding.fingerprint(other_ip, ua)
+        );
+
+        let binding = SessionBinding::UserAgent;
+        assert_eq!(
+            binding.fingerprint(ip, ua),
+            binding.fingerprint(other_ip, ua)
+        );
+        assert_ne!(binding.fingerprint(ip, ua), binding.fingerprint(ip, None));
+
+        let binding = SessionBinding::IpAndUserAgent;
+        assert_ne!(
+            binding.fingerprint(ip, ua),
+            binding.fingerprint(other_ip, ua)
+        );
+        assert_ne!(binding.fingerprint(ip, ua), binding.fingerprint(ip, None));
+    }
+
+    #[tokio::test]
+    async fn test_bind_to() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn user_id(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .bind_to(SessionBinding::Ip)
+        .client_ip_header(HeaderName::from_static("x-forwarded-for"))
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("user_id").get(user_id));
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .add_header("x-forwarded-for", "203.0.113.1, 10.0.0.1", true)
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/user_id")
+            .add_header(COOKIE, cookie, true)
+            .add_header("x-forwarded-for", "198.51.100.1, 10.0.0.1", true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/user_id")
+            .add_header(COOKIE, cookie, true)
+            .add_header("x-forwarded-for", "10.0.0.2", true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+    }
+}
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_session::{async_trait, Result, Session, SessionStore};
+
+use crate::{detach, PatchableStore, SessionPatch, UserSessionStore, USER_ID_KEY};
+
+/// A read-through cache in front of another [`SessionStore`].
+///
+/// Loaded sessions are kept in memory for at most `ttl`, so repeated requests with the same
+/// cookie don't hit the inner store. Entries are invalidated when the session is stored or
+/// destroyed through this wrapper, are never served past the session's own expiry, and the cache
+/// holds at most `capacity` entries, evicting the oldest first. A load racing with a store or
+/// destroy of the same session doesn't cache what it loaded.
+///
+/// Every cached session is a detached copy, so changes made by a handler are only visible to
+/// other requests once they have been stored.
+#[derive(Clone)]
+pub struct CachingStore<S> {
+    inner: S,
+    capacity: usize,
+    ttl: Duration,
+    cache: Arc<Mutex<Cache>>,
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<String, CacheEntry>,
+    // Session id to cookie value, used to invalidate entries from `store_session`
+    // and `destroy_session` which only know the session.
+    ids: HashMap<String, String>,
+    // Bumped on every invalidation. A load only caches its session if it was not invalidated
+    // since the load started, or it could cache a session that was just stored or destroyed.
+    epoch: u64,
+    // Epoch of the last invalidation of each session id, kept while loads are running.
+    invalidated: HashMap<String, u64>,
+    // Epoch of the last invalidation of every session.
+    cleared: u64,
+    loading: usize,
+}
+
+struct CacheEntry {
+    session: Session,
+    cached_at: Instant,
+}
+
+impl Cache {
+    fn remove_id(&mut self, id: &str) {
+        if let Some(cookie_value) = self.ids.remove(id) {
+            self.entries.remove(&cookie_value);
+        }
+    }
+    fn remove_cookie_value(&mut self, cookie_value: &str) {
+        if let Some(entry) = self.entries.remove(cookie_value) {
+            self.ids.remove(entry.session.id());
+        }
+    }
+    fn invalidated_since(&self, id: &str, epoch: u64) -> bool {
+        self.cleared > epoch || self.invalidated.get(id).is_some_and(|&at| at > epoch)
+    }
+    fn skip_running_loads(&mut self) {
+        self.epoch += 1;
+        self.cleared = self.epoch;
+    }
+}
+
+/// Marks a load from the inner store as running, until it is dropped.
+struct LoadGuard<'a> {
+    cache: &'a Mutex<Cache>,
+    epoch: u64,
+}
+
+impl<'a> LoadGuard<'a> {
+    fn new(cache: &'a Mutex<Cache>) -> Self {
+        let epoch = match cache.lock() {
+            Ok(mut cache) => {
+                cache.loading += 1;
+                cache.epoch
+            }
+            Err(_) => 0,
+        };
+        Self { cache, epoch }
+    }
+}
+
+impl Drop for LoadGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.loading = cache.loading.saturating_sub(1);
+            if cache.loading == 0 {
+                cache.invalidated.clear();
+            }
+        }
+    }
+}
+
+impl<S> fmt::Debug for CachingStore<S>
+where
+    S: SessionStore,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CachingStore")
+            .field("inner", &self.inner)
+            .field("capacity", &self.capacity)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl<S> CachingStore<S>
+where
+    S: SessionStore,
+{
+    /// Create a new `CachingStore` that caches at most `capacity` sessions for `ttl`.
+    pub fn new(inner: S, capacity: usize, ttl: Duration) -> Self {
+        Self {
+            inner,
+            capacity,
+            ttl,
+            cache: Default::default(),
+        }
+    }
+
+    /// Returns a reference to the inner store.
+    #[inline]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns the number of cached sessions.
+    pub fn cached_count(&self) -> usize {
+        self.cache
+            .lock()
+            .map(|cache| cache.entries.len())
+            .unwrap_or_default()
+    }
+
+    fn cached(&self, cookie_value: &str) -> Option<Session> {
+        let mut cache = self.cache.lock().ok()?;
+        let entry = cache.entries.get(cookie_value)?;
+        if entry.cached_at.elapsed() < self.ttl && !entry.session.is_expired() {
+            return detach(&entry.session);
+        }
+        cache.remove_cookie_value(cookie_value);
+        None
+    }
+
+    /// Caches `session`, loaded by a load started at `epoch`, unless it was invalidated since.
+    fn cache(&self, cookie_value: String, session: &Session, epoch: u64) {
+        if self.capacity == 0 {
+            return;
+        }
+        let Some(session) = detach(session) else {
+            return;
+        };
+        let Ok(mut cache) = self.cache.lock() else {
+            return;
+        };
+        if cache.invalidated_since(session.id(), epoch) {
+            return;
+        }
+        cache.remove_cookie_value(&cookie_value);
+        if cache.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            cache
+                .entries
+                .retain(|_, entry| entry.cached_at.elapsed() < ttl && !entry.session.is_expired());
+            let Cache { entries, ids, .. } = &mut *cache;
+            ids.retain(|_, cookie_value| entries.contains_key(cookie_value));
+        }
+        if cache.entries.len() >= self.capacity {
+            let oldest = cache
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.cached_at)
+                .map(|(cookie_value, _)| cookie_value.clone());
+            if let Some(oldest) = oldest {
+                cache.remove_cookie_value(&oldest);
+            }
+        }
+        cache.remove_id(session.id());
+        cache
+            .ids
+            .insert(session.id().to_owned(), cookie_value.clone());
+        cache.entries.insert(
+            cookie_value,
+            CacheEntry {
+                session,
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    fn invalidate(&self, session: &Session) {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.remove_id(session.id());
+            cache.epoch += 1;
+            if cache.loading > 0 {
+                let epoch = cache.epoch;
+                cache.invalidated.insert(session.id().to_owned(), epoch);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S> SessionStore for CachingStore<S>
+where
+    S: SessionStore,
+{
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        if let Some(session) = self.cached(&cookie_value) {
+            return Ok(Some(session));
+        }
+        let guard = LoadGuard::new(&self.cache);
+        let session = self.inner.load_session(cookie_value.clone()).await?;
+        if let Some(session) = &session {
+            self.cache(cookie_value, session, guard.epoch);
+        }
+        Ok(session)
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        self.invalidate(&session);
+        self.inner.store_session(session).await
+    }
+
+    async fn destroy_session(&self, session: Session) -> Result {
+        self.invalidate(&session);
+        self.inner.destroy_session(session).await
+    }
+
+    async fn clear_store(&self) -> Result {
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.entries.clear();
+            cache.ids.clear();
+            cache.skip_running_loads();
+        }
+        self.inner.clear_store().await
+    }
+}
+
+#[async_trait]
+impl<S> UserSessionStore for CachingStore<S>
+where
+    S: UserSessionStore,
+{
+    async fn destroy_user_sessions(&self, user_id: &str, keep_id: Option<&str>) -> Result<usize> {
+        if let Ok(mut cache) = self.cache.lock() {
+            let Cache { entries, ids, .. } = &mut *cache;
+            entries.retain(|_, entry| {
+                Some(entry.session.id()) == keep_id
+                    || entry.session.get::<String>(USER_ID_KEY).as_deref() != Some(user_id)
+            });
+            ids.retain(|_, cookie_value| entries.contains_key(cookie_value));
+            // Loads running now may return sessions of the user, so none of them are cached.
+            cache.skip_running_loads();
+        }
+        self.inner.destroy_user_sessions(user_id, keep_id).await
+    }
+}
+
+#[async_trait]
+impl<S> PatchableStore for CachingStore<S>
+where
+    S: PatchableStore,
+{
+    async fn patch_session(&self, session: Session, patch: SessionPatch) -> Result<Option<String>> {
+        self.invalidate(&session);
+        self.inner.patch_session(session, patch).await
+    }
+
+    async fn renew_session(&self, session: Session) -> Result<Option<String>> {
+        self.invalidate(&session);
+        self.inner.renew_session(session).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStore;
+
+    #[tokio::test]
+    async fn test_caching_store() {
+        let inner = MemoryStore::new();
+        let store = CachingStore::new(inner.clone(), 2, Duration::from_secs(60));
+        let mut session = Session::new();
+        session.insert("key", "value").unwrap();
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+
+        let session = store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(store.cached_count(), 1);
+
+        // Served from the cache even though the inner store no longer has it.
+        inner.clear_store().await.unwrap();
+        assert!(store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .is_some());
+
+        store.destroy_session(session).await.unwrap();
+        assert_eq!(store.cached_count(), 0);
+        assert!(store.load_session(cookie_value).await.unwrap().is_none());
+    }
+
+    type Hook = Box<dyn FnOnce() + Send>;
+
+    /// Runs a hook in the middle of the next load, to race it.
+    #[derive(Clone)]
+    struct RacingStore {
+        inner: MemoryStore,
+        during_load: Arc<Mutex<Option<Hook>>>,
+    }
+
+    impl fmt::Debug for RacingStore {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            f.debug_struct("RacingStore").finish()
+        }
+    }
+
+    #[async_trait]
+    impl SessionStore for RacingStore {
+        async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+            let session = self.inner.load_session(cookie_value).await?;
+            let during_load = self.during_load.lock().unwrap().take();
+            if let Some(during_load) = during_load {
+                during_load();
+            }
+            Ok(session)
+        }
+        async fn store_session(&self, session: Session) -> Result<Option<String>> {
+            self.inner.store_session(session).await
+        }
+        async fn destroy_session(&self, session: Session) -> Result {
+            self.inner.destroy_session(session).await
+        }
+        async fn clear_store(&self) -> Result {
+            self.inner.clear_store().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caching_store_invalidated_during_load() {
+        let inner = RacingStore {
+            inner: MemoryStore::new(),
+            during_load: Default::default(),
+        };
+        let store = CachingStore::new(inner.clone(), 2, Duration::from_secs(60));
+        let session = Session::new();
+        let stored = session.clone();
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+
+        let racing = store.clone();
+        *inner.during_load.lock().unwrap() = Some(Box::new(move || racing.invalidate(&stored)));
+        assert!(store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .is_some());
+        assert_eq!(store.cached_count(), 0);
+        assert!(store.cache.lock().unwrap().invalidated.is_empty());
+
+        assert!(store.load_session(cookie_value).await.unwrap().is_some());
+        assert_eq!(store.cached_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_caching_store_capacity() {
+        let store = CachingStore::new(MemoryStore::new(), 2, Duration::from_secs(60));
+        for _ in 0..3 {
+            let cookie_value = store.store_session(Session::new()).await.unwrap().unwrap();
+            store.load_session(cookie_value).await.unwrap();
+        }
+        assert_eq!(store.cached_count(), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use salvo_core::http::header::{HeaderName, HeaderValue};
+
+/// The `Clear-Site-Data` header.
+pub(crate) const CLEAR_SITE_DATA: HeaderName = HeaderName::from_static("clear-site-data");
+
+/// A directive of the `Clear-Site-Data` header sent when a session is destroyed, see
+/// [`HandlerBuilder::clear_site_data_directives`](crate::HandlerBuilder::clear_site_data_directives).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ClearSiteData {
+    /// Clears the browser cache of the site.
+    Cache,
+    /// Clears all the cookies of the site, not only the session cookie.
+    Cookies,
+    /// Clears local storage, session storage, IndexedDB, service workers and similar storage.
+    Storage,
+    /// Reloads all the browsing contexts of the site.
+    ExecutionContexts,
+}
+
+impl ClearSiteData {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Cache => "\"cache\"",
+            Self::Cookies => "\"cookies\"",
+            Self::Storage => "\"storage\"",
+            Self::ExecutionContexts => "\"executionContexts\"",
+        }
+    }
+
+    /// Returns the value of the `Clear-Site-Data` header with `directives`.
+    pub(crate) fn header_value(directives: &[Self]) -> HeaderValue {
+        let value = directives
+            .iter()
+            .map(|directive| directive.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        HeaderValue::from_str(&value).expect("directives should be a valid header value")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+    use crate::{MemoryStore, SessionDepotExt, SessionHandler};
+
+    #[test]
+    fn test_header_value() {
+        let value = ClearSiteData::header_value(&[ClearSiteData::Cookies, ClearSiteData::Storage]);
+        assert_eq!(value, "\"cookies\", \"storage\"");
+    }
+
+    #[tokio::test]
+    async fn test_clear_site_data_on_destroy() {
+        #[handler]
+        async fn logout(depot: &mut Depot) {
+            depot.session_mut().unwrap().destroy();
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .clear_site_data_on_destroy(true)
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).post(logout));
+        let respone = TestClient::post("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert_eq!(
+            respone.headers().get("clear-site-data").unwrap(),
+            "\"cookies\", \"storage\""
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use serde::Serialize;
+
+/// A read-only snapshot of the settings of a [`SessionHandler`](crate::SessionHandler),
+/// returned by [`SessionHandler::config`](crate::SessionHandler::config).
+///
+/// It serializes with `serde`, for example to check the deployed configuration from an admin
+/// endpoint. Secret material is never included: only the number of fallback keys is reported.
+/// Durations are in seconds.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct SessionConfig {
+    /// Name of the session cookie.
+    pub cookie_name: String,
+    /// Path of the session cookie.
+    pub cookie_path: String,
+    /// Domain of the session cookie.
+    pub cookie_domain: Option<String>,
+    /// Value of the `SameSite` attribute of the session cookie, or `omitted` when it is not sent.
+    pub same_site: String,
+    /// Whether the cookie uses the `__Host-` prefix and is always `Secure`.
+    pub host_prefixed: bool,
+    /// Lifetime of sessions, or `None` for sessions without expiry.
+    pub session_ttl_secs: Option<u64>,
+    /// Remaining lifetime below which sessions are refreshed.
+    pub refresh_threshold_secs: Option<u64>,
+    /// Time during which expired sessions are still accepted.
+    pub grace_period_secs: Option<u64>,
+    /// Ceiling on the lifetime of sessions and their cookies.
+    pub max_session_ttl_secs: Option<u64>,
+    /// Whether unchanged sessions are saved on every request.
+    pub save_unchanged: bool,
+    /// Number of fallback keys accepted when verifying cookies.
+    pub fallback_keys: usize,
+    /// Whether the signing keys are refreshed from a [`KeyProvider`](crate::KeyProvider).
+    pub key_provider: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use cookie::Key;
+
+    use crate::{MemoryStore, SessionHandler};
+
+    #[test]
+    fn test_config() {
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_domain("example.com")
+        .session_ttl(Some(Duration::from_secs(60 * 60)))
+        .add_fallback_key(Key::generate())
+        .build()
+        .unwrap();
+        let config = session_handler.config();
+        assert_eq!(config.cookie_name, "salvo.session.id");
+        assert_eq!(config.cookie_domain.as_deref(), Some("example.com"));
+        assert_eq!(config.same_site, "Lax");
+        assert_eq!(config.session_ttl_secs, Some(60 * 60));
+        assert_eq!(config.fallback_keys, 1);
+
+        let json = async_session::serde_json::to_string(&config).unwrap();
+        assert!(json.contains("\"cookie_path\":\"/\""));
+        assert!(!json.contains("secretab"));
+    }
+}
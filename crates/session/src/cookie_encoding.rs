@@ -0,0 +1,114 @@
+use async_session::base64;
+
+/// Length of the HMAC-SHA256 digest of signed values.
+pub(crate) const DIGEST_LEN: usize = 32;
+/// Length of the digest encoded with standard base64.
+const BASE64_DIGEST_LEN: usize = 44;
+
+/// How the signed session value is encoded in the cookie, set with
+/// [`HandlerBuilder::cookie_encoding`](crate::HandlerBuilder::cookie_encoding).
+///
+/// Some proxies reject cookies with characters outside a narrow set. The other encodings avoid
+/// the `+`, `/` and `=` of standard base64 at the cost of a longer cookie, and changing the
+/// encoding invalidates the existing session cookies.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CookieEncoding {
+    /// The standard base64 signature followed by the value, which is itself usually standard
+    /// base64. This is the default.
+    #[default]
+    StandardBase64,
+    /// URL-safe base64 without padding of the signature and value, about a third longer.
+    UrlSafeBase64,
+    /// Lowercase hexadecimal of the signature and value, about twice as long but made only of
+    /// `0-9a-f`.
+    Hex,
+}
+
+impl CookieEncoding {
+    /// Encodes `digest` and the signed `value`.
+    pub(crate) fn encode(self, digest: &[u8], value: &str) -> String {
+        match self {
+            Self::StandardBase64 => {
+                let mut encoded = base64::encode(digest);
+                encoded.push_str(value);
+                encoded
+            }
+            Self::UrlSafeBase64 => {
+                base64::encode_config([digest, value.as_bytes()].concat(), base64::URL_SAFE_NO_PAD)
+            }
+            Self::Hex => hex::encode([digest, value.as_bytes()].concat()),
+        }
+    }
+
+    /// Splits an encoded signed value into its digest and value. Returns `None` if it is
+    /// malformed.
+    pub(crate) fn decode(self, encoded: &str) -> Option<(Vec<u8>, String)> {
+        let (digest, value) = match self {
+            Self::StandardBase64 => {
+                if !encoded.is_char_boundary(BASE64_DIGEST_LEN) {
+                    return None;
+                }
+                let (digest, value) = encoded.split_at(BASE64_DIGEST_LEN);
+                (base64::decode(digest).ok()?, value.to_owned())
+            }
+            Self::UrlSafeBase64 | Self::Hex => {
+                let mut bytes = match self {
+                    Self::Hex => hex::decode(encoded).ok()?,
+                    _ => base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).ok()?,
+                };
+                if bytes.len() < DIGEST_LEN {
+                    return None;
+                }
+                let value = bytes.split_off(DIGEST_LEN);
+                (bytes, String::from_utf8(value).ok()?)
+            }
+        };
+        (digest.len() == DIGEST_LEN).then_some((digest, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemoryStore, Session, SessionHandler};
+
+    #[test]
+    fn test_cookie_encoding() {
+        let digest = [7u8; DIGEST_LEN];
+        let value = "aGVsbG8+d29ybGQ/Pw==";
+        for encoding in [
+            CookieEncoding::StandardBase64,
+            CookieEncoding::UrlSafeBase64,
+            CookieEncoding::Hex,
+        ] {
+            let encoded = encoding.encode(&digest, value);
+            assert_eq!(
+                encoding.decode(&encoded),
+                Some((digest.to_vec(), value.to_owned()))
+            );
+            assert_eq!(encoding.decode("short"), None);
+        }
+        let encoded = CookieEncoding::UrlSafeBase64.encode(&digest, value);
+        assert!(!encoded.contains(['+', '/', '=']));
+        let encoded = CookieEncoding::Hex.encode(&digest, value);
+        assert!(encoded.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[tokio::test]
+    async fn test_handler_cookie_encoding() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_encoding(CookieEncoding::Hex)
+        .build()
+        .unwrap();
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
+        assert!(signed_value.chars().all(|c| c.is_ascii_hexdigit()));
+        let session = handler.load_by_cookie_value(&signed_value).await.unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+    }
+}
@@ -0,0 +1,676 @@
+use std::collections::BTreeMap;
+
+use cookie::Cookie;
+use salvo_core::http::header::{HeaderName, HeaderValue, COOKIE, SET_COOKIE};
+use salvo_core::{Request, Response};
+
+/// Reads the signed session value sent by the client.
+///
+/// The default, [`HttpCookies`], reads the request cookies. Set another source with
+/// [`HandlerBuilder::cookie_source`](crate::HandlerBuilder::cookie_source), for example to
+/// support native clients that carry the value in a header.
+pub trait CookieSource: Send + Sync + 'static {
+    /// Returns the value of the cookie called `name`, as sent by the client.
+    fn cookie_value(&self, req: &Request, name: &str) -> Option<String>;
+}
+
+/// Sends session cookies, including removal cookies, to the client.
+///
+/// The default, [`HttpCookies`], adds them to the response cookies. Set another sink with
+/// [`HandlerBuilder::cookie_sink`](crate::HandlerBuilder::cookie_sink).
+pub trait CookieSink: Send + Sync + 'static {
+    /// Sends `cookie` with the response.
+    fn set_cookie(&self, res: &mut Response, cookie: Cookie<'static>);
+
+    /// Sends `cookie`, the cookie of a regenerated session, along with `removal`, which removes
+    /// the cookie of the previous session. The default only sends `cookie`, which replaces the
+    /// previous cookie as it has the same name.
+    fn replace_cookie(
+        &self,
+        res: &mut Response,
+        removal: Cookie<'static>,
+        cookie: Cookie<'static>,
+    ) {
+        let _ = removal;
+        self.set_cookie(res, cookie);
+    }
+}
+
+/// Returns why `domain` can't scope a cookie shared by its subdomains, if it can't.
+///
+/// The public suffix list is not checked, so `co.uk` passes; browsers reject such cookies.
+pub(crate) fn shared_domain_error(domain: &str) -> Option<&'static str> {
+    if domain.is_empty() || domain.split('.').any(str::is_empty) {
+        Some("has an empty label")
+    } else if !domain.contains('.') {
+        Some("must have at least two labels, like `example.com`")
+    } else if domain.parse::<std::net::IpAddr>().is_ok() {
+        Some("is an IP address, which has no subdomains")
+    } else if domain.contains([':', '/', '[', ']']) {
+        Some("must be a bare domain, without scheme, port or path")
+    } else {
+        None
+    }
+}
+
+/// Whether `host`, with an optional port, is `domain` or one of its subdomains, so the browser
+/// accepts a cookie scoped to `domain` from it.
+pub(crate) fn host_in_domain(host: &str, domain: &str) -> bool {
+    let host = match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|subdomain| subdomain.ends_with('.'))
+}
+
+/// Whether a `Cookie` header of `req` is not valid UTF-8 or has entries that don't parse as a
+/// cookie, which are dropped from the request cookies.
+pub(crate) fn cookie_header_malformed(req: &Request) -> bool {
+    req.headers()
+        .get_all(COOKIE)
+        .iter()
+        .any(|header| match header.to_str() {
+            Ok(header) => header
+                .split(';')
+                .map(str::trim)
+                .filter(|pair| !pair.is_empty())
+                .any(|pair| Cookie::parse_encoded(pair).is_err()),
+            Err(_) => true,
+        })
+}
+
+/// Reads the session cookie from the request and writes it to the response with `Set-Cookie`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HttpCookies;
+
+impl CookieSource for HttpCookies {
+    #[inline]
+    fn cookie_value(&self, req: &Request, name: &str) -> Option<String> {
+        req.cookies()
+            .get(name)
+            .map(|cookie| cookie.value().to_owned())
+    }
+}
+
+impl CookieSink for HttpCookies {
+    #[inline]
+    fn set_cookie(&self, res: &mut Response, cookie: Cookie<'static>) {
+        res.add_cookie(cookie);
+    }
+
+    /// Sends the removal and the new cookie as two `Set-Cookie` headers, in that order, as the
+    /// response cookies can't hold two cookies with the same name. The new cookie is then not
+    /// among the response cookies.
+    fn replace_cookie(
+        &self,
+        res: &mut Response,
+        removal: Cookie<'static>,
+        cookie: Cookie<'static>,
+    ) {
+        for cookie in [removal, cookie] {
+            match HeaderValue::from_str(&cookie.encoded().to_string()) {
+                Ok(value) => {
+                    // A cookie of the same name already added to the response cookies would be
+                    // sent after these headers and win.
+                    res.cookies_mut().force_remove(cookie.name());
+                    res.headers_mut().append(SET_COOKIE, value);
+                }
+                Err(e) => tracing::error!(error = ?e, "invalid session cookie"),
+            }
+        }
+    }
+}
+
+/// Splits the session cookie into several cookies, `name.0`, `name.1` and so on, for session
+/// values too large for a single cookie, as with a [`CookieStore`](crate::CookieStore).
+///
+/// Browsers drop cookies over about 4 KB, so each chunk holds at most `chunk_size` bytes of the
+/// value. When reading, the chunks are ordered by index and joined up to the first missing index,
+/// so chunks left over from a larger value after a gap are ignored. When the value shrinks, the
+/// chunks the client still holds beyond the new last one are removed. A session cookie sent
+/// whole, before chunking was enabled, is still read, and removed once the chunks are sent. Set
+/// it with [`HandlerBuilder::chunked_cookies`](crate::HandlerBuilder::chunked_cookies).
+#[derive(Clone, Copy, Debug)]
+pub struct ChunkedCookies {
+    chunk_size: usize,
+}
+
+impl Default for ChunkedCookies {
+    fn default() -> Self {
+        Self::new(3800)
+    }
+}
+
+impl ChunkedCookies {
+    /// Create a new `ChunkedCookies` putting at most `chunk_size` bytes of the value in each
+    /// cookie.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    #[inline]
+    pub fn new(chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "chunk size must not be zero");
+        Self { chunk_size }
+    }
+}
+
+/// Returns the index of the chunk called `cookie_name` of the cookie called `name`.
+fn chunk_index(cookie_name: &str, name: &str) -> Option<usize> {
+    cookie_name
+        .strip_prefix(name)?
+        .strip_prefix('.')?
+        .parse()
+        .ok()
+}
+
+/// Splits `value` into pieces of at most `size` bytes, on character boundaries.
+fn split_value(value: &str, size: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = value;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        // A character longer than the chunk size goes in a chunk of its own.
+        if end == 0 {
+            end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
+impl CookieSource for ChunkedCookies {
+    fn cookie_value(&self, req: &Request, name: &str) -> Option<String> {
+        let chunks = req
+            .cookies()
+            .iter()
+            .filter_map(|cookie| Some((chunk_index(cookie.name(), name)?, cookie.value())))
+            .collect::<BTreeMap<_, _>>();
+        if chunks.is_empty() {
+            return HttpCookies.cookie_value(req, name);
+        }
+        let value = chunks
+            .iter()
+            .enumerate()
+            .take_while(|(expected, (index, _))| expected == *index)
+            .map(|(_, (_, chunk))| *chunk)
+            .collect::<String>();
+        (!value.is_empty()).then_some(value)
+    }
+}
+
+impl CookieSink for ChunkedCookies {
+    fn set_cookie(&self, res: &mut Response, cookie: Cookie<'static>) {
+        let name = cookie.name().to_owned();
+        let chunks = split_value(cookie.value(), self.chunk_size);
+        // The response cookies start with the request cookies, which tells the chunks the
+        // client holds.
+        let stale = res
+            .cookies()
+            .iter()
+            .filter(|held| match chunk_index(held.name(), &name) {
+                Some(index) => index >= chunks.len(),
+                None => held.name() == name,
+            })
+            .map(|held| held.name().to_owned())
+            .collect::<Vec<_>>();
+        for (index, chunk) in chunks.iter().enumerate() {
+            let mut chunk_cookie = cookie.clone();
+            chunk_cookie.set_name(format!("{name}.{index}"));
+            chunk_cookie.set_value(chunk.to_string());
+            res.add_cookie(chunk_cookie);
+        }
+        let removals = if chunks.is_empty() && stale.is_empty() {
+            vec![format!("{name}.0")]
+        } else {
+            stale
+        };
+        for removal_name in removals {
+            let mut removal = cookie.clone();
+            removal.set_name(removal_name);
+            removal.make_removal();
+            res.add_cookie(removal);
+        }
+    }
+}
+
+/// Carries the signed session value in a request and response header instead of cookies, for
+/// clients where cookies are awkward, such as gRPC-web or native apps.
+///
+/// The response header is set whenever the session handler would send the session cookie. When
+/// the cookie would be removed, the header is sent empty, telling the client to drop the value.
+/// Set it with [`HandlerBuilder::session_header`](crate::HandlerBuilder::session_header).
+#[derive(Clone, Debug)]
+pub struct SessionHeader {
+    name: HeaderName,
+}
+
+impl SessionHeader {
+    /// Create a new `SessionHeader` using the header called `name`.
+    #[inline]
+    pub fn new(name: HeaderName) -> Self {
+        Self { name }
+    }
+}
+
+impl CookieSource for SessionHeader {
+    #[inline]
+    fn cookie_value(&self, req: &Request, _name: &str) -> Option<String> {
+        req.header(&self.name)
+    }
+}
+
+impl CookieSink for SessionHeader {
+    fn set_cookie(&self, res: &mut Response, cookie: Cookie<'static>) {
+        match HeaderValue::from_str(cookie.value()) {
+            Ok(value) => {
+                res.headers_mut().insert(self.name.clone(), value);
+            }
+            Err(e) => tracing::error!(error = ?e, "invalid session header value"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_extra::test_util::SessionTestHarness;
+
+    use super::*;
+    use crate::{CookieStore, MemoryStore, SessionDepotExt, SessionHandler};
+
+    fn request(cookies: &str) -> Request {
+        TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookies, true)
+            .build()
+    }
+
+    fn response(req: &Request) -> Response {
+        Response::with_cookies(req.cookies().clone())
+    }
+
+    fn sent(res: &Response) -> Vec<(String, String)> {
+        let mut sent = res
+            .cookies()
+            .delta()
+            .map(|cookie| (cookie.name().to_owned(), cookie.value().to_owned()))
+            .collect::<Vec<_>>();
+        sent.sort();
+        sent
+    }
+
+    #[test]
+    fn test_split_value() {
+        assert_eq!(split_value("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(split_value("aé", 2), vec!["a", "é"]);
+        assert!(split_value("", 3).is_empty());
+    }
+
+    #[test]
+    fn test_chunked_cookies_read() {
+        let chunked = ChunkedCookies::new(3);
+        let req = request("sid.1=def; sid.0=abc; sid.2=g");
+        assert_eq!(
+            chunked.cookie_value(&req, "sid").as_deref(),
+            Some("abcdefg")
+        );
+        // A stale chunk after a gap is ignored.
+        let req = request("sid.0=abc; sid.1=de; sid.3=xyz");
+        assert_eq!(chunked.cookie_value(&req, "sid").as_deref(), Some("abcde"));
+        let req = request("sid.1=def");
+        assert_eq!(chunked.cookie_value(&req, "sid"), None);
+        let req = request("sid=whole; sidebar.0=x");
+        assert_eq!(chunked.cookie_value(&req, "sid").as_deref(), Some("whole"));
+    }
+
+    #[test]
+    fn test_chunked_cookies_grow_and_shrink() {
+        let chunked = ChunkedCookies::new(3);
+        let req = request("sid=abc");
+        let mut res = response(&req);
+        chunked.set_cookie(&mut res, Cookie::new("sid", "abcdefg"));
+        assert_eq!(
+            sent(&res),
+            vec![
+                ("sid".into(), "".into()),
+                ("sid.0".into(), "abc".into()),
+                ("sid.1".into(), "def".into()),
+                ("sid.2".into(), "g".into()),
+            ]
+        );
+
+        let req = request("sid.0=abc; sid.1=def; sid.2=g");
+        let mut res = response(&req);
+        chunked.set_cookie(&mut res, Cookie::new("sid", "xyz"));
+        assert_eq!(
+            sent(&res),
+            vec![
+                ("sid.0".into(), "xyz".into()),
+                ("sid.1".into(), "".into()),
+                ("sid.2".into(), "".into()),
+            ]
+        );
+
+        let mut res = response(&req);
+        let mut removal = Cookie::new("sid", "");
+        removal.make_removal();
+        chunked.set_cookie(&mut res, removal);
+        assert_eq!(
+            sent(&res),
+            vec![
+                ("sid.0".into(), "".into()),
+                ("sid.1".into(), "".into()),
+                ("sid.2".into(), "".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_cookie_lookup() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        for (enabled, expected) in [(false, "None"), (true, "Some(1)")] {
+            let session_handler = SessionHandler::builder(
+                MemoryStore::new(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .case_insensitive_cookie_lookup(enabled)
+            .build()
+            .unwrap();
+            let router = Router::new()
+                .hoop(session_handler)
+                .push(Router::with_path("login").post(login))
+                .get(current_user);
+            let service = Service::new(router);
+
+            let respone = TestClient::post("http://127.0.0.1:5800/login")
+                .send(&service)
+                .await;
+            let cookie = respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+            let cookie = cookie.replace("salvo.session.id=", "SALVO.SESSION.ID=");
+            let mut respone = TestClient::get("http://127.0.0.1:5800/")
+                .add_header(COOKIE, cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(respone.take_string().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shared_across_subdomains() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn user_id(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let secret = b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab";
+        for domain in ["com", "127.0.0.1", "example..com", "example.com:443"] {
+            assert!(
+                SessionHandler::builder(MemoryStore::new(), secret)
+                    .shared_across_subdomains(domain)
+                    .build()
+                    .is_err(),
+                "{domain}"
+            );
+        }
+        assert!(host_in_domain("api.Example.com:8080", "example.com"));
+        assert!(host_in_domain("example.com", "example.com"));
+        assert!(!host_in_domain("badexample.com", "example.com"));
+
+        let session_handler = SessionHandler::builder(MemoryStore::new(), secret)
+            .shared_across_subdomains(".Example.com")
+            .build()
+            .unwrap();
+        let router = Router::new().hoop(session_handler).get(user_id).post(login);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://app.example.com/")
+            .send(&service)
+            .await;
+        let cookie =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(cookie.domain(), Some("example.com"));
+        let mut respone = TestClient::get("http://api.example.com/")
+            .add_header(COOKIE, cookie.stripped().encoded().to_string(), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+
+        let respone = TestClient::post("http://example.org/").send(&service).await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_chunked_cookies() {
+        #[handler]
+        async fn set_note(req: &mut Request, depot: &mut Depot) {
+            let len = req.query::<usize>("len").unwrap();
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("note", "x".repeat(len))
+                .unwrap();
+        }
+        #[handler]
+        async fn note_len(depot: &mut Depot) -> String {
+            let note = depot.session().unwrap().get::<String>("note");
+            note.unwrap_or_default().len().to_string()
+        }
+        let session_handler = SessionHandler::builder(
+            CookieStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .chunked_cookies(200)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .post(set_note)
+            .get(note_len);
+        let mut harness = SessionTestHarness::new(router);
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/?len=1000"))
+            .await;
+        let chunks = (0..)
+            .take_while(|index| {
+                harness
+                    .cookie(&format!("salvo.session.id.{index}"))
+                    .is_some()
+            })
+            .count();
+        assert!(chunks > 5);
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "1000");
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/?len=10"))
+            .await;
+        assert!(harness.cookie("salvo.session.id.1").is_some());
+        for index in 3..chunks {
+            assert!(harness
+                .cookie(&format!("salvo.session.id.{index}"))
+                .is_none());
+        }
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "10");
+    }
+
+    #[tokio::test]
+    async fn test_detect_malformed_cookie_header() {
+        #[handler]
+        async fn show_diagnostics(depot: &mut Depot) -> String {
+            let diagnostics = depot.session_diagnostics().unwrap();
+            format!(
+                "{} {}",
+                diagnostics.cookie_present, diagnostics.malformed_cookie_header
+            )
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .detect_malformed_cookie_header(true)
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(show_diagnostics));
+
+        for (cookie, expected) in [
+            (None, "false false"),
+            (Some("theme=dark"), "false false"),
+            (Some("theme=dark; salvo.session.id"), "false true"),
+        ] {
+            let mut req = TestClient::get("http://127.0.0.1:5800/");
+            if let Some(cookie) = cookie {
+                req = req.add_header(COOKIE, cookie, true);
+            }
+            let mut respone = req.send(&service).await;
+            assert_eq!(respone.take_string().await.unwrap(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cookie_source_and_sink() {
+        struct SessionHeader;
+        impl CookieSource for SessionHeader {
+            fn cookie_value(&self, req: &Request, _name: &str) -> Option<String> {
+                req.header("x-session")
+            }
+        }
+        impl CookieSink for SessionHeader {
+            fn set_cookie(&self, res: &mut Response, cookie: Cookie<'static>) {
+                res.add_header("x-session", cookie.value(), true).unwrap();
+            }
+        }
+        #[handler]
+        async fn increment(depot: &mut Depot) -> String {
+            let session = depot.session_mut().unwrap();
+            let visits = session.get::<u32>("visits").unwrap_or_default() + 1;
+            session.insert("visits", visits).unwrap();
+            visits.to_string()
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_source(SessionHeader)
+        .cookie_sink(SessionHeader)
+        .build()
+        .unwrap();
+        let router = Router::new().hoop(session_handler).get(increment);
+        let service = Service::new(router);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert!(!respone.headers().contains_key(SET_COOKIE));
+        let token = respone.headers().get("x-session").unwrap().clone();
+        assert_eq!(respone.take_string().await.unwrap(), "1");
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("x-session", token, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_session_header() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            let user_id = depot.session().unwrap().get::<u32>("user_id");
+            depot.clear_session_cookie();
+            format!("{user_id:?}")
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .session_header(HeaderName::from_static("grpc-session"))
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .get(current_user);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        assert!(!respone.headers().contains_key(SET_COOKIE));
+        let token = respone.headers().get("grpc-session").unwrap().clone();
+        assert!(!token.is_empty());
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header("grpc-session", token, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.headers().get("grpc-session").unwrap(), "");
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+    }
+
+    #[tokio::test]
+    async fn test_host_prefixed() {
+        #[handler]
+        async fn index() -> &'static str {
+            "index"
+        }
+        let secret = b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab";
+        let session_handler = SessionHandler::builder(MemoryStore::new(), secret)
+            .cookie_name("id")
+            .host_prefixed()
+            .build()
+            .unwrap();
+        let router = Router::new().hoop(session_handler).get(index);
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&Service::new(router))
+            .await;
+        let cookie =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(cookie.name(), "__Host-id");
+        assert_eq!(cookie.secure(), Some(true));
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.domain(), None);
+
+        assert!(SessionHandler::builder(MemoryStore::new(), secret)
+            .host_prefixed()
+            .cookie_domain("example.com")
+            .build()
+            .is_err());
+        assert!(SessionHandler::builder(MemoryStore::new(), secret)
+            .host_prefixed()
+            .cookie_path("/app")
+            .build()
+            .is_err());
+    }
+}
@@ -0,0 +1,76 @@
+/// What the session handler found in the session cookie of the current request, for
+/// troubleshooting, returned by
+/// [`SessionDepotExt::session_diagnostics`](crate::SessionDepotExt::session_diagnostics).
+///
+/// It never holds the cookie value or any key material.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SessionDiagnostics {
+    /// Whether the request carried the session cookie.
+    pub cookie_present: bool,
+    /// Whether the `Cookie` header of the request had entries that couldn't be parsed, with
+    /// [`HandlerBuilder::detect_malformed_cookie_header`](crate::HandlerBuilder::detect_malformed_cookie_header)
+    /// and when the session cookie was not found.
+    pub malformed_cookie_header: bool,
+    /// Length of the session cookie value, or `0` if there was none.
+    pub cookie_len: usize,
+    /// Whether the cookie value could be split into a signature and a value with the configured
+    /// [`CookieEncoding`](crate::CookieEncoding).
+    pub well_formed: bool,
+    /// Whether the signature verified with one of the keys.
+    pub verified: bool,
+    /// Whether the signature verified with a fallback key rather than the current key, which
+    /// happens with cookies issued before a key rotation.
+    pub fallback_key: bool,
+    /// Whether a session was found in the store for the cookie.
+    pub loaded: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use cookie::Key;
+    use salvo_core::http::header::COOKIE;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use crate::{HandlerBuilder, MemoryStore, Session, SessionDepotExt};
+
+    #[tokio::test]
+    async fn test_session_diagnostics() {
+        #[handler]
+        async fn show_diagnostics(depot: &mut Depot) -> String {
+            let diagnostics = depot.session_diagnostics().unwrap();
+            format!(
+                "{} {} {} {}",
+                diagnostics.cookie_present,
+                diagnostics.verified,
+                diagnostics.fallback_key,
+                diagnostics.loaded
+            )
+        }
+        let store = MemoryStore::new();
+        let old_key = Key::generate();
+        let old_handler = HandlerBuilder::from_key(store.clone(), old_key.clone())
+            .build()
+            .unwrap();
+        let signed_value = old_handler.save(Session::new()).await.unwrap().unwrap();
+        let session_handler = HandlerBuilder::from_key(store, Key::generate())
+            .add_fallback_key(old_key)
+            .build()
+            .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(show_diagnostics));
+
+        for (cookie, expected) in [
+            (None, "false false false false"),
+            (Some(signed_value.clone()), "true true true true"),
+            (Some(format!("{signed_value}x")), "true false false false"),
+        ] {
+            let mut req = TestClient::get("http://127.0.0.1:5800/");
+            if let Some(cookie) = cookie {
+                req = req.add_header(COOKIE, format!("salvo.session.id={cookie}"), true);
+            }
+            let mut respone = req.send(&service).await;
+            assert_eq!(respone.take_string().await.unwrap(), expected);
+        }
+    }
+}
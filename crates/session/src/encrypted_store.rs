@@ -0,0 +1,218 @@
+use std::fmt::{self, Formatter};
+
+use async_session::{async_trait, serde_json, Error, Result, Session, SessionStore};
+use cookie::{Cookie, CookieJar, Key};
+
+use crate::{session_with_id, BatchSessionStore, PatchableStore, StoreInfo, StoreTelemetry};
+
+/// Key of the stored session data holding the encrypted session.
+const ENCRYPTED_KEY: &str = "__salvo.encrypted";
+
+/// Encrypts sessions before they reach another [`SessionStore`].
+///
+/// The whole session is serialized and encrypted with AES-256-GCM, using the encryption half of
+/// `key`, and stored in the inner store as a session with the same id and expiry but no readable
+/// data. A compromised store then leaks neither the session data nor a way to forge it.
+/// Sessions that fail to decrypt, for example after a key change, are treated as missing, so a
+/// new session is started. To change the key without that, keep the old key as a fallback with
+/// [`add_fallback_key`](Self::add_fallback_key): sessions encrypted with it still decrypt, and
+/// are encrypted with the new key when they are saved again.
+///
+/// Stores that look into session data, such as stores implementing
+/// [`UserSessionStore`](crate::UserSessionStore), can't do so through this wrapper.
+#[derive(Clone)]
+pub struct EncryptedStore<S> {
+    inner: S,
+    key: Key,
+    fallback_keys: Vec<Key>,
+}
+
+impl<S> fmt::Debug for EncryptedStore<S>
+where
+    S: SessionStore,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedStore")
+            .field("inner", &self.inner)
+            .field("key", &"..")
+            .field("fallback_keys", &"..")
+            .finish()
+    }
+}
+
+impl<S> EncryptedStore<S>
+where
+    S: SessionStore,
+{
+    /// Create a new `EncryptedStore` encrypting the sessions stored in `inner` with `key`.
+    pub fn new(inner: S, key: &Key) -> Self {
+        Self {
+            inner,
+            key: key.clone(),
+            fallback_keys: vec![],
+        }
+    }
+
+    /// Sets the keys tried, in order, to decrypt sessions that don't decrypt with the current
+    /// key.
+    #[inline]
+    pub fn fallback_keys(mut self, keys: Vec<impl Into<Key>>) -> Self {
+        self.fallback_keys = keys.into_iter().map(|key| key.into()).collect();
+        self
+    }
+
+    /// Adds a key tried to decrypt sessions that don't decrypt with the current key.
+    #[inline]
+    pub fn add_fallback_key(mut self, key: impl Into<Key>) -> Self {
+        self.fallback_keys.push(key.into());
+        self
+    }
+
+    /// Returns a reference to the inner store.
+    #[inline]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    // The session id is used as the name of the private cookie, which binds the ciphertext to
+    // the id: a record copied under another id doesn't decrypt.
+    fn encrypt(&self, id: &str, plaintext: String) -> Option<String> {
+        let mut jar = CookieJar::new();
+        jar.private_mut(&self.key)
+            .add(Cookie::new(id.to_owned(), plaintext));
+        jar.get(id).map(|cookie| cookie.value().to_owned())
+    }
+
+    fn decrypt(&self, id: &str, ciphertext: String) -> Option<String> {
+        let jar = CookieJar::new();
+        std::iter::once(&self.key)
+            .chain(&self.fallback_keys)
+            .find_map(|key| {
+                jar.private(key)
+                    .decrypt(Cookie::new(id.to_owned(), ciphertext.clone()))
+            })
+            .map(|cookie| cookie.value().to_owned())
+    }
+}
+
+#[async_trait]
+impl<S> SessionStore for EncryptedStore<S>
+where
+    S: SessionStore,
+{
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        let Some(stored) = self.inner.load_session(cookie_value).await? else {
+            return Ok(None);
+        };
+        let session = stored
+            .get::<String>(ENCRYPTED_KEY)
+            .and_then(|ciphertext| self.decrypt(stored.id(), ciphertext))
+            .and_then(|plaintext| serde_json::from_str::<Session>(&plaintext).ok())
+            .filter(|session| session.id() == stored.id());
+        if session.is_none() {
+            tracing::warn!(id = stored.id(), "unable to decrypt session");
+        }
+        Ok(session)
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        let plaintext = serde_json::to_string(&session)?;
+        let ciphertext = self
+            .encrypt(session.id(), plaintext)
+            .ok_or_else(|| Error::msg("unable to encrypt session"))?;
+        let mut stored = session_with_id(session.id());
+        if let Some(expiry) = session.expiry() {
+            stored.set_expiry(*expiry);
+        }
+        stored.insert(ENCRYPTED_KEY, ciphertext)?;
+        // Clones share the changed flag but not the cookie value, which is only set on new
+        // sessions.
+        let changed = session.clone();
+        if let Some(cookie_value) = session.into_cookie_value() {
+            stored.set_cookie_value(cookie_value);
+        }
+        let cookie_value = self.inner.store_session(stored).await?;
+        changed.reset_data_changed();
+        Ok(cookie_value)
+    }
+
+    async fn destroy_session(&self, session: Session) -> Result {
+        self.inner.destroy_session(session).await
+    }
+
+    async fn clear_store(&self) -> Result {
+        self.inner.clear_store().await
+    }
+}
+
+impl<S: SessionStore> BatchSessionStore for EncryptedStore<S> {}
+impl<S: SessionStore> PatchableStore for EncryptedStore<S> {}
+
+impl<S: SessionStore + StoreTelemetry> StoreTelemetry for EncryptedStore<S> {
+    fn store_telemetry(&self) -> StoreInfo {
+        self.inner.store_telemetry()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStore;
+
+    #[tokio::test]
+    async fn test_encrypted_store() {
+        let inner = MemoryStore::new();
+        let key = Key::generate();
+        let store = EncryptedStore::new(inner.clone(), &key);
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+
+        let stored = inner
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.get::<u32>("user_id"), None);
+        assert!(stored.get::<String>(ENCRYPTED_KEY).is_some());
+
+        let session = store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+
+        let other_key = EncryptedStore::new(inner, &Key::generate());
+        assert!(other_key
+            .load_session(cookie_value)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_fallback_keys() {
+        let inner = MemoryStore::new();
+        let old_key = Key::generate();
+        let old_store = EncryptedStore::new(inner.clone(), &old_key);
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let cookie_value = old_store.store_session(session).await.unwrap().unwrap();
+
+        let new_key = Key::generate();
+        let store = EncryptedStore::new(inner.clone(), &new_key).add_fallback_key(old_key);
+        let mut session = store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+
+        session.insert("user_id", 2).unwrap();
+        store.store_session(session).await.unwrap();
+        let new_only = EncryptedStore::new(inner, &new_key);
+        let session = new_only.load_session(cookie_value).await.unwrap().unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(2));
+    }
+}
@@ -0,0 +1,423 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+use async_session::chrono::{DateTime, Utc};
+
+use crate::{Session, SessionHandler, SessionStore, EXPIRES_KEY};
+
+/// Returns `time` plus `duration`, saturating at the latest representable time.
+fn add_duration(time: DateTime<Utc>, duration: Duration) -> DateTime<Utc> {
+    async_session::chrono::Duration::from_std(duration)
+        .ok()
+        .and_then(|duration| time.checked_add_signed(duration))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+impl<S> SessionHandler<S>
+where
+    S: SessionStore,
+{
+    /// Returns the current time, from [`HandlerBuilder::clock`](crate::HandlerBuilder::clock) if it is set.
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        match &self.options.clock {
+            Some(clock) => clock(),
+            None => Utc::now(),
+        }
+    }
+    /// Gives a loaded session back its actual expiry, see [`HandlerBuilder::grace_period`](crate::HandlerBuilder::grace_period), and
+    /// returns it unless it expired before the grace period.
+    pub(crate) fn check_expiry(&self, mut session: Session) -> Option<Session> {
+        if let Some(grace_period) = self.options.grace_period {
+            let expires = session
+                .get::<i64>(EXPIRES_KEY)
+                .and_then(|expires| DateTime::from_timestamp(expires, 0));
+            if let Some(expires) = expires {
+                session.set_expiry(expires);
+            }
+            if matches!(session.expiry(), Some(expiry) if add_duration(*expiry, grace_period) <= self.now())
+            {
+                tracing::debug!("session grace period is over, starting a new session");
+                return None;
+            }
+        } else if matches!(session.expiry(), Some(expiry) if *expiry <= self.now()) {
+            tracing::debug!("session is expired, starting a new session");
+            return None;
+        }
+        Some(session)
+    }
+    /// Gives the store a session expiry later by the grace period, keeping the actual expiry
+    /// under [`EXPIRES_KEY`], see [`HandlerBuilder::grace_period`](crate::HandlerBuilder::grace_period).
+    pub(crate) fn extend_for_grace(&self, session: &mut Session) {
+        let (Some(grace_period), Some(expiry)) =
+            (self.options.grace_period, session.expiry().copied())
+        else {
+            return;
+        };
+        if let Err(e) = session.insert(EXPIRES_KEY, expiry.timestamp()) {
+            tracing::error!(error = ?e, "unable to set session expiry");
+            return;
+        }
+        session.set_expiry(add_duration(expiry, grace_period));
+    }
+    /// Returns the ttl of `session`, including its expiry jitter, clamped to the maximum session
+    /// ttl.
+    pub(crate) fn ttl_for(&self, session: &Session) -> Option<Duration> {
+        let Some(session_ttl) = self.options.session_ttl else {
+            return self.options.max_session_ttl;
+        };
+        let ttl = match self.options.expiry_jitter {
+            Some(jitter) if !jitter.is_zero() => {
+                let mut hasher = DefaultHasher::new();
+                session.id().hash(&mut hasher);
+                let range = u64::try_from(jitter.as_millis()).unwrap_or(u64::MAX);
+                let offset = hasher.finish() % range.saturating_add(1);
+                session_ttl + Duration::from_millis(offset)
+            }
+            _ => session_ttl,
+        };
+        Some(self.clamp_ttl(ttl))
+    }
+    /// Clamps `ttl` to the maximum session ttl.
+    pub(crate) fn clamp_ttl(&self, ttl: Duration) -> Duration {
+        match self.options.max_session_ttl {
+            Some(max_session_ttl) => ttl.min(max_session_ttl),
+            None => ttl,
+        }
+    }
+    /// Clamps the expiry a handler set on `session` to the maximum session ttl.
+    pub(crate) fn clamp_expiry(&self, session: &mut Session) {
+        if let Some(max_session_ttl) = self.options.max_session_ttl {
+            let too_long = match session.expires_in() {
+                Some(remaining) => remaining > max_session_ttl,
+                // A session that already expired stays expired.
+                None => session.expiry().is_none(),
+            };
+            if too_long {
+                session.expire_in(max_session_ttl);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use cookie::Cookie;
+    use salvo_core::http::header::{HeaderName, COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_extra::test_util::SessionTestHarness;
+
+    use super::*;
+    use crate::{CookieStore, MemoryStore, SessionDepotExt, CREATED_KEY};
+
+    #[test]
+    fn test_expiry_jitter() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .session_ttl(Some(Duration::from_secs(60)))
+        .expiry_jitter(Duration::from_secs(30))
+        .build()
+        .unwrap();
+        let session = Session::new();
+        let ttl = handler.ttl_for(&session).unwrap();
+        assert!(ttl >= Duration::from_secs(60) && ttl <= Duration::from_secs(90));
+        assert_eq!(handler.ttl_for(&session), Some(ttl));
+    }
+
+    #[tokio::test]
+    async fn test_expiry_jitter_deferred() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .save_unchanged(false)
+        .session_ttl(Some(Duration::from_secs(60)))
+        .expiry_jitter(Duration::from_secs(3600))
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).post(login));
+
+        let mut expires = Vec::new();
+        for _ in 0..5 {
+            let respone = TestClient::post("http://127.0.0.1:5800/")
+                .send(&service)
+                .await;
+            let cookie = respone.cookies().get("salvo.session.id").unwrap();
+            expires.push(cookie.expires_datetime().unwrap().unix_timestamp());
+        }
+        // Without jitter per session, they would all expire within the same couple of seconds.
+        let spread = expires.iter().max().unwrap() - expires.iter().min().unwrap();
+        assert!(spread > 5);
+    }
+
+    #[tokio::test]
+    async fn test_session_age() {
+        #[handler]
+        async fn regenerate(depot: &mut Depot) {
+            depot.session_mut().unwrap().regenerate();
+        }
+        #[handler]
+        async fn age(depot: &mut Depot) -> String {
+            format!("{}", depot.session_age().is_some())
+        }
+        let secret = b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab";
+        let store = MemoryStore::new();
+        let handler = SessionHandler::builder(store.clone(), secret)
+            .build()
+            .unwrap();
+        let router = Router::new()
+            .hoop(SessionHandler::builder(store, secret).build().unwrap())
+            .push(Router::with_path("regenerate").post(regenerate))
+            .push(Router::with_path("age").get(age));
+        let service = Service::new(router);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/age")
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "true");
+        let cookie =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        let session = handler.load_by_cookie_value(cookie.value()).await.unwrap();
+        let created = session.get::<i64>(CREATED_KEY);
+        assert!(created.is_some());
+
+        let respone = TestClient::post("http://127.0.0.1:5800/regenerate")
+            .add_header(COOKIE, cookie.stripped().encoded().to_string(), true)
+            .send(&service)
+            .await;
+        // The first `Set-Cookie` header removes the previous cookie.
+        let set_cookie = respone
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .next_back()
+            .unwrap();
+        let cookie = Cookie::parse_encoded(set_cookie.to_str().unwrap()).unwrap();
+        let regenerated = handler.load_by_cookie_value(cookie.value()).await.unwrap();
+        assert_ne!(regenerated.id(), session.id());
+        assert_eq!(regenerated.get::<i64>(CREATED_KEY), created);
+
+        let mut session = Session::new();
+        session
+            .insert(CREATED_KEY, Utc::now().timestamp() - 60)
+            .unwrap();
+        let mut depot = Depot::new();
+        depot.set_session(session);
+        assert!(depot.session_age().unwrap() >= Duration::from_secs(60));
+        depot.reset_session_age();
+        assert!(depot.session_age().unwrap() < Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_threshold() {
+        #[handler]
+        async fn update(req: &mut Request, depot: &mut Depot) {
+            if let Some(value) = req.query::<u32>("value") {
+                depot.session_mut().unwrap().insert("value", value).unwrap();
+            }
+        }
+        let build_service = |threshold: Duration| {
+            let session_handler = SessionHandler::builder(
+                CookieStore::new(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .session_ttl(Some(Duration::from_secs(60 * 60)))
+            .refresh_threshold(threshold)
+            .build()
+            .unwrap();
+            Service::new(Router::new().hoop(session_handler).get(update))
+        };
+
+        let service = build_service(Duration::from_secs(60));
+        let respone = TestClient::get("http://127.0.0.1:5800/?value=1")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert!(!respone.headers().contains_key(SET_COOKIE));
+        let respone = TestClient::get("http://127.0.0.1:5800/?value=2")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert!(respone.headers().contains_key(SET_COOKIE));
+
+        let service = build_service(Duration::from_secs(2 * 60 * 60));
+        let respone = TestClient::get("http://127.0.0.1:5800/?value=1")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert!(respone.headers().contains_key(SET_COOKIE));
+    }
+
+    #[tokio::test]
+    async fn test_grace_period() {
+        use std::sync::atomic::{AtomicI64, Ordering};
+
+        #[handler]
+        async fn visit(depot: &mut Depot) -> String {
+            let session = depot.session_mut().unwrap();
+            let count = session.get::<u32>("count").unwrap_or_default() + 1;
+            session.insert("count", count).unwrap();
+            count.to_string()
+        }
+        let store = MemoryStore::new();
+        let offset = Arc::new(AtomicI64::new(0));
+        let clock_offset = offset.clone();
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .session_ttl(Some(Duration::from_secs(60 * 60)))
+        .grace_period(Duration::from_secs(10 * 60))
+        .refresh_threshold(Duration::from_secs(60))
+        .clock(move || {
+            Utc::now()
+                + async_session::chrono::Duration::seconds(clock_offset.load(Ordering::SeqCst))
+        })
+        .build()
+        .unwrap();
+
+        // The store is given the expiry plus the grace period.
+        let mut session = Session::new();
+        session.expire_in(Duration::from_secs(30 * 60));
+        let signed_value = session_handler.save(session).await.unwrap().unwrap();
+        let cookie_value = session_handler.verify_signature(&signed_value).unwrap();
+        let stored = store.load_session(cookie_value).await.unwrap().unwrap();
+        let expires = stored.get::<i64>(EXPIRES_KEY).unwrap();
+        assert_eq!(stored.expiry().unwrap().timestamp() - expires, 10 * 60);
+
+        let mut harness = SessionTestHarness::new(Router::new().hoop(session_handler).get(visit));
+        let url = "http://127.0.0.1:5800/";
+        let mut res = harness.send(TestClient::get(url)).await;
+        assert_eq!(res.take_string().await.unwrap(), "1");
+        let expires_in = harness
+            .cookie("salvo.session.id")
+            .unwrap()
+            .expires_datetime()
+            .unwrap()
+            - cookie::time::OffsetDateTime::now_utc();
+        assert!(expires_in <= cookie::time::Duration::minutes(60));
+
+        // Expired but in the grace period, the session is kept and refreshed.
+        offset.store(65 * 60, Ordering::SeqCst);
+        let mut res = harness.send(TestClient::get(url)).await;
+        assert!(res.cookies().get("salvo.session.id").is_some());
+        assert_eq!(res.take_string().await.unwrap(), "2");
+
+        // Past the grace period, a new session is started.
+        offset.store(75 * 60, Ordering::SeqCst);
+        let mut res = harness.send(TestClient::get(url)).await;
+        assert_eq!(res.take_string().await.unwrap(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_max_session_ttl() {
+        #[handler]
+        async fn remember_me(depot: &mut Depot) {
+            let session = depot.session_mut().unwrap();
+            session.insert("user_id", 1).unwrap();
+            session.expire_in(Duration::from_secs(10 * 365 * 24 * 60 * 60));
+        }
+        let store = MemoryStore::new();
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .session_ttl(Some(Duration::from_secs(2 * 24 * 60 * 60)))
+        .max_session_ttl(Duration::from_secs(24 * 60 * 60))
+        .build()
+        .unwrap();
+        let session = Session::new();
+        assert_eq!(
+            session_handler.ttl_for(&session),
+            Some(Duration::from_secs(24 * 60 * 60))
+        );
+        let service = Service::new(Router::new().hoop(session_handler).post(remember_me));
+
+        let respone = TestClient::post("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        let cookie =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        let expires_in =
+            cookie.expires_datetime().unwrap() - cookie::time::OffsetDateTime::now_utc();
+        assert!(expires_in <= cookie::time::Duration::days(1));
+        assert!(expires_in > cookie::time::Duration::hours(23));
+        let handler = SessionHandler::builder(
+            store,
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let session = handler.load_by_cookie_value(cookie.value()).await.unwrap();
+        assert!(session.expires_in().unwrap() <= Duration::from_secs(24 * 60 * 60));
+    }
+
+    #[tokio::test]
+    async fn test_expose_expiry_header() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn logout(depot: &mut Depot) {
+            depot.session_mut().unwrap().destroy();
+        }
+        #[handler]
+        async fn index() {}
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_on_empty(false)
+        .expose_expiry_header(Some(HeaderName::from_static("x-session-expires")))
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .get(index)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("logout").post(logout));
+        let mut harness = SessionTestHarness::new(router);
+
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert!(res.headers().get("x-session-expires").is_none());
+
+        let res = harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        let expires = res.headers().get("x-session-expires").unwrap();
+        let expires = expires.to_str().unwrap().parse::<i64>().unwrap();
+        let expires_in = expires - Utc::now().timestamp();
+        assert!((24 * 60 * 60 - 5..=24 * 60 * 60).contains(&expires_in));
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert!(res.headers().get("x-session-expires").is_some());
+
+        let res = harness
+            .send(TestClient::post("http://127.0.0.1:5800/logout"))
+            .await;
+        assert!(res.headers().get("x-session-expires").is_none());
+    }
+}
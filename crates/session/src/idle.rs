@@ -0,0 +1,22 @@
+use std::time::Duration;
+
+use async_session::{async_trait, Result, SessionStore};
+
+/// Session stores that can expire sessions by their last activity.
+///
+/// Sessions are matched on the unix timestamp stored under
+/// [`LAST_SEEN_KEY`](crate::LAST_SEEN_KEY), which the handler records when
+/// [`HandlerBuilder::track_last_seen`](crate::HandlerBuilder::track_last_seen) is set. This
+/// terminates sessions that have been idle for too long independently of their expiry, for
+/// example to meet a compliance rule. Call [`cleanup_idle`](Self::cleanup_idle) periodically.
+///
+/// Support is best-effort: this crate has no Redis or SQL store, so only `ShardedMemoryStore`
+/// implements it, by scanning its sessions. A persistent store from another crate must
+/// implement it itself, for example with an index on the last-seen time, which it has to keep
+/// up to date when a session is written.
+#[async_trait]
+pub trait IdleSessionStore: SessionStore {
+    /// Destroys the sessions that have not been seen for longer than `idle` and returns how many
+    /// were destroyed. Sessions without a last-seen time are kept.
+    async fn cleanup_idle(&self, idle: Duration) -> Result<usize>;
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use async_session::{serde_json, Session};
+
+use crate::patch::session_data;
+use crate::{IMPERSONATOR_KEY, RESERVED_PREFIX, USER_ID_KEY};
+
+/// Returns the serialized keys that make up the identity of the session user: the application
+/// keys and [`USER_ID_KEY`].
+fn identity_data(session: &Session) -> HashMap<String, String> {
+    let mut data = session_data(session);
+    data.retain(|key, _| !key.starts_with(RESERVED_PREFIX) || key == USER_ID_KEY);
+    data
+}
+
+/// Replaces the identity of `session` with the one of `user_session`, keeping the current one
+/// under [`IMPERSONATOR_KEY`]. Returns `false` if `session` is already impersonating.
+pub(crate) fn impersonate(session: &mut Session, user_session: &Session) -> bool {
+    if session.get_raw(IMPERSONATOR_KEY).is_some() {
+        return false;
+    }
+    let impersonator = identity_data(session);
+    let saved = match serde_json::to_string(&impersonator) {
+        Ok(saved) => saved,
+        Err(e) => {
+            tracing::error!(error = ?e, "unable to save impersonator");
+            return false;
+        }
+    };
+    for key in impersonator.keys() {
+        session.remove(key);
+    }
+    for (key, value) in identity_data(user_session) {
+        session.insert_raw(&key, value);
+    }
+    session.insert_raw(IMPERSONATOR_KEY, saved);
+    tracing::info!(
+        impersonator = impersonator_id(session),
+        user = session.get::<String>(USER_ID_KEY),
+        "impersonation started"
+    );
+    true
+}
+
+/// Restores the identity saved by [`impersonate`]. Returns `false` if `session` is not
+/// impersonating.
+pub(crate) fn stop_impersonation(session: &mut Session) -> bool {
+    let Some(impersonator) = session.get::<HashMap<String, String>>(IMPERSONATOR_KEY) else {
+        return false;
+    };
+    tracing::info!(
+        impersonator = impersonator_id(session),
+        user = session.get::<String>(USER_ID_KEY),
+        "impersonation stopped"
+    );
+    for key in identity_data(session).keys() {
+        session.remove(key);
+    }
+    for (key, value) in impersonator {
+        session.insert_raw(&key, value);
+    }
+    session.remove(IMPERSONATOR_KEY);
+    true
+}
+
+/// Returns the [`USER_ID_KEY`] of the impersonator, if `session` is impersonating.
+pub(crate) fn impersonator_id(session: &Session) -> Option<String> {
+    let impersonator = session.get::<HashMap<String, String>>(IMPERSONATOR_KEY)?;
+    serde_json::from_str(impersonator.get(USER_ID_KEY)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_impersonation() {
+        let mut session = Session::new();
+        session.insert(USER_ID_KEY, "admin").unwrap();
+        session.insert("theme", "dark").unwrap();
+        session.insert(crate::CREATED_KEY, 1).unwrap();
+        let mut user_session = Session::new();
+        user_session.insert(USER_ID_KEY, "alice").unwrap();
+        user_session.insert("cart", 3).unwrap();
+
+        assert!(impersonate(&mut session, &user_session));
+        assert!(!impersonate(&mut session, &user_session));
+        assert_eq!(session.get::<String>(USER_ID_KEY).as_deref(), Some("alice"));
+        assert_eq!(session.get::<u32>("cart"), Some(3));
+        assert_eq!(session.get::<String>("theme"), None);
+        assert_eq!(session.get::<i64>(crate::CREATED_KEY), Some(1));
+        assert_eq!(impersonator_id(&session).as_deref(), Some("admin"));
+
+        assert!(stop_impersonation(&mut session));
+        assert!(!stop_impersonation(&mut session));
+        assert_eq!(session.get::<String>(USER_ID_KEY).as_deref(), Some("admin"));
+        assert_eq!(session.get::<String>("theme").as_deref(), Some("dark"));
+        assert_eq!(session.get::<u32>("cart"), None);
+        assert_eq!(impersonator_id(&session), None);
+    }
+}
@@ -0,0 +1,172 @@
+use async_session::hmac::{Hmac, Mac, NewMac};
+use async_session::sha2::Sha256;
+
+/// Length of the output of SHA-256, the hash of the derivation.
+const HASH_LEN: usize = 32;
+
+/// Derives a secret of `N` bytes for the purpose named `context` from the `master` secret,
+/// with HKDF-SHA256 (RFC 5869) without salt.
+///
+/// Secrets derived with different contexts are independent, so one master secret can give a
+/// distinct key to each purpose, such as session signing, CSRF tokens and flash messages,
+/// instead of reusing the same key everywhere. The master secret should be at least 32 random
+/// bytes. [`HandlerBuilder::from_master_secret`](crate::HandlerBuilder::from_master_secret)
+/// uses this for the session key; other middlewares can request their own secret:
+///
+/// ```
+/// let master = b"01234567012345670123456701234567";
+/// let csrf_key: [u8; 32] = salvo_session::derive_secret(master, "salvo.csrf");
+/// let flash_key: [u8; 32] = salvo_session::derive_secret(master, "salvo.flash");
+/// assert_ne!(csrf_key, flash_key);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `N` is larger than 8160, the most HKDF-SHA256 can derive.
+pub fn derive_secret<const N: usize>(master: &[u8], context: &str) -> [u8; N] {
+    assert!(
+        N <= 255 * HASH_LEN,
+        "HKDF-SHA256 can't derive more than 8160 bytes"
+    );
+    let mut extract =
+        Hmac::<Sha256>::new_from_slice(&[0; HASH_LEN]).expect("HMAC should accept any key length");
+    extract.update(master);
+    let prk = extract.finalize().into_bytes();
+    let mut okm = [0; N];
+    let mut block = Vec::new();
+    for (counter, chunk) in (1..=u8::MAX).zip(okm.chunks_mut(HASH_LEN)) {
+        let mut expand =
+            Hmac::<Sha256>::new_from_slice(&prk).expect("HMAC should accept any key length");
+        expand.update(&block);
+        expand.update(context.as_bytes());
+        expand.update(&[counter]);
+        block = expand.finalize().into_bytes().to_vec();
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+    okm
+}
+
+#[cfg(test)]
+mod tests {
+    use cookie::Key;
+    use salvo_core::http::header::{COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+    use crate::{
+        CookieStore, EncryptedStore, MemoryStore, Session, SessionDepotExt, SessionHandler,
+        SessionStore,
+    };
+
+    #[test]
+    fn test_derive_secret() {
+        // RFC 5869, test case 3.
+        let okm: [u8; 42] = derive_secret(&[0x0b; 22], "");
+        assert_eq!(
+            hex::encode(okm),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"
+        );
+        let session: [u8; 64] = derive_secret(b"master", "salvo.session");
+        let csrf: [u8; 64] = derive_secret(b"master", "salvo.csrf");
+        assert_ne!(session, csrf);
+    }
+
+    #[tokio::test]
+    async fn test_derive_store_key() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            let session = depot.session_mut().unwrap();
+            session.regenerate();
+            session.insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let store = MemoryStore::new();
+        let handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .derive_store_key(true)
+        .build()
+        .unwrap();
+
+        let mut session = Session::new();
+        session.insert("user_id", 2).unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
+        let cookie_value = handler.verify_signature(&signed_value).unwrap();
+        assert!(store.load_session(cookie_value).await.unwrap().is_none());
+        let session = handler.load_by_cookie_value(&signed_value).await.unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(2));
+
+        let router = Router::new()
+            .hoop(handler)
+            .push(Router::with_path("login").post(login))
+            .get(current_user);
+        let service = Service::new(router);
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+    }
+
+    #[tokio::test]
+    async fn test_app_id() {
+        let store = MemoryStore::new();
+        let builder = |app_id: &str| {
+            SessionHandler::builder(
+                store.clone(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .app_id(app_id)
+            .build()
+            .unwrap()
+        };
+        let first = builder("first");
+        let second = builder("second");
+        let unset = builder("");
+
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let signed_value = first.save(session).await.unwrap().unwrap();
+        let session = first.load_by_cookie_value(&signed_value).await.unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+        assert!(second.load_by_cookie_value(&signed_value).await.is_none());
+        assert!(unset.load_by_cookie_value(&signed_value).await.is_none());
+
+        let signed_value = unset.save(Session::new()).await.unwrap().unwrap();
+        let cookie_value = unset.verify_signature(&signed_value).unwrap();
+        assert!(store.load_session(cookie_value).await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_derive_store_key_cookie_store() {
+        let key = b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab";
+        assert!(SessionHandler::builder(CookieStore::new(), key)
+            .derive_store_key(true)
+            .build()
+            .is_err());
+        assert!(SessionHandler::builder(
+            EncryptedStore::new(CookieStore::new(), &Key::from(key)),
+            key
+        )
+        .derive_store_key(true)
+        .build()
+        .is_err());
+        assert!(SessionHandler::builder(CookieStore::new(), key)
+            .app_id("app")
+            .build()
+            .is_err());
+        assert!(SessionHandler::builder(CookieStore::new(), key)
+            .app_id("")
+            .build()
+            .is_ok());
+    }
+}
@@ -0,0 +1,156 @@
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use async_session::hmac::{Hmac, NewMac};
+use async_session::sha2::Sha256;
+use cookie::Key;
+use salvo_core::{async_trait, Error};
+
+/// Signing keys returned by a [`KeyProvider`].
+#[derive(Clone)]
+pub struct SigningKeys {
+    /// Key used to sign new cookies and tried first when verifying.
+    pub primary: Key,
+    /// Keys still accepted when verifying, for example the previous primary key.
+    pub fallbacks: Vec<Key>,
+}
+
+impl SigningKeys {
+    /// Create new `SigningKeys` without fallback keys.
+    #[inline]
+    pub fn new(primary: Key) -> Self {
+        Self {
+            primary,
+            fallbacks: vec![],
+        }
+    }
+
+    /// Sets the fallback keys.
+    #[inline]
+    pub fn fallbacks(mut self, fallbacks: Vec<Key>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+}
+
+/// Provides the session signing keys at runtime, for example from a secrets manager.
+///
+/// Set it with [`HandlerBuilder::key_provider`](crate::HandlerBuilder::key_provider) to rotate
+/// the signing keys without restarting the process.
+#[async_trait]
+pub trait KeyProvider: Send + Sync + 'static {
+    /// Returns the current signing keys.
+    async fn signing_keys(&self) -> Result<SigningKeys, Error>;
+}
+
+/// HMACs derived from the signing keys.
+pub(crate) struct KeySet {
+    pub(crate) hmac: Hmac<Sha256>,
+    pub(crate) fallback_hmacs: Vec<Hmac<Sha256>>,
+}
+
+impl KeySet {
+    pub(crate) fn new(key: &Key, fallback_keys: &[Key]) -> Result<Self, Error> {
+        let hmac = Hmac::<Sha256>::new_from_slice(key.signing())
+            .map_err(|_| Error::Other("invalid key length".into()))?;
+        let fallback_hmacs = fallback_keys
+            .iter()
+            .map(|key| Hmac::<Sha256>::new_from_slice(key.signing()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| Error::Other("invalid key length".into()))?;
+        Ok(Self {
+            hmac,
+            fallback_hmacs,
+        })
+    }
+}
+
+/// Asks a [`KeyProvider`] for the signing keys at most once per refresh interval.
+pub(crate) struct KeyRefresher {
+    provider: Box<dyn KeyProvider>,
+    interval: Duration,
+    next_refresh: Mutex<Instant>,
+}
+
+impl Debug for KeyRefresher {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyRefresher")
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+impl KeyRefresher {
+    pub(crate) fn new(provider: impl KeyProvider, interval: Duration) -> Self {
+        Self {
+            provider: Box::new(provider),
+            interval,
+            next_refresh: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Replaces `keys` with the keys from the provider if the refresh interval has elapsed.
+    ///
+    /// Only one caller refreshes per interval, the others keep using the current keys. If the
+    /// provider fails, the current keys are kept until the next interval.
+    pub(crate) async fn refresh(&self, keys: &RwLock<Arc<KeySet>>) {
+        {
+            let mut next_refresh = self.next_refresh.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            if now < *next_refresh {
+                return;
+            }
+            *next_refresh = now + self.interval;
+        }
+        let key_set = self
+            .provider
+            .signing_keys()
+            .await
+            .and_then(|keys| KeySet::new(&keys.primary, &keys.fallbacks));
+        match key_set {
+            Ok(key_set) => *keys.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key_set),
+            Err(e) => tracing::error!(error = ?e, "unable to refresh session signing keys"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::prelude::*;
+
+    use super::*;
+    use crate::{HandlerBuilder, MemoryStore, Session};
+
+    #[tokio::test]
+    async fn test_key_provider() {
+        struct RotatingKeys(std::sync::Mutex<SigningKeys>);
+        #[async_trait]
+        impl KeyProvider for Arc<RotatingKeys> {
+            async fn signing_keys(&self) -> Result<SigningKeys, Error> {
+                Ok(self.0.lock().unwrap().clone())
+            }
+        }
+
+        let old_key = Key::generate();
+        let provider = Arc::new(RotatingKeys(std::sync::Mutex::new(SigningKeys::new(
+            old_key.clone(),
+        ))));
+        let handler = HandlerBuilder::from_key(MemoryStore::new(), Key::generate())
+            .key_provider(provider.clone(), Duration::ZERO)
+            .build()
+            .unwrap();
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let old_value = handler.save(session).await.unwrap().unwrap();
+        assert!(handler.load_by_cookie_value(&old_value).await.is_some());
+
+        *provider.0.lock().unwrap() = SigningKeys::new(Key::generate()).fallbacks(vec![old_key]);
+        assert!(handler.load_by_cookie_value(&old_value).await.is_some());
+        let new_value = handler.sign_value(&handler.verify_signature(&old_value).unwrap());
+        assert_ne!(new_value, old_value);
+
+        *provider.0.lock().unwrap() = SigningKeys::new(Key::generate());
+        assert!(handler.load_by_cookie_value(&old_value).await.is_none());
+    }
+}
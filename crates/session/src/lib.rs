@@ -18,6 +18,22 @@ external-datastore-backed session storage. For a list of currently
 available session stores, see [the documentation for
 async-session](https://github.com/http-rs/async-session).
 
+The `CookieStore` and `MemoryStore` of async-session are re-exported with
+the `cookie-store` and `memory-store` features, which are enabled by
+default. Disable them to build the handler with external stores only.
+
+For single-node deployments, the `sharded-memory-store` feature provides
+`ShardedMemoryStore`, an in-memory store with lower lock contention than
+`MemoryStore` under concurrent load.
+
+The `memcached-store` feature provides `MemcachedStore`, which keeps
+sessions in Memcached and lets it expire them.
+
+Any store can be wrapped in a `CachingStore` to keep recently loaded
+sessions in memory and save round trips to an external datastore.
+Any store can also be wrapped in an `EncryptedStore` to encrypt the
+sessions it keeps.
+
 ## Security
 
 Although each session store may have different security implications,
@@ -55,6 +71,14 @@ If there are any failures in the above session retrieval process, a
 new empty session is generated for the request, which proceeds through
 the application as normal.
 
+## Reserved keys
+
+Session keys starting with `__salvo.` (see `RESERVED_PREFIX`) are reserved
+for data stored by this crate, such as the creation time of the session.
+Application keys must not use this prefix; other keys, including keys
+starting with `_` such as `_csrf`, are left to the application. Internal keys that belong to
+options that are no longer enabled are removed when the session is saved.
+
 ## Stale/expired session cleanup
 
 Any session store other than the cookie store will accumulate stale
@@ -69,23 +93,287 @@ Read more: <https://salvo.rs>
 #![doc(html_logo_url = "https://salvo.rs/images/logo.svg")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-pub use async_session::{CookieStore, MemoryStore, Session, SessionStore};
+pub use async_session::{Session, SessionStore};
+
+#[macro_use]
+mod cfg;
+
+cfg_feature! {
+    #![feature = "cookie-store"]
+
+    pub use async_session::CookieStore;
+}
+cfg_feature! {
+    #![feature = "memory-store"]
+
+    pub use async_session::MemoryStore;
+}
+
+mod auth_flow;
+mod batch_store;
+mod binding;
+mod caching_store;
+mod clear_site_data;
+mod config;
+mod cookie_encoding;
+mod cookies;
+mod diagnostics;
+mod encrypted_store;
+mod expiry;
+mod idle;
+mod impersonation;
+mod key_derivation;
+mod key_provider;
+mod new_session_limit;
+mod patch;
+mod rand_source;
+mod require_session;
+mod revocation;
+mod session_tx;
+mod signature;
+mod signer;
+mod telemetry;
+mod user_sessions;
+pub use auth_flow::AuthFlow;
+pub use batch_store::BatchSessionStore;
+pub use binding::SessionBinding;
+pub use caching_store::CachingStore;
+pub use clear_site_data::ClearSiteData;
+pub use config::SessionConfig;
+pub use cookie_encoding::CookieEncoding;
+pub use cookies::{ChunkedCookies, CookieSink, CookieSource, HttpCookies, SessionHeader};
+pub use diagnostics::SessionDiagnostics;
+pub use encrypted_store::EncryptedStore;
+pub use idle::IdleSessionStore;
+pub use key_derivation::derive_secret;
+pub use key_provider::{KeyProvider, SigningKeys};
+pub use patch::{PatchableStore, SessionPatch};
+pub use rand_source::{OsRandSource, RandSource};
+pub use require_session::{local_return_to, RequireSession};
+pub use revocation::{MemoryRevocationList, NotBefore, RevocationList};
+pub use session_tx::SessionTx;
+pub use signer::Signer;
+pub use telemetry::{StoreInfo, StoreTelemetry};
+pub use user_sessions::UserSessionStore;
+
+cfg_feature! {
+    #![feature = "sharded-memory-store"]
+
+    mod sharded_memory_store;
+    pub use sharded_memory_store::ShardedMemoryStore;
+}
+#[cfg(any(test, feature = "test-util"))]
+mod recording_store;
+#[cfg(any(test, feature = "test-util"))]
+#[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+pub use recording_store::{RecordingStore, StoreOperation};
+cfg_feature! {
+    #![feature = "memcached-store"]
+
+    mod memcached_store;
+    pub use memcached_store::MemcachedStore;
+}
+cfg_feature! {
+    #![feature = "oapi"]
+
+    mod oapi;
+    pub use oapi::{SessionRouterExt, SESSION_SECURITY_SCHEME};
+}
 
 use std::fmt::{self, Formatter};
+use std::net::IpAddr;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 use async_session::base64;
+use async_session::chrono::{DateTime, Utc};
 use async_session::hmac::{Hmac, Mac, NewMac};
+use async_session::serde_json;
 use async_session::sha2::Sha256;
 use cookie::{Cookie, Key, SameSite};
-use salvo_core::http::uri::Scheme;
+use salvo_core::http::header::{HeaderName, HeaderValue, HOST, USER_AGENT};
+use salvo_core::http::uri::{Scheme, Uri};
+use salvo_core::http::{Method, StatusCode};
+use salvo_core::writing::Redirect;
 use salvo_core::{async_trait, Depot, Error, FlowCtrl, Handler, Request, Response};
+use serde::Serialize;
+use tracing::Instrument;
 
-/// Key for store data in depot.
+use crate::clear_site_data::CLEAR_SITE_DATA;
+use crate::cookies::{cookie_header_malformed, host_in_domain, shared_domain_error};
+use crate::key_provider::{KeyRefresher, KeySet};
+use crate::new_session_limit::NewSessionLimiter;
+use crate::patch::{patch_session, renew_session, session_data, PatchFn, RenewFn};
+use crate::session_tx::{PendingChanges, PENDING_KEY};
+
+/// Key for store data in depot, used by the session handler with the default cookie name. Other
+/// cookie names get their own key, see [`session_depot_key`].
 pub const SESSION_KEY: &str = "::salvo::session";
-const BASE64_DIGEST_LEN: usize = 44;
+/// Key for the depot key of the session of the innermost session handler in depot.
+const CURRENT_SESSION_KEY: &str = "::salvo::session::current";
+/// Context of the key derived from the secret for [`HandlerBuilder::derive_store_key`].
+const STORE_KEY_CONTEXT: &str = "salvo.session.store_key";
+/// Default name of the session cookie.
+const DEFAULT_COOKIE_NAME: &str = "salvo.session.id";
+/// Key for the flag that only clears the session cookie in depot.
+const CLEAR_COOKIE_KEY: &str = "::salvo::session::clear_cookie";
+/// Key for the `SameSite` override of the session cookie in depot.
+const SAME_SITE_KEY: &str = "::salvo::session::same_site";
+/// Key for whether the session handler treats the request as secure in depot.
+const SECURE_KEY: &str = "::salvo::session::secure";
+/// Key for the [`SessionDiagnostics`] of the request in depot.
+const DIAGNOSTICS_KEY: &str = "::salvo::session::diagnostics";
+/// Key for whether the session handler sends a cookie with the response in depot.
+const WILL_SET_COOKIE_KEY: &str = "::salvo::session::will_set_cookie";
+/// Key for the volatile keys of the session in depot.
+const VOLATILE_KEY: &str = "::salvo::session::volatile";
+/// Key for the [`SessionError`] of the last session write in depot.
+const ERROR_KEY: &str = "::salvo::session::error";
+/// Session key where [`RequireSession`] remembers the url to return to after login.
+pub const RETURN_TO_KEY: &str = "__salvo.return_to";
+/// Session key holding the session version when [`HandlerBuilder::track_version`] is enabled.
+pub const VERSION_KEY: &str = "__salvo.version";
+/// Session key holding the fingerprint of the client when [`HandlerBuilder::bind_to`] is set.
+pub const BINDING_KEY: &str = "__salvo.binding";
+/// Session key holding the unix timestamp at which the session was created.
+pub const CREATED_KEY: &str = "__salvo.created";
+/// Session key holding the id of the logged in user, as a string, for stores implementing
+/// [`UserSessionStore`]. The application sets it at login.
+pub const USER_ID_KEY: &str = "__salvo.user_id";
+/// Session key holding the unix timestamp at which the session was last used, when
+/// [`HandlerBuilder::track_last_seen`] is set.
+pub const LAST_SEEN_KEY: &str = "__salvo.last_seen";
+/// Session key holding the id of the session that replaced this one, when
+/// [`HandlerBuilder::rotate_every_request`] is enabled. Only superseded records carry it.
+pub const SUPERSEDED_BY_KEY: &str = "__salvo.superseded_by";
+/// Session key holding the identity of the user impersonating another one, see
+/// [`SessionDepotExt::impersonate`].
+pub const IMPERSONATOR_KEY: &str = "__salvo.impersonator";
+/// Session key holding the unix timestamp at which the session expires when
+/// [`HandlerBuilder::grace_period`] is set, since the store is given an expiry later by the grace
+/// period.
+pub const EXPIRES_KEY: &str = "__salvo.expires";
+/// Prefix of the session keys reserved for internal use by this crate, such as
+/// [`CREATED_KEY`]. Application keys must not start with it.
+pub const RESERVED_PREFIX: &str = "__salvo.";
+/// Internal keys that are only set with an option of the handler.
+const OPTIONAL_KEYS: [&str; 4] = [VERSION_KEY, BINDING_KEY, LAST_SEEN_KEY, EXPIRES_KEY];
+
+/// Returns the depot key of the session of the handler whose cookie is called `cookie_name`, as
+/// set with [`HandlerBuilder::cookie_name`].
+///
+/// This is [`SESSION_KEY`] for the default cookie name, so several session handlers with
+/// different cookie names run on the same route without overwriting each other's session.
+pub fn session_depot_key(cookie_name: &str) -> String {
+    if cookie_name == DEFAULT_COOKIE_NAME {
+        SESSION_KEY.to_owned()
+    } else {
+        format!("{SESSION_KEY}::{cookie_name}")
+    }
+}
+
+cfg_feature! {
+    #![feature = "cookie-store"]
+
+    use std::any::TypeId;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Whether the warning of [`HandlerBuilder::warn_insecure_cookie_store`] was logged.
+    static INSECURE_COOKIE_STORE_WARNED: AtomicBool = AtomicBool::new(false);
+
+    /// Whether `S` is a plain [`CookieStore`].
+    fn is_cookie_store<S: 'static>() -> bool {
+        TypeId::of::<S>() == TypeId::of::<CookieStore>()
+    }
+
+    /// Whether `S` keeps the session in the cookie value, as a [`CookieStore`] does, so it
+    /// can't find sessions under a store key.
+    fn stores_in_cookie<S: 'static>() -> bool {
+        is_cookie_store::<S>() || TypeId::of::<S>() == TypeId::of::<EncryptedStore<CookieStore>>()
+    }
+}
+
+/// Returns the depot key of the session of the innermost session handler.
+fn current_session_key(depot: &Depot) -> String {
+    depot
+        .get::<String>(CURRENT_SESSION_KEY)
+        .map_or_else(|_| SESSION_KEY.to_owned(), Clone::clone)
+}
+
+/// Returns the path and query of `uri` without the query parameter `param`.
+fn without_query_param(uri: &Uri, param: &str) -> String {
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(
+            form_urlencoded::parse(uri.query().unwrap_or_default().as_bytes())
+                .filter(|(name, _)| name != param),
+        )
+        .finish();
+    if query.is_empty() {
+        uri.path().to_owned()
+    } else {
+        format!("{}?{query}", uri.path())
+    }
+}
+
+/// Returns an empty session with the id `id` and no cookie value.
+pub(crate) fn session_with_id(id: &str) -> Session {
+    // `Session` has no constructor taking an id, but deserializes from one.
+    serde_json::from_value(serde_json::json!({ "id": id, "expiry": null, "data": {} }))
+        .expect("session should deserialize from an empty data map")
+}
+
+/// Creates a copy of `session` that doesn't share its data with the original.
+pub(crate) fn detach(session: &Session) -> Option<Session> {
+    serde_json::to_string(session)
+        .and_then(|data| serde_json::from_str(&data))
+        .ok()
+}
+
+/// Returns an empty session without id, standing for a new session until it is saved, see
+/// [`HandlerBuilder::save_unchanged`].
+fn placeholder_session() -> Session {
+    serde_json::from_str(r#"{"id":"","expiry":null,"data":{}}"#)
+        .expect("session should deserialize from an empty data map")
+}
+
+/// Errors reported by session stores and the session handler.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SessionError {
+    /// The session was written by someone else since it was loaded, so storing it would
+    /// silently overwrite their changes. Reload the session and retry.
+    VersionConflict,
+    /// The session cookie would be `size` bytes long, over the `budget` set with
+    /// [`HandlerBuilder::cookie_size_budget`], so it was not sent.
+    TooLarge {
+        /// Length of the name and value of the refused cookie.
+        size: usize,
+        /// The configured budget.
+        budget: usize,
+    },
+}
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionError::VersionConflict => write!(f, "session was modified concurrently"),
+            SessionError::TooLarge { size, budget } => {
+                write!(
+                    f,
+                    "session cookie of {size} bytes exceeds the budget of {budget} bytes"
+                )
+            }
+        }
+    }
+}
+impl std::error::Error for SessionError {}
 
 /// Trait for `Depot` to get and set session.
+///
+/// When several session handlers run on a route, the methods without a cookie name apply to
+/// the session of the innermost one, and [`named_session`](Self::named_session) reaches the
+/// others.
 pub trait SessionDepotExt {
     /// Sets session
     fn set_session(&mut self, session: Session) -> &mut Self;
@@ -95,52 +383,502 @@ pub trait SessionDepotExt {
     fn session(&self) -> Option<&Session>;
     /// Get session mutable reference
     fn session_mut(&mut self) -> Option<&mut Session>;
+    /// Get a reference to the session of the handler whose cookie is called `cookie_name`, as
+    /// set with [`HandlerBuilder::cookie_name`].
+    fn named_session(&self, cookie_name: &str) -> Option<&Session>;
+    /// Get a mutable reference to the session of the handler whose cookie is called
+    /// `cookie_name`, as set with [`HandlerBuilder::cookie_name`].
+    fn named_session_mut(&mut self, cookie_name: &str) -> Option<&mut Session>;
+    /// Destroys the current session, see [`Session::destroy`]. Returns `false` if there is no
+    /// session.
+    fn destroy_session(&mut self) -> bool;
+    /// Get the local url that [`RequireSession::login_redirect`] remembered before
+    /// redirecting to the login page, if it is safe to redirect to.
+    fn login_return_to(&self) -> Option<String>;
+    /// Get the sorted keys of the current session, without the keys used internally, which start
+    /// with `_`. Useful to dump the session contents while debugging.
+    fn session_keys(&self) -> Vec<String>;
+    /// Get the time elapsed since the current session was created, for example to ask for the
+    /// password again before a sensitive action. Returns `None` for sessions created before the
+    /// creation time was recorded.
+    ///
+    /// The age is kept when the session is regenerated, use
+    /// [`reset_session_age`](Self::reset_session_age) to restart it.
+    fn session_age(&self) -> Option<Duration>;
+    /// Restart the age of the current session from now, for example after the user has
+    /// authenticated again.
+    fn reset_session_age(&mut self);
+    /// Get transactional access to the session, whose changes are only saved if the response
+    /// is successful. See [`SessionTx`].
+    fn session_tx(&mut self) -> SessionTx<'_>;
+    /// Inserts `value` under `key` in the current session for the rest of the request only.
+    ///
+    /// The key is removed from the session before it is saved, so volatile values don't survive
+    /// to the next request, and setting one doesn't make the session worth saving. Returns
+    /// `false` if there is no session.
+    fn session_set_volatile(
+        &mut self,
+        key: &str,
+        value: impl Serialize,
+    ) -> Result<bool, serde_json::Error>;
+    /// Overrides the `SameSite` attribute of the session cookie for the current response only,
+    /// for example `SameSite::None` for an OAuth callback or a payment iframe. `SameSite::None`
+    /// also makes the cookie `Secure`, as browsers require.
+    ///
+    /// The session cookie is sent again with the override even if it would not be otherwise.
+    /// For an external login whose identity provider posts back cross-site, set
+    /// `SameSite::None` on the response that redirects to the provider: the cookie then
+    /// reaches the callback, whose response sends it back with the configured policy.
+    fn set_session_same_site(&mut self, same_site: SameSite) -> &mut Self;
+    /// Removes the session cookie from the client without touching the store.
+    ///
+    /// The response gets a removal cookie with the same scope as the session cookie, and the
+    /// session is neither saved nor destroyed. Unlike [`Session::destroy`], the stored session
+    /// stays valid for anyone still holding the cookie, so this is mostly useful with
+    /// [`CookieStore`], where the cookie is the session, or to make the client authenticate
+    /// again without logging out other requests.
+    fn clear_session_cookie(&mut self) -> &mut Self;
+    /// Whether the session handler treats the current request as coming over a secure
+    /// connection, which is also what decides the `Secure` attribute of the session cookie.
+    ///
+    /// This is `true` for https requests and for every request with
+    /// [`HandlerBuilder::host_prefixed`], and `false` outside of a session handler.
+    fn request_is_secure(&self) -> bool;
+    /// Returns what the session handler found in the session cookie of the request, such as
+    /// whether its signature verified, to troubleshoot lost sessions.
+    ///
+    /// This is `None` outside of a session handler.
+    fn session_diagnostics(&self) -> Option<&SessionDiagnostics>;
+    /// Returns why the session handler couldn't save the session of the request, such as
+    /// [`SessionError::TooLarge`] or [`SessionError::VersionConflict`].
+    ///
+    /// The session is saved once the handlers inside the session handler are done, so this is
+    /// for middleware running around it.
+    fn session_error(&self) -> Option<&SessionError>;
+    /// Whether the session handler sends the session cookie, or a cookie removing it, with the
+    /// response, for example to leave responses that set a cookie out of a cache or to add
+    /// `Vary: Cookie`.
+    ///
+    /// It is set when the handler adds the cookie to the response, once the handlers inside it
+    /// are done and the session is saved, so this is for middleware running around it. A cookie
+    /// the handler meant to send but didn't, because the store gave no cookie value, the cookie
+    /// went over [`HandlerBuilder::cookie_size_budget`] or saving failed, leaves it `false`. A
+    /// precommitted cookie, see [`HandlerBuilder::precommit_cookie`], is known from the start.
+    /// This is `false` outside of a session handler.
+    fn session_will_set_cookie(&self) -> bool;
+    /// Makes the current session act as the user of `user_session`, for admins viewing the
+    /// application as a user.
+    ///
+    /// The identity of the session, its application keys and [`USER_ID_KEY`], is saved under
+    /// [`IMPERSONATOR_KEY`] and replaced with the one of `user_session`, and is restored by
+    /// [`stop_impersonation`](Self::stop_impersonation). Both identities are saved with the
+    /// session, so [`impersonator`](Self::impersonator) can tell audit logs who is really
+    /// acting. Returns `false` if there is no session or it is already impersonating.
+    fn impersonate(&mut self, user_session: &Session) -> bool;
+    /// Restores the identity saved by [`impersonate`](Self::impersonate). Returns `false` if
+    /// there is no session or it is not impersonating.
+    fn stop_impersonation(&mut self) -> bool;
+    /// Returns the [`USER_ID_KEY`] of the user impersonating the current one, if any.
+    fn impersonator(&self) -> Option<String>;
 }
 
 impl SessionDepotExt for Depot {
     #[inline]
     fn set_session(&mut self, session: Session) -> &mut Self {
-        self.insert(SESSION_KEY, session);
+        self.insert(current_session_key(self), session);
         self
     }
     #[inline]
     fn take_session(&mut self) -> Option<Session> {
-        self.remove(SESSION_KEY).ok()
+        self.remove(&current_session_key(self)).ok()
     }
     #[inline]
     fn session(&self) -> Option<&Session> {
-        self.get(SESSION_KEY).ok()
+        self.get(&current_session_key(self)).ok()
     }
     #[inline]
     fn session_mut(&mut self) -> Option<&mut Session> {
-        self.get_mut(SESSION_KEY).ok()
+        self.get_mut(&current_session_key(self)).ok()
+    }
+    #[inline]
+    fn named_session(&self, cookie_name: &str) -> Option<&Session> {
+        self.get(&session_depot_key(cookie_name)).ok()
+    }
+    #[inline]
+    fn named_session_mut(&mut self, cookie_name: &str) -> Option<&mut Session> {
+        self.get_mut(&session_depot_key(cookie_name)).ok()
+    }
+    #[inline]
+    fn destroy_session(&mut self) -> bool {
+        self.session_mut().map(Session::destroy).is_some()
+    }
+    #[inline]
+    fn login_return_to(&self) -> Option<String> {
+        self.session()
+            .and_then(|session| session.get::<String>(RETURN_TO_KEY))
+            .filter(|next| local_return_to(next).is_some())
+    }
+    fn session_keys(&self) -> Vec<String> {
+        let mut keys = self.session().map(user_keys).unwrap_or_default();
+        keys.sort();
+        keys
+    }
+    fn session_age(&self) -> Option<Duration> {
+        let created = self.session()?.get::<i64>(CREATED_KEY)?;
+        let created = DateTime::from_timestamp(created, 0)?;
+        Utc::now().signed_duration_since(created).to_std().ok()
+    }
+    #[inline]
+    fn clear_session_cookie(&mut self) -> &mut Self {
+        self.insert(CLEAR_COOKIE_KEY, true);
+        self
+    }
+    #[inline]
+    fn set_session_same_site(&mut self, same_site: SameSite) -> &mut Self {
+        self.insert(SAME_SITE_KEY, same_site);
+        self
+    }
+    #[inline]
+    fn request_is_secure(&self) -> bool {
+        self.get::<bool>(SECURE_KEY).ok().copied().unwrap_or(false)
+    }
+    #[inline]
+    fn session_diagnostics(&self) -> Option<&SessionDiagnostics> {
+        self.get::<SessionDiagnostics>(DIAGNOSTICS_KEY).ok()
+    }
+    #[inline]
+    fn session_error(&self) -> Option<&SessionError> {
+        self.get::<SessionError>(ERROR_KEY).ok()
+    }
+    #[inline]
+    fn session_will_set_cookie(&self) -> bool {
+        self.get::<bool>(WILL_SET_COOKIE_KEY)
+            .ok()
+            .copied()
+            .unwrap_or(false)
+    }
+    fn impersonate(&mut self, user_session: &Session) -> bool {
+        self.session_mut()
+            .is_some_and(|session| impersonation::impersonate(session, user_session))
+    }
+    fn stop_impersonation(&mut self) -> bool {
+        self.session_mut()
+            .is_some_and(impersonation::stop_impersonation)
+    }
+    fn impersonator(&self) -> Option<String> {
+        self.session().and_then(impersonation::impersonator_id)
+    }
+    #[inline]
+    fn session_tx(&mut self) -> SessionTx<'_> {
+        SessionTx::new(self)
+    }
+    fn session_set_volatile(
+        &mut self,
+        key: &str,
+        value: impl Serialize,
+    ) -> Result<bool, serde_json::Error> {
+        let Some(session) = self.session_mut() else {
+            return Ok(false);
+        };
+        let changed = session.data_changed();
+        session.insert(key, value)?;
+        if !changed {
+            session.reset_data_changed();
+        }
+        if self.get::<Vec<String>>(VOLATILE_KEY).is_err() {
+            self.insert(VOLATILE_KEY, Vec::<String>::new());
+        }
+        if let Ok(keys) = self.get_mut::<Vec<String>>(VOLATILE_KEY) {
+            keys.push(key.to_owned());
+        }
+        Ok(true)
+    }
+    fn reset_session_age(&mut self) {
+        if let Some(session) = self.session_mut() {
+            if let Err(e) = session.insert(CREATED_KEY, Utc::now().timestamp()) {
+                tracing::error!(error = ?e, "unable to reset session age");
+            }
+        }
     }
 }
 
-/// `HandlerBuilder` is a builder for [`SessionHandler`].
-pub struct HandlerBuilder<S> {
-    store: S,
+/// Returns all the keys of the session.
+fn all_keys(session: &Session) -> Vec<String> {
+    session_data(session).into_keys().collect()
+}
+
+/// Returns the keys of the session that are not used internally.
+fn user_keys(session: &Session) -> Vec<String> {
+    let mut keys = all_keys(session);
+    keys.retain(|key| !key.starts_with(RESERVED_PREFIX));
+    keys
+}
+
+/// Parses the content of a key file, see [`HandlerBuilder::from_key_file`].
+fn key_from_file_content(content: &[u8]) -> Result<Key, String> {
+    const KEY_LEN: usize = 64;
+    if content.len() == KEY_LEN {
+        return Ok(Key::from(content));
+    }
+    let text = std::str::from_utf8(content).map_err(|_| {
+        format!(
+            "expected {KEY_LEN} raw bytes, found {} bytes",
+            content.len()
+        )
+    })?;
+    let encoded = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with("-----"))
+        .collect::<String>();
+    let encoded = encoded.trim_end_matches('=');
+    let decoded = base64::decode_config(encoded, base64::STANDARD_NO_PAD)
+        .or_else(|_| base64::decode_config(encoded, base64::URL_SAFE_NO_PAD))
+        .map_err(|e| format!("invalid base64: {e}"))?;
+    if decoded.len() != KEY_LEN {
+        return Err(format!(
+            "expected a base64 encoded key of {KEY_LEN} bytes, found {} bytes",
+            decoded.len()
+        ));
+    }
+    Ok(Key::from(&decoded))
+}
+
+/// Function run on sessions, set with [`HandlerBuilder::on_load_migrate`],
+/// [`HandlerBuilder::before_save`] or [`HandlerBuilder::after_load`].
+type SessionFn = Box<dyn Fn(&mut Session) + Send + Sync>;
+/// Function run on the session after the request is handled, set with
+/// [`HandlerBuilder::after_handle`].
+type AfterHandleFn = Box<dyn Fn(&Request, &mut Session) + Send + Sync>;
+/// Source of the current time, set with [`HandlerBuilder::clock`].
+type ClockFn = Box<dyn Fn() -> DateTime<Utc> + Send + Sync>;
+/// Check run on loaded sessions, set with [`HandlerBuilder::validate_fn`].
+type ValidateFn = Box<dyn Fn(&Session) -> bool + Send + Sync>;
+/// Decides whether requests are secure, set with [`HandlerBuilder::cookie_secure_fn`].
+type SecureFn = Box<dyn Fn(&Request) -> bool + Send + Sync>;
+/// Decides whether the handler skips a request, set with [`HandlerBuilder::skip_if`].
+type SkipFn = Box<dyn Fn(&Request) -> bool + Send + Sync>;
+/// Extractor of store keys from requests, set with [`HandlerBuilder::session_key_extractor`].
+type KeyExtractorFn = Box<dyn Fn(&Request) -> Option<String> + Send + Sync>;
+
+/// Options of [`HandlerBuilder`], moved whole into the [`SessionHandler`] it builds.
+struct HandlerOptions<S> {
     cookie_path: String,
     cookie_name: String,
     cookie_domain: Option<String>,
+    shared_across_subdomains: bool,
     session_ttl: Option<Duration>,
+    expiry_jitter: Option<Duration>,
+    refresh_threshold: Option<Duration>,
+    grace_period: Option<Duration>,
+    clock: Option<ClockFn>,
+    max_session_ttl: Option<Duration>,
     save_unchanged: bool,
+    persist_methods: Option<Vec<Method>>,
+    precommit_cookie: bool,
+    cookie_on_empty: bool,
+    require_valid: bool,
+    track_version: bool,
+    rotate_every_request: bool,
+    last_seen_resolution: Option<Duration>,
+    on_load_migrate: Option<SessionFn>,
+    revocation_list: Option<Box<dyn RevocationList>>,
+    not_before: Option<NotBefore>,
+    before_save: Option<SessionFn>,
+    after_load: Option<SessionFn>,
+    validate_fn: Option<ValidateFn>,
+    cookie_size_budget: Option<usize>,
+    cookie_secure_fn: Option<SecureFn>,
+    skip_if: Option<SkipFn>,
+    case_insensitive_cookie_lookup: bool,
+    detect_malformed_cookie_header: bool,
+    after_handle: Option<AfterHandleFn>,
+    derive_store_key: bool,
+    app_id: String,
+    store_info: Option<StoreInfo>,
+    patch_session: Option<PatchFn<S>>,
+    renew_session: Option<RenewFn<S>>,
+    save_on_cease: bool,
+    clear_site_data: Option<HeaderValue>,
+    tx_rollback_status: StatusCode,
+    skip_save_on_error: bool,
+    host_prefixed: bool,
+    key_provider: Option<KeyRefresher>,
+    new_session_limit: Option<NewSessionLimiter>,
+    binding: Option<SessionBinding>,
+    client_ip_header: Option<HeaderName>,
+    expose_expiry_header: Option<HeaderName>,
+    cookie_source: Box<dyn CookieSource>,
+    cookie_sink: Box<dyn CookieSink>,
+    session_key_extractor: Option<KeyExtractorFn>,
+    rand_source: Arc<dyn RandSource>,
     same_site_policy: SameSite,
-    key: Key,
-    fallback_keys: Vec<Key>,
+    omit_same_site: bool,
+    cookie_encoding: CookieEncoding,
+    query_token_param: Option<String>,
+    detached_signature: bool,
+    warn_insecure_cookie_store: bool,
+    cookie_template: Option<Cookie<'static>>,
 }
-impl<S: SessionStore> fmt::Debug for HandlerBuilder<S> {
+impl<S> Default for HandlerOptions<S> {
+    fn default() -> Self {
+        Self {
+            cookie_path: "/".into(),
+            cookie_name: DEFAULT_COOKIE_NAME.into(),
+            cookie_domain: None,
+            shared_across_subdomains: false,
+            session_ttl: Some(Duration::from_secs(24 * 60 * 60)),
+            expiry_jitter: None,
+            refresh_threshold: None,
+            grace_period: None,
+            clock: None,
+            max_session_ttl: None,
+            save_unchanged: true,
+            persist_methods: None,
+            precommit_cookie: false,
+            cookie_on_empty: true,
+            require_valid: false,
+            track_version: false,
+            rotate_every_request: false,
+            last_seen_resolution: None,
+            on_load_migrate: None,
+            revocation_list: None,
+            not_before: None,
+            before_save: None,
+            after_load: None,
+            validate_fn: None,
+            cookie_size_budget: None,
+            cookie_secure_fn: None,
+            skip_if: None,
+            case_insensitive_cookie_lookup: false,
+            detect_malformed_cookie_header: false,
+            after_handle: None,
+            derive_store_key: false,
+            app_id: String::new(),
+            store_info: None,
+            patch_session: None,
+            renew_session: None,
+            save_on_cease: false,
+            clear_site_data: None,
+            tx_rollback_status: StatusCode::BAD_REQUEST,
+            skip_save_on_error: false,
+            host_prefixed: false,
+            key_provider: None,
+            new_session_limit: None,
+            binding: None,
+            client_ip_header: None,
+            expose_expiry_header: None,
+            cookie_source: Box::new(HttpCookies),
+            cookie_sink: Box::new(HttpCookies),
+            session_key_extractor: None,
+            rand_source: Arc::new(OsRandSource),
+            same_site_policy: SameSite::Lax,
+            omit_same_site: false,
+            cookie_encoding: CookieEncoding::StandardBase64,
+            query_token_param: None,
+            detached_signature: false,
+            warn_insecure_cookie_store: true,
+            cookie_template: None,
+        }
+    }
+}
+impl<S> fmt::Debug for HandlerOptions<S> {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("HandlerBuilder")
-            .field("store", &self.store)
+        f.debug_struct("HandlerOptions")
             .field("cookie_path", &self.cookie_path)
             .field("cookie_name", &self.cookie_name)
             .field("cookie_domain", &self.cookie_domain)
+            .field("shared_across_subdomains", &self.shared_across_subdomains)
             .field("session_ttl", &self.session_ttl)
+            .field("expiry_jitter", &self.expiry_jitter)
+            .field("refresh_threshold", &self.refresh_threshold)
+            .field("grace_period", &self.grace_period)
+            .field("clock", &self.clock.as_ref().map(|_| ".."))
+            .field("max_session_ttl", &self.max_session_ttl)
+            .field("save_unchanged", &self.save_unchanged)
+            .field("persist_methods", &self.persist_methods)
+            .field("precommit_cookie", &self.precommit_cookie)
+            .field("cookie_on_empty", &self.cookie_on_empty)
+            .field("require_valid", &self.require_valid)
+            .field("track_version", &self.track_version)
+            .field("rotate_every_request", &self.rotate_every_request)
+            .field("last_seen_resolution", &self.last_seen_resolution)
+            .field(
+                "on_load_migrate",
+                &self.on_load_migrate.as_ref().map(|_| ".."),
+            )
+            .field(
+                "revocation_list",
+                &self.revocation_list.as_ref().map(|_| ".."),
+            )
+            .field("not_before", &self.not_before)
+            .field("before_save", &self.before_save.as_ref().map(|_| ".."))
+            .field("after_load", &self.after_load.as_ref().map(|_| ".."))
+            .field("validate_fn", &self.validate_fn.as_ref().map(|_| ".."))
+            .field("cookie_size_budget", &self.cookie_size_budget)
+            .field(
+                "cookie_secure_fn",
+                &self.cookie_secure_fn.as_ref().map(|_| ".."),
+            )
+            .field("skip_if", &self.skip_if.as_ref().map(|_| ".."))
+            .field(
+                "case_insensitive_cookie_lookup",
+                &self.case_insensitive_cookie_lookup,
+            )
+            .field(
+                "detect_malformed_cookie_header",
+                &self.detect_malformed_cookie_header,
+            )
+            .field("after_handle", &self.after_handle.as_ref().map(|_| ".."))
+            .field("derive_store_key", &self.derive_store_key)
+            .field("app_id", &self.app_id)
+            .field("store_info", &self.store_info)
+            .field("patch_writes", &self.patch_session.is_some())
+            .field("save_on_cease", &self.save_on_cease)
+            .field("clear_site_data", &self.clear_site_data)
+            .field("tx_rollback_status", &self.tx_rollback_status)
+            .field("skip_save_on_error", &self.skip_save_on_error)
+            .field("host_prefixed", &self.host_prefixed)
+            .field("key_provider", &self.key_provider)
+            .field("new_session_limit", &self.new_session_limit)
+            .field("binding", &self.binding)
+            .field("client_ip_header", &self.client_ip_header)
+            .field("expose_expiry_header", &self.expose_expiry_header)
+            .field("cookie_source", &"..")
+            .field("cookie_sink", &"..")
+            .field(
+                "session_key_extractor",
+                &self.session_key_extractor.as_ref().map(|_| ".."),
+            )
+            .field("rand_source", &"..")
             .field("same_site_policy", &self.same_site_policy)
+            .field("omit_same_site", &self.omit_same_site)
+            .field("cookie_encoding", &self.cookie_encoding)
+            .field("query_token_param", &self.query_token_param)
+            .field("detached_signature", &self.detached_signature)
+            .field(
+                "warn_insecure_cookie_store",
+                &self.warn_insecure_cookie_store,
+            )
+            .field("cookie_template", &self.cookie_template)
+            .finish()
+    }
+}
+
+/// `HandlerBuilder` is a builder for [`SessionHandler`].
+pub struct HandlerBuilder<S> {
+    store: S,
+    key: Key,
+    fallback_keys: Vec<Key>,
+    options: HandlerOptions<S>,
+}
+impl<S: SessionStore> fmt::Debug for HandlerBuilder<S> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlerBuilder")
+            .field("store", &self.store)
             .field("key", &"..")
             .field("fallback_keys", &"..")
-            .field("save_unchanged", &self.save_unchanged)
+            .field("options", &self.options)
             .finish()
     }
 }
@@ -150,18 +888,51 @@ where
     S: SessionStore,
 {
     /// Create new `HandlerBuilder`
+    ///
+    /// The signing key is created with [`Key::from`], so `secret` must be at least 64 bytes
+    /// long. Use [`from_key`](Self::from_key) to pass a key you already hold.
     #[inline]
     pub fn new(store: S, secret: &[u8]) -> Self {
+        Self::from_key(store, Key::from(secret))
+    }
+
+    /// Create new `HandlerBuilder` with a key derived from a `master` secret shared with other
+    /// purposes, such as CSRF protection, see [`derive_secret`].
+    ///
+    /// `context` labels the purpose of the key, for example `"salvo.session"`. Handlers with
+    /// different contexts get different keys from the same master secret.
+    #[inline]
+    pub fn from_master_secret(store: S, master: &[u8], context: &str) -> Self {
+        Self::from_key(store, Key::from(&derive_secret::<64>(master, context)))
+    }
+
+    /// Create new `HandlerBuilder` with the key stored in the file at `path`, read once at
+    /// startup.
+    ///
+    /// The file holds the 64 bytes of a [`Key::master`], either raw or encoded in base64,
+    /// standard or url-safe, with or without padding. Around the base64 text, whitespace and
+    /// `-----BEGIN ...-----` and `-----END ...-----` lines, as in a PEM file, are ignored. An
+    /// error is returned if the file can't be read or doesn't hold a key of the right length.
+    pub fn from_key_file(store: S, path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let content = std::fs::read(path)?;
+        let key = key_from_file_content(&content).map_err(|reason| {
+            Error::other(format!("invalid key file `{}`: {reason}", path.display()))
+        })?;
+        Ok(Self::from_key(store, key))
+    }
+
+    /// Create new `HandlerBuilder` from a pre-built [`Key`].
+    ///
+    /// This lets you choose how the key is made, for example with [`Key::generate`],
+    /// [`Key::derive_from`] or from a stored key file, instead of always using [`Key::from`].
+    #[inline]
+    pub fn from_key(store: S, key: Key) -> Self {
         Self {
             store,
-            save_unchanged: true,
-            cookie_path: "/".into(),
-            cookie_name: "salvo.session.id".into(),
-            cookie_domain: None,
-            same_site_policy: SameSite::Lax,
-            session_ttl: Some(Duration::from_secs(24 * 60 * 60)),
-            key: Key::from(secret),
+            key,
             fallback_keys: vec![],
+            options: HandlerOptions::default(),
         }
     }
 
@@ -170,7 +941,7 @@ where
     /// The default for this value is "/".
     #[inline]
     pub fn cookie_path(mut self, cookie_path: impl Into<String>) -> Self {
-        self.cookie_path = cookie_path.into();
+        self.options.cookie_path = cookie_path.into();
         self
     }
 
@@ -181,7 +952,70 @@ where
     /// set a cookie or session expiry. This is not recommended.
     #[inline]
     pub fn session_ttl(mut self, session_ttl: Option<Duration>) -> Self {
-        self.session_ttl = session_ttl;
+        self.options.session_ttl = session_ttl;
+        self
+    }
+
+    /// Sets a maximum random offset added to the session ttl.
+    ///
+    /// Sessions created in a burst, for example right after a deploy, would otherwise all
+    /// expire at the same moment. With a jitter, each session gets an extra lifetime between
+    /// zero and `jitter`. The offset is derived from the session id, so it stays the same on
+    /// every request and is used for both the cookie expiry and the session-internal expiry.
+    #[inline]
+    pub fn expiry_jitter(mut self, jitter: Duration) -> Self {
+        self.options.expiry_jitter = Some(jitter);
+        self
+    }
+
+    /// Keeps accepting a session for `grace_period` after it expires, to smooth over clock skew
+    /// between servers. A session loaded in its grace period is refreshed and its cookie sent
+    /// again; past it, a new session is started as usual.
+    ///
+    /// Stores drop sessions once they expire, so the expiry given to the store is later by
+    /// `grace_period` and the actual expiry is kept under [`EXPIRES_KEY`]. The cookie expiry and
+    /// the header of [`expose_expiry_header`](Self::expose_expiry_header) give the actual
+    /// expiry. Sessions stored before the option was enabled, or by a store that drops them
+    /// earlier than the expiry it is given, get no grace period.
+    #[inline]
+    pub fn grace_period(mut self, grace_period: Duration) -> Self {
+        self.options.grace_period = Some(grace_period);
+        self
+    }
+
+    /// Sets the source of the current time used to decide whether a loaded session is expired
+    /// or in its [`grace_period`](Self::grace_period), for example to test expiry without
+    /// waiting. New expiries are still computed from the system time.
+    ///
+    /// The default is [`Utc::now`].
+    #[inline]
+    pub fn clock(mut self, clock: impl Fn() -> DateTime<Utc> + Send + Sync + 'static) -> Self {
+        self.options.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Sets a ceiling on the lifetime of sessions and their cookies.
+    ///
+    /// The session ttl, and any expiry a handler sets on the session, for example with
+    /// [`Session::expire_in`], are clamped to `max_session_ttl`, so a handler can't hand out a
+    /// cookie that outlives the policy. Without a session ttl, sessions expire after
+    /// `max_session_ttl`.
+    #[inline]
+    pub fn max_session_ttl(mut self, max_session_ttl: Duration) -> Self {
+        self.options.max_session_ttl = Some(max_session_ttl);
+        self
+    }
+
+    /// Only extends the expiry of an existing session, and sends its cookie again, when its
+    /// remaining lifetime drops below `threshold`.
+    ///
+    /// By default the expiry slides on every request, so stores that put the session in the
+    /// cookie send a new `Set-Cookie` header with each response. With a threshold, the cookie
+    /// is only sent again when the session is close to expiring, when its data changed or when
+    /// it was regenerated. The session is still saved to the store as usual.
+    #[inline]
+    pub fn refresh_threshold(mut self, threshold: Duration) -> Self {
+        self.options.refresh_threshold = Some(threshold);
         self
     }
 
@@ -190,9 +1024,24 @@ where
     /// If you are running multiple tide applications on the same
     /// domain, you will need different values for each
     /// application. The default value is "salvo.session_id".
+    ///
+    /// Handlers with different cookie names keep their sessions apart in the depot, see
+    /// [`SessionDepotExt::named_session`].
     #[inline]
     pub fn cookie_name(mut self, cookie_name: impl Into<String>) -> Self {
-        self.cookie_name = cookie_name.into();
+        self.options.cookie_name = cookie_name.into();
+        self
+    }
+
+    /// Only saves the session for requests with one of `methods`, such as `POST`, `PUT`,
+    /// `PATCH` and `DELETE`. By default the session is saved for every method.
+    ///
+    /// Requests with other methods, typically `GET` and `HEAD`, can read the session but
+    /// their changes are not saved, new sessions get no cookie and the expiry of existing
+    /// sessions is not extended. Destroying a session is honored for every method.
+    #[inline]
+    pub fn persist_methods(mut self, methods: Vec<Method>) -> Self {
+        self.options.persist_methods = Some(methods);
         self
     }
 
@@ -205,270 +1054,2911 @@ where
     /// already exists and its data unmodified in the course of a
     /// request, the session will only be persisted if
     /// `save_unchanged` is enabled.
+    ///
+    /// With `save_unchanged` disabled, requests without a session cookie also get a
+    /// placeholder session, which saves generating a session id for anonymous visitors who
+    /// never write to their session. It becomes a real session, with its own id, once it is
+    /// saved at the end of the request, so its [`Session::id`] is empty while the request is
+    /// handled.
     #[inline]
     pub fn save_unchanged(mut self, value: bool) -> Self {
-        self.save_unchanged = value;
+        self.options.save_unchanged = value;
         self
     }
 
-    /// Sets the same site policy for the session cookie. Defaults to
-    /// SameSite::Lax. See [incrementally better
-    /// cookies](https://tools.ietf.org/html/draft-west-cookie-incrementalism-01)
-    /// for more information about this setting.
+    /// Sets the `precommit_cookie` value.
+    ///
+    /// When `precommit_cookie` is enabled and the request carries a valid
+    /// session cookie, the refreshed cookie is added to the response before
+    /// the next handler is called. This keeps the `Set-Cookie` header on
+    /// streaming responses (such as SSE) whose headers are sent before the
+    /// handler chain completes.
+    ///
+    /// Mutations made to the session after the cookie has been precommitted
+    /// are still saved to the store, but they will not be reflected in the
+    /// cookie. Only enable this for routes that treat the session as
+    /// read-only, or with stores that keep the data server side.
     #[inline]
-    pub fn same_site_policy(mut self, policy: SameSite) -> Self {
-        self.same_site_policy = policy;
+    pub fn precommit_cookie(mut self, value: bool) -> Self {
+        self.options.precommit_cookie = value;
         self
     }
 
-    /// Sets the domain of the cookie.
+    /// Sets the `cookie_on_empty` value.
+    ///
+    /// When `cookie_on_empty` is disabled, a brand-new session that is still
+    /// empty at the end of the request is neither stored nor sent to the
+    /// client, even if `save_unchanged` is enabled. Existing sessions are
+    /// persisted as usual, so anonymous visitors don't start a session until
+    /// something is written into it.
+    ///
+    /// The default for this value is `true`.
     #[inline]
-    pub fn cookie_domain(mut self, cookie_domain: impl AsRef<str>) -> Self {
-        self.cookie_domain = Some(cookie_domain.as_ref().to_owned());
+    pub fn cookie_on_empty(mut self, value: bool) -> Self {
+        self.options.cookie_on_empty = value;
         self
     }
-    /// Sets fallbacks.
+
+    /// Whether requests without an existing session get `401 Unauthorized`, `false` by default.
+    ///
+    /// When the session cookie is missing, doesn't verify, or its session can't be loaded, the
+    /// handler responds with `401` and skips the rest of the chain instead of starting an empty
+    /// session. This makes the handler suitable only for subtrees whose users are already
+    /// authenticated, such as an admin area mounted after a login handled elsewhere, and saves
+    /// a separate [`RequireSession`] hoop there.
     #[inline]
-    pub fn fallback_keys(mut self, keys: Vec<impl Into<Key>>) -> Self {
-        self.fallback_keys = keys.into_iter().map(|s| s.into()).collect();
+    pub fn require_valid(mut self, require_valid: bool) -> Self {
+        self.options.require_valid = require_valid;
         self
     }
 
-    /// Add fallback secret.
+    /// Sets the `track_version` value.
+    ///
+    /// When enabled, every stored session carries a version under [`VERSION_KEY`] that is
+    /// incremented on each write. Stores that support conditional writes, such as
+    /// `ShardedMemoryStore`, reject a write whose version is not
+    /// newer than the stored one with [`SessionError::VersionConflict`], which prevents lost
+    /// updates from concurrent requests. Other stores ignore the version.
+    ///
+    /// The changes of a request whose write is rejected are discarded and the conflict is
+    /// reported by [`SessionDepotExt::session_error`], so a middleware around the session
+    /// handler can answer `409 Conflict` and let the client retry with the current session.
+    ///
+    /// The default for this value is `false`.
     #[inline]
-    pub fn add_fallback_key(mut self, key: impl Into<Key>) -> Self {
-        self.fallback_keys.push(key.into());
+    pub fn track_version(mut self, value: bool) -> Self {
+        self.options.track_version = value;
         self
     }
 
-    /// Build `SessionHandler`
-    pub fn build(self) -> Result<SessionHandler<S>, Error> {
-        let Self {
-            store,
-            save_unchanged,
-            cookie_path,
-            cookie_name,
-            cookie_domain,
-            session_ttl,
-            same_site_policy,
-            key,
-            fallback_keys,
-        } = self;
-        let hmac = Hmac::<Sha256>::new_from_slice(key.signing())
-            .map_err(|_| Error::Other("invalid key length".into()))?;
-        let fallback_hmacs = fallback_keys
-            .iter()
-            .map(|key| Hmac::<Sha256>::new_from_slice(key.signing()))
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_| Error::Other("invalid key length".into()))?;
-        Ok(SessionHandler {
-            store,
-            save_unchanged,
-            cookie_path,
-            cookie_name,
-            cookie_domain,
-            session_ttl,
-            same_site_policy,
-            hmac,
-            fallback_hmacs,
-        })
+    /// Gives the session a new id on every request, and a new cookie value with it, so a stolen
+    /// cookie stops working as soon as the legitimate client makes its next request.
+    ///
+    /// The record under the previous id is kept until the session expires and marks it as
+    /// superseded. When a superseded cookie is sent again, the session is considered stolen:
+    /// both the superseded record and the current session are destroyed and a new session is
+    /// started.
+    ///
+    /// Concurrent requests of the same client carry the same cookie, so all but the first are
+    /// taken for theft: only enable it for clients that send one request at a time. It requires a
+    /// server-side store; with [`CookieStore`] there is no record to mark as superseded.
+    /// Requests whose method is not persisted, see [`persist_methods`](Self::persist_methods),
+    /// and responses of ceased requests keep the current id.
+    ///
+    /// The default for this value is `false`.
+    #[inline]
+    pub fn rotate_every_request(mut self, value: bool) -> Self {
+        self.options.rotate_every_request = value;
+        self
     }
-}
 
-/// `SessionHandler` is a middleware for session.
-pub struct SessionHandler<S> {
-    store: S,
-    cookie_path: String,
-    cookie_name: String,
-    cookie_domain: Option<String>,
-    session_ttl: Option<Duration>,
-    save_unchanged: bool,
-    same_site_policy: SameSite,
-    hmac: Hmac<Sha256>,
-    fallback_hmacs: Vec<Hmac<Sha256>>,
-}
-impl<S: SessionStore> fmt::Debug for SessionHandler<S> {
+    /// Records when each session was last used under [`LAST_SEEN_KEY`], for stores implementing
+    /// [`IdleSessionStore`] to expire idle sessions.
+    ///
+    /// The time is updated at most once per `resolution`, as each update writes the session to
+    /// the store. A resolution of a few minutes is precise enough for idle limits counted in days.
     #[inline]
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("SessionHandler")
-            .field("store", &self.store)
-            .field("cookie_path", &self.cookie_path)
-            .field("cookie_name", &self.cookie_name)
-            .field("cookie_domain", &self.cookie_domain)
-            .field("session_ttl", &self.session_ttl)
-            .field("same_site_policy", &self.same_site_policy)
-            .field("key", &"..")
-            .field("fallback_keys", &"..")
-            .field("save_unchanged", &self.save_unchanged)
-            .finish()
+    pub fn track_last_seen(mut self, resolution: Duration) -> Self {
+        self.options.last_seen_resolution = Some(resolution);
+        self
     }
-}
-#[async_trait]
-impl<S> Handler for SessionHandler<S>
-where
-    S: SessionStore,
+
+    /// Sets a migration run on every session loaded from the store, before handlers see it.
+    ///
+    /// It lets old sessions be converted when the shape of the session data changes, for
+    /// example by renaming a key, instead of logging everybody out. Changes made by the
+    /// migration are saved with the session. If the migration panics, the session is dropped
+    /// and a new one is started.
+    #[inline]
+    pub fn on_load_migrate(
+        mut self,
+        migrate: impl Fn(&mut Session) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.on_load_migrate = Some(Box::new(migrate));
+        self
+    }
+
+    /// Sets a function run on the session right before it is saved to the store, for example to
+    /// remove keys that must only live for the request, such as a decrypted secret.
+    ///
+    /// Changes made by the function are what the store gets, they are not seen by handlers.
+    #[inline]
+    pub fn before_save(
+        mut self,
+        before_save: impl Fn(&mut Session) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.before_save = Some(Box::new(before_save));
+        self
+    }
+
+    /// Sets a function run on every session loaded from the store, after
+    /// [`on_load_migrate`](Self::on_load_migrate), for example to rebuild the keys removed by
+    /// [`before_save`](Self::before_save).
+    ///
+    /// Unlike a migration, its changes alone don't make the session worth saving.
+    #[inline]
+    pub fn after_load(mut self, after_load: impl Fn(&mut Session) + Send + Sync + 'static) -> Self {
+        self.options.after_load = Some(Box::new(after_load));
+        self
+    }
+
+    /// Sets a function run on the session once the handlers inside the session handler are
+    /// done, before it is saved, for example to record the last page visited in every session.
+    ///
+    /// Its changes count as changes of the session, so they are saved. It is not run for
+    /// destroyed sessions.
+    #[inline]
+    pub fn after_handle(
+        mut self,
+        after_handle: impl Fn(&Request, &mut Session) + Send + Sync + 'static,
+    ) -> Self {
+        self.options.after_handle = Some(Box::new(after_handle));
+        self
+    }
+
+    /// Makes the store find sessions under a keyed hash of the cookie value instead of the
+    /// cookie value itself, `false` by default.
+    ///
+    /// The hash uses a key derived from the secret, so knowing the keys of a store, for example
+    /// from a leaked backup, doesn't give the cookie values, and the other way around. The key
+    /// is derived from the secret the handler is built with, so changing the secret loses the
+    /// sessions even with fallback keys, and so does enabling or disabling this option.
+    ///
+    /// A [`CookieStore`], plain or in an [`EncryptedStore`], keeps the session in the cookie
+    /// value itself and has no key to hash, so [`build`](Self::build) returns an error when this
+    /// option is enabled with it.
+    #[inline]
+    pub fn derive_store_key(mut self, enabled: bool) -> Self {
+        self.options.derive_store_key = enabled;
+        self
+    }
+
+    /// Sets an identifier of the application mixed into the key under which the store finds
+    /// sessions, empty by default.
+    ///
+    /// With different ids, applications sharing a store and a secret find the same cookie value
+    /// under different keys, so a session of one application is never loaded by another.
+    /// Changing the id loses the existing sessions. It combines with
+    /// [`derive_store_key`](Self::derive_store_key); an empty id leaves the keys unchanged.
+    ///
+    /// Like `derive_store_key`, a non-empty id can't be used with a [`CookieStore`], plain or in
+    /// an [`EncryptedStore`], and [`build`](Self::build) returns an error for it.
+    #[inline]
+    pub fn app_id(mut self, app_id: impl Into<String>) -> Self {
+        self.options.app_id = app_id.into();
+        self
+    }
+
+    /// Sets a function consulted on every session loaded from the store, after the built-in
+    /// expiry check. When it returns `false`, the session is treated as invalid and a new session
+    /// is started.
+    ///
+    /// Combined with a timestamp read from shared state, it can log every user out after a
+    /// security event by refusing the sessions created before it, see [`CREATED_KEY`].
+    #[inline]
+    pub fn validate_fn(
+        mut self,
+        validate: impl Fn(&Session) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.options.validate_fn = Some(Box::new(validate));
+        self
+    }
+
+    /// Refuses destroyed sessions whose id is in `list`, see [`RevocationList`].
+    ///
+    /// Sessions destroyed by a handler or with [`SessionHandler::destroy`] are added to the list
+    /// until they expire. This gives [`CookieStore`] sessions a real logout, as copies of their
+    /// cookie are otherwise accepted until they expire.
+    #[inline]
+    pub fn revocation_list(mut self, list: impl RevocationList) -> Self {
+        self.options.revocation_list = Some(Box::new(list));
+        self
+    }
+
+    /// Refuses the sessions created before the cutoff of `not_before`, which can be moved
+    /// while the handler runs to log every user out at once, see [`NotBefore`].
+    #[inline]
+    pub fn not_before(mut self, not_before: NotBefore) -> Self {
+        self.options.not_before = Some(not_before);
+        self
+    }
+
+    /// Sets the `save_on_cease` value.
+    ///
+    /// By default, the session is not saved when a following handler ceases the flow with
+    /// [`FlowCtrl::cease`]. When enabled, the session is saved as usual, so changes made before
+    /// ceasing, such as updated counters, are kept.
+    ///
+    /// The default for this value is `false`.
+    #[inline]
+    pub fn save_on_cease(mut self, value: bool) -> Self {
+        self.options.save_on_cease = value;
+        self
+    }
+
+    /// Whether a `Clear-Site-Data: "cookies", "storage"` header is sent when the session is
+    /// destroyed, so the browser also wipes the other cookies and client-side storage of the
+    /// site. Use [`clear_site_data_directives`](Self::clear_site_data_directives) to choose
+    /// other directives. Browsers only honor this header over https.
+    ///
+    /// The default for this value is `false`.
+    #[inline]
+    pub fn clear_site_data_on_destroy(mut self, value: bool) -> Self {
+        if value {
+            self.clear_site_data_directives(vec![ClearSiteData::Cookies, ClearSiteData::Storage])
+        } else {
+            self.options.clear_site_data = None;
+            self
+        }
+    }
+
+    /// Sends a `Clear-Site-Data` header with `directives` when the session is destroyed. An
+    /// empty list sends no header.
+    #[inline]
+    pub fn clear_site_data_directives(mut self, directives: Vec<ClearSiteData>) -> Self {
+        self.options.clear_site_data =
+            (!directives.is_empty()).then(|| ClearSiteData::header_value(&directives));
+        self
+    }
+
+    /// Sets the status from which changes made with [`SessionDepotExt::session_tx`] are rolled
+    /// back instead of saved.
+    ///
+    /// The default for this value is `400 Bad Request`, so changes are kept for informational,
+    /// successful and redirection responses.
+    #[inline]
+    pub fn tx_rollback_status(mut self, status_code: StatusCode) -> Self {
+        self.options.tx_rollback_status = status_code;
+        self
+    }
+
+    /// Whether the session is not saved when the response is a server error, so the changes
+    /// made by a request that failed halfway are dropped. A destroyed session is still destroyed.
+    ///
+    /// The default for this value is `false`.
+    #[inline]
+    pub fn skip_save_on_error(mut self, enabled: bool) -> Self {
+        self.options.skip_save_on_error = enabled;
+        self
+    }
+
+    /// Hardens the session cookie with the `__Host-` prefix.
+    ///
+    /// The cookie name gets the `__Host-` prefix and the cookie is always marked `Secure`, even
+    /// for plain http requests, with `Path=/` and no `Domain`, so browsers only send it to the
+    /// exact host that set it over https. [`build`](Self::build) fails if a cookie domain or a
+    /// path other than `/` is configured.
+    #[inline]
+    pub fn host_prefixed(mut self) -> Self {
+        self.options.host_prefixed = true;
+        self
+    }
+
+    /// Loads the signing keys from `provider` at runtime, so they can be rotated without a
+    /// restart.
+    ///
+    /// The provider is asked for the keys on the first request and then at most once every
+    /// `refresh_interval`. Until the first refresh, and whenever the provider fails, the
+    /// previous keys are used, starting with the key given to the builder.
+    #[inline]
+    pub fn key_provider(mut self, provider: impl KeyProvider, refresh_interval: Duration) -> Self {
+        self.options.key_provider = Some(KeyRefresher::new(provider, refresh_interval));
+        self
+    }
+
+    /// Limits how many new sessions a single client address can create within `window`.
+    ///
+    /// Creating a session costs a write to the store, so without a limit a client can fill an
+    /// external store with empty sessions cheaply. Only sessions that are created and stored
+    /// count, loading an existing session doesn't. Once the limit is reached, requests from that
+    /// address without a valid session are answered with `429 Too Many Requests` until the
+    /// window has elapsed.
+    ///
+    /// The address is the peer address of the connection, so behind a reverse proxy all clients
+    /// share the proxy's address unless [`client_ip_header`](Self::client_ip_header) is set.
+    #[inline]
+    pub fn max_new_sessions_per_ip(mut self, count: usize, window: Duration) -> Self {
+        self.options.new_session_limit = Some(NewSessionLimiter::new(count, window));
+        self
+    }
+
+    /// Binds sessions to the client address and/or user agent of the request that created them.
+    ///
+    /// A fingerprint of the bound attributes is stored in the session under [`BINDING_KEY`].
+    /// When a session is loaded for a request whose fingerprint doesn't match, including sessions
+    /// created before the binding was enabled, the request gets a fresh session as if it had no
+    /// cookie. See [`SessionBinding`] for the tradeoffs.
+    #[inline]
+    pub fn bind_to(mut self, binding: SessionBinding) -> Self {
+        self.options.binding = Some(binding);
+        self
+    }
+
+    /// Reads the client address from `header`, for example `x-forwarded-for`, instead of using
+    /// the peer address of the connection.
+    ///
+    /// Use this when the application runs behind a reverse proxy. The last address in the header
+    /// is used, which is the one added by the proxy in front of the application, because earlier
+    /// ones are sent by the client and can be forged. If the header is missing or invalid, the
+    /// peer address is used.
+    #[inline]
+    pub fn client_ip_header(mut self, header: HeaderName) -> Self {
+        self.options.client_ip_header = Some(header);
+        self
+    }
+
+    /// Sends the expiry of the session in `header`, for example `x-session-expires`, so a
+    /// frontend can warn the user or refresh the session before it expires.
+    ///
+    /// The value is the expiry as a unix timestamp in seconds. The header is only added to
+    /// responses with a live session that has an expiry: a session that was loaded or is being
+    /// stored, and not destroyed. Cross-origin clients also need the header listed in
+    /// `Access-Control-Expose-Headers` to read it. Not set by default.
+    #[inline]
+    pub fn expose_expiry_header(mut self, header: Option<HeaderName>) -> Self {
+        self.options.expose_expiry_header = header;
+        self
+    }
+
+    /// Sets where the signed session value is read from. Defaults to the request cookies.
+    #[inline]
+    pub fn cookie_source(mut self, source: impl CookieSource) -> Self {
+        self.options.cookie_source = Box::new(source);
+        self
+    }
+
+    /// Sets how session cookies are sent to the client. Defaults to `Set-Cookie` headers.
+    #[inline]
+    pub fn cookie_sink(mut self, sink: impl CookieSink) -> Self {
+        self.options.cookie_sink = Box::new(sink);
+        self
+    }
+
+    /// Reads and sends the signed session value in the header called `name` instead of cookies,
+    /// see [`SessionHeader`]. Cookies stay the default.
+    #[inline]
+    pub fn session_header(self, name: HeaderName) -> Self {
+        let header = SessionHeader::new(name);
+        self.cookie_source(header.clone()).cookie_sink(header)
+    }
+
+    /// Splits the session cookie into cookies of at most `chunk_size` bytes of value, see
+    /// [`ChunkedCookies`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    #[inline]
+    pub fn chunked_cookies(self, chunk_size: usize) -> Self {
+        let chunked = ChunkedCookies::new(chunk_size);
+        self.cookie_source(chunked).cookie_sink(chunked)
+    }
+
+    /// Sets a hook that resolves the session of a request from something other than the
+    /// session cookie, such as an API key header mapped to a session.
+    ///
+    /// When `extract` returns a key, it is passed to the store as is, without signature
+    /// verification, so it must be as hard to guess as a session id. No cookie is sent in the
+    /// response of such requests. When it returns `None`, the session cookie is used as usual.
+    #[inline]
+    pub fn session_key_extractor(
+        mut self,
+        extract: impl Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        self.options.session_key_extractor = Some(Box::new(extract));
+        self
+    }
+
+    /// Sets the query parameter carrying a one-time session token, for magic-link logins.
+    ///
+    /// When a request has no valid session cookie but has this parameter, the token is
+    /// verified like a cookie and its session is loaded, given a new id and sent as the session
+    /// cookie. The client is then redirected to the same url without the parameter, so the token
+    /// doesn't stay in the address bar or in logs of later requests. The session under the
+    /// token is destroyed, so a token works only once. With [`CookieStore`], which keeps nothing
+    /// on the server, set a [`revocation_list`](Self::revocation_list) for that to hold.
+    ///
+    /// Tokens are signed session values, such as the ones returned by [`SessionHandler::save`].
+    /// Not set by default.
+    #[inline]
+    pub fn query_token_param(mut self, param: Option<String>) -> Self {
+        self.options.query_token_param = param;
+        self
+    }
+
+    /// Sets the source of the random bytes of new session cookie values. Defaults to
+    /// [`OsRandSource`], the random number generator of the operating system.
+    ///
+    /// Sessions regenerated by a handler with [`Session::regenerate`] get their new cookie value
+    /// from `async-session` itself.
+    #[inline]
+    pub fn rand_source(mut self, rand_source: impl RandSource) -> Self {
+        self.options.rand_source = Arc::new(rand_source);
+        self
+    }
+
+    /// Generates the values of new session cookies with `rng` instead of the operating system
+    /// RNG, see [`rand_source`](Self::rand_source).
+    ///
+    /// With a seeded RNG the cookie values, and so the signed `Set-Cookie` headers, are the same
+    /// on every run, which allows snapshot tests of the whole response. Only available in tests
+    /// and with the `test-util` feature, so it can't end up in production by accident.
+    #[cfg(any(test, feature = "test-util"))]
+    #[cfg_attr(docsrs, doc(cfg(feature = "test-util")))]
+    #[inline]
+    pub fn with_rng(self, rng: impl rand::RngCore + Send + 'static) -> Self {
+        self.rand_source(std::sync::Mutex::new(rng))
+    }
+
+    /// Sets the same site policy for the session cookie. Defaults to
+    /// SameSite::Lax. See [incrementally better
+    /// cookies](https://tools.ietf.org/html/draft-west-cookie-incrementalism-01)
+    /// for more information about this setting.
+    #[inline]
+    pub fn same_site_policy(mut self, policy: SameSite) -> Self {
+        self.options.same_site_policy = policy;
+        self
+    }
+
+    /// Whether the session cookie is sent without any `SameSite` attribute, ignoring the
+    /// [`same_site_policy`](Self::same_site_policy).
+    ///
+    /// Some legacy browsers treat an absent attribute differently from `Lax`, or reject
+    /// `SameSite=None`. A `SameSite` override set for a response, or set on the
+    /// [`cookie_template`](Self::cookie_template), still applies.
+    ///
+    /// The default for this value is `false`.
+    #[inline]
+    pub fn omit_same_site(mut self, enabled: bool) -> Self {
+        self.options.omit_same_site = enabled;
+        self
+    }
+
+    /// Sets how the signed session value is encoded in the cookie. Defaults to
+    /// [`CookieEncoding::StandardBase64`].
+    ///
+    /// Changing the encoding invalidates the session cookies already handed out.
+    #[inline]
+    pub fn cookie_encoding(mut self, encoding: CookieEncoding) -> Self {
+        self.options.cookie_encoding = encoding;
+        self
+    }
+
+    /// Whether a request cookie whose name only differs from the session cookie name in case is
+    /// read as the session cookie when there is no exact match, `false` by default.
+    ///
+    /// Cookie names are case-sensitive, but some proxies and legacy clients change their case,
+    /// which otherwise loses the session.
+    #[inline]
+    pub fn case_insensitive_cookie_lookup(mut self, enabled: bool) -> Self {
+        self.options.case_insensitive_cookie_lookup = enabled;
+        self
+    }
+
+    /// Whether the handler checks the `Cookie` header of requests without a session cookie for
+    /// entries that can't be parsed, `false` by default.
+    ///
+    /// Such entries are silently dropped from the request cookies, which looks like a request
+    /// without a session. With this option, a malformed header is logged at debug level and
+    /// reported in [`SessionDiagnostics::malformed_cookie_header`], to tell a broken client or
+    /// proxy apart from a missing cookie.
+    #[inline]
+    pub fn detect_malformed_cookie_header(mut self, enabled: bool) -> Self {
+        self.options.detect_malformed_cookie_header = enabled;
+        self
+    }
+
+    /// Sets the function deciding whether a request is secure, which makes the session cookie
+    /// `Secure`, for example to trust a header set by a proxy terminating TLS.
+    ///
+    /// By default a request is secure when its scheme is https. Cookies of
+    /// [`host_prefixed`](Self::host_prefixed) handlers are always `Secure`.
+    #[inline]
+    pub fn cookie_secure_fn(
+        mut self,
+        cookie_secure_fn: impl Fn(&Request) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.options.cookie_secure_fn = Some(Box::new(cookie_secure_fn));
+        self
+    }
+
+    /// Passes the requests for which `skip_if` returns `true` through untouched, for example
+    /// requests for static assets.
+    ///
+    /// A skipped request gets no session in the depot: the session is not loaded nor saved, and
+    /// no cookie is sent. Handlers that need a session must not be reachable by skipped requests.
+    #[inline]
+    pub fn skip_if(mut self, skip_if: impl Fn(&Request) -> bool + Send + Sync + 'static) -> Self {
+        self.options.skip_if = Some(Box::new(skip_if));
+        self
+    }
+
+    /// Refuses to send session cookies whose name and value are longer than `budget` bytes.
+    ///
+    /// Browsers silently drop cookies over about 4096 bytes, so a [`CookieStore`] session that
+    /// keeps growing is suddenly lost. With a budget, the oversized cookie is not sent, so the
+    /// client keeps its previous cookie, and [`SessionError::TooLarge`] is left in the depot for
+    /// the middleware around the session handler, see [`SessionDepotExt::session_error`].
+    #[inline]
+    pub fn cookie_size_budget(mut self, budget: usize) -> Self {
+        self.options.cookie_size_budget = Some(budget);
+        self
+    }
+
+    /// Sends the signature of the session value in a separate `<name>.sig` cookie, so the session
+    /// cookie only carries the value, which some CDNs handle better than a long signed value.
+    /// Both cookies are needed to load the session. Defaults to `false`, one signed cookie.
+    ///
+    /// Changing this setting invalidates the session cookies already handed out.
+    #[inline]
+    pub fn detached_signature(mut self, detached: bool) -> Self {
+        self.options.detached_signature = detached;
+        self
+    }
+
+    /// Whether [`build`](Self::build) logs a warning, once per process, when the store is a
+    /// plain [`CookieStore`], `true` by default.
+    ///
+    /// A `CookieStore` keeps the session in the cookie signed but not encrypted, so the client
+    /// can read the session data. Wrap it in an [`EncryptedStore`] to hide it, or disable this
+    /// warning if the session data is not sensitive.
+    #[inline]
+    pub fn warn_insecure_cookie_store(mut self, warn: bool) -> Self {
+        self.options.warn_insecure_cookie_store = warn;
+        self
+    }
+
+    /// Sets a template for the session cookie.
+    ///
+    /// The attributes of `template`, such as `SameSite`, `Path`, `Domain`, `Secure`, `HttpOnly`
+    /// and any extensions, are copied to the session cookie instead of the ones set with the
+    /// individual setters. The name, value, signature and expiry are still managed by the handler.
+    /// If the template doesn't set `Secure`, it is set for https requests as usual.
+    #[inline]
+    pub fn cookie_template(mut self, template: Cookie<'static>) -> Self {
+        self.options.cookie_template = Some(template);
+        self
+    }
+
+    /// Sets the domain of the cookie.
+    #[inline]
+    pub fn cookie_domain(mut self, cookie_domain: impl AsRef<str>) -> Self {
+        self.options.cookie_domain = Some(cookie_domain.as_ref().to_owned());
+        self
+    }
+
+    /// Shares the session between `domain` and all its subdomains, for single sign-on across
+    /// hosts such as `app.example.com` and `api.example.com` with a `domain` of `example.com`.
+    ///
+    /// The session cookie is scoped to `domain`, which [`build`](Self::build) checks to be a
+    /// domain with at least two labels. The public suffix list is not checked, so make sure
+    /// `domain` is one you own. Requests to hosts outside `domain` get no session cookie, since
+    /// browsers would ignore it, and a warning is logged.
+    ///
+    /// All the hosts must share the same store and secret, so each of them can load and verify
+    /// the sessions started by the others: a `MemoryStore` only works when a single server
+    /// answers for all of them.
+    #[inline]
+    pub fn shared_across_subdomains(mut self, domain: impl AsRef<str>) -> Self {
+        let domain = domain.as_ref().trim_start_matches('.').to_ascii_lowercase();
+        self.options.cookie_domain = Some(domain);
+        self.options.shared_across_subdomains = true;
+        self
+    }
+    /// Sets the keys that signed session cookies before the current key, to rotate the key
+    /// without logging everyone out.
+    ///
+    /// A cookie whose signature only verifies with a fallback key is accepted and sent again,
+    /// signed with the current key. A fallback key can be dropped once the cookies it signed
+    /// have expired.
+    #[inline]
+    pub fn fallback_keys(mut self, keys: Vec<impl Into<Key>>) -> Self {
+        self.fallback_keys = keys.into_iter().map(|s| s.into()).collect();
+        self
+    }
+
+    /// Adds a key that signed session cookies before the current key, see
+    /// [`fallback_keys`](Self::fallback_keys).
+    #[inline]
+    pub fn add_fallback_key(mut self, key: impl Into<Key>) -> Self {
+        self.fallback_keys.push(key.into());
+        self
+    }
+
+    /// Build `SessionHandler`
+    pub fn build(self) -> Result<SessionHandler<S>, Error> {
+        let Self {
+            store,
+            key,
+            fallback_keys,
+            mut options,
+        } = self;
+        #[cfg(feature = "cookie-store")]
+        if options.warn_insecure_cookie_store
+            && is_cookie_store::<S>()
+            && !INSECURE_COOKIE_STORE_WARNED.swap(true, Ordering::Relaxed)
+        {
+            tracing::warn!(
+                "`CookieStore` sends the session data readable by the client, \
+                 wrap it in an `EncryptedStore` to encrypt it"
+            );
+        }
+        #[cfg(not(feature = "cookie-store"))]
+        let _ = options.warn_insecure_cookie_store;
+        if let (true, Some(domain)) = (options.shared_across_subdomains, &options.cookie_domain) {
+            if let Some(reason) = shared_domain_error(domain) {
+                return Err(Error::Other(
+                    format!("shared session cookie domain `{domain}` {reason}").into(),
+                ));
+            }
+        }
+        let depot_key = session_depot_key(&options.cookie_name);
+        if options.host_prefixed {
+            if options.cookie_domain.is_some() || options.cookie_path != "/" {
+                return Err(Error::Other(
+                    "host prefixed session cookie can't have a domain or a path other than `/`"
+                        .into(),
+                ));
+            }
+            options.cookie_name = format!("__Host-{}", options.cookie_name);
+        }
+        let keys = KeySet::new(&key, &fallback_keys)?;
+        #[cfg(feature = "cookie-store")]
+        if (options.derive_store_key || !options.app_id.is_empty()) && stores_in_cookie::<S>() {
+            return Err(Error::Other(
+                "`derive_store_key` and `app_id` can't be used with `CookieStore`, which keeps \
+                 the session in the cookie value"
+                    .into(),
+            ));
+        }
+        let store_key = (options.derive_store_key || !options.app_id.is_empty()).then(|| {
+            let secret = if options.derive_store_key {
+                derive_secret::<32>(key.signing(), STORE_KEY_CONTEXT).to_vec()
+            } else {
+                Vec::new()
+            };
+            let mut mac = Hmac::<Sha256>::new_from_slice(&secret)
+                .expect("hmac should accept keys of any length");
+            if !options.app_id.is_empty() {
+                // The length prefix keeps the id apart from the cookie value that follows.
+                mac.update(&(options.app_id.len() as u64).to_be_bytes());
+                mac.update(options.app_id.as_bytes());
+            }
+            mac
+        });
+        Ok(SessionHandler {
+            store,
+            options,
+            depot_key,
+            store_key,
+            keys: RwLock::new(Arc::new(keys)),
+        })
+    }
+}
+
+impl<S> HandlerBuilder<S>
+where
+    S: SessionStore + StoreTelemetry,
 {
-    async fn handle(
-        &self,
-        req: &mut Request,
-        depot: &mut Depot,
-        res: &mut Response,
-        ctrl: &mut FlowCtrl,
-    ) {
-        let cookie = req.cookies().get(&self.cookie_name);
-        let cookie_value = cookie.and_then(|cookie| self.verify_signature(cookie.value()).ok());
+    /// Annotates the spans of store calls with the [`StoreInfo`] of the store, such as the
+    /// `db.system` attribute, so distributed traces show the session store as a dependency.
+    #[inline]
+    pub fn store_telemetry(mut self) -> Self {
+        self.options.store_info = Some(self.store.store_telemetry());
+        self
+    }
+}
+
+impl<S> HandlerBuilder<S>
+where
+    S: PatchableStore,
+{
+    /// Writes only the changed fields of loaded sessions, with
+    /// [`PatchableStore::patch_session`], instead of the whole session.
+    ///
+    /// This saves bandwidth for large sessions with small changes on stores that can update
+    /// fields separately. The session data is copied when it is loaded to find the changes.
+    /// Sessions whose data didn't change only get their expiry renewed, with
+    /// [`PatchableStore::renew_session`].
+    #[inline]
+    pub fn patch_writes(mut self) -> Self {
+        self.options.patch_session = Some(patch_session::<S>);
+        self.options.renew_session = Some(renew_session::<S>);
+        self
+    }
+}
+
+/// `SessionHandler` is a middleware for session.
+pub struct SessionHandler<S> {
+    store: S,
+    options: HandlerOptions<S>,
+    depot_key: String,
+    store_key: Option<Hmac<Sha256>>,
+    keys: RwLock<Arc<KeySet>>,
+}
+impl<S: SessionStore> fmt::Debug for SessionHandler<S> {
+    #[inline]
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionHandler")
+            .field("store", &self.store)
+            .field("options", &self.options)
+            .field("depot_key", &self.depot_key)
+            .field("store_key", &self.store_key.as_ref().map(|_| ".."))
+            .field("keys", &"..")
+            .finish()
+    }
+}
+#[async_trait]
+impl<S> Handler for SessionHandler<S>
+where
+    S: SessionStore,
+{
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if matches!(&self.options.skip_if, Some(skip_if) if skip_if(req)) {
+            return;
+        }
+        self.refresh_keys().await;
+        let extracted_key = self
+            .options
+            .session_key_extractor
+            .as_ref()
+            .and_then(|extract| extract(req));
+        // Sessions resolved by the key extractor are not tied to a cookie.
+        let use_cookie = extracted_key.is_none() && self.host_in_cookie_domain(req);
+        let (cookie_value, mut diagnostics) = match extracted_key {
+            Some(key) => (Some(key), SessionDiagnostics::default()),
+            None => self.verified_cookie_value(req),
+        };
+        if self.options.detect_malformed_cookie_header
+            && use_cookie
+            && !diagnostics.cookie_present
+            && cookie_header_malformed(req)
+        {
+            tracing::debug!(
+                "malformed cookie header, treating the request as without session cookie"
+            );
+            diagnostics.malformed_cookie_header = true;
+        }
+        let secure_cookie = self.options.host_prefixed
+            || match &self.options.cookie_secure_fn {
+                Some(cookie_secure_fn) => cookie_secure_fn(req),
+                None => req.uri().scheme() == Some(&Scheme::HTTPS),
+            };
+        depot.insert(SECURE_KEY, secure_cookie);
+        if let (None, Some(param)) = (&cookie_value, &self.options.query_token_param) {
+            if let Some(token) = req.query::<String>(param) {
+                self.redeem_query_token(req, res, param, &token, secure_cookie)
+                    .await;
+                ctrl.skip_rest();
+                return;
+            }
+        }
+
+        let client_ip = self.client_ip(req);
+        let fingerprint = self.options.binding.map(|binding| {
+            let user_agent = req.headers().get(USER_AGENT).map(|value| value.as_bytes());
+            binding.fingerprint(client_ip, user_agent)
+        });
+
+        // Requests without a session cookie only need a session id if their session is saved.
+        let deferred = cookie_value.is_none() && !self.options.save_unchanged;
+        let (mut session, mut loaded) = if deferred {
+            (placeholder_session(), false)
+        } else {
+            self.load_or_create(cookie_value.clone()).await
+        };
+        diagnostics.loaded = loaded;
+        let signed_by_fallback = diagnostics.fallback_key;
+        depot.insert(DIAGNOSTICS_KEY, diagnostics);
+        if let (Some(successor_id), true) = (session.get::<String>(SUPERSEDED_BY_KEY), loaded) {
+            tracing::warn!("superseded session cookie reused, destroying the session");
+            if let Err(e) = self.destroy(session_with_id(&successor_id)).await {
+                tracing::error!(error = ?e, "unable to destroy session");
+            }
+            if let Err(e) = self.destroy(session).await {
+                tracing::error!(error = ?e, "unable to destroy session");
+            }
+            session = self.new_session();
+            loaded = false;
+        }
+        if let (Some(fingerprint), true) = (&fingerprint, loaded) {
+            if session.get::<String>(BINDING_KEY).as_ref() != Some(fingerprint) {
+                tracing::debug!("session binding mismatch, starting a new session");
+                session = self.new_session();
+                loaded = false;
+            }
+        }
+        if self.options.require_valid && !loaded {
+            tracing::debug!("no valid session, request is unauthorized");
+            res.status_code(StatusCode::UNAUTHORIZED);
+            ctrl.skip_rest();
+            return;
+        }
+        if let (Some(limiter), Some(client_ip), false) =
+            (&self.options.new_session_limit, client_ip, loaded)
+        {
+            if limiter.is_exceeded(client_ip) {
+                tracing::debug!(%client_ip, "too many new sessions");
+                res.status_code(StatusCode::TOO_MANY_REQUESTS);
+                ctrl.skip_rest();
+                return;
+            }
+        }
+        if let Some(resolution) = self.options.last_seen_resolution {
+            let now = Utc::now().timestamp();
+            let resolution = i64::try_from(resolution.as_secs()).unwrap_or(i64::MAX);
+            let stale = !matches!(
+                session.get::<i64>(LAST_SEEN_KEY),
+                Some(last_seen) if now.saturating_sub(last_seen) < resolution
+            );
+            if stale {
+                if let Err(e) = session.insert(LAST_SEEN_KEY, now) {
+                    tracing::error!(error = ?e, "unable to set session last seen time");
+                }
+            }
+        }
+        if !loaded {
+            // A placeholder gets its creation time when it is adopted.
+            if !deferred {
+                if let Err(e) = session.insert(CREATED_KEY, Utc::now().timestamp()) {
+                    tracing::error!(error = ?e, "unable to set session creation time");
+                }
+            }
+            // The creation time alone doesn't make the session worth saving.
+            session.reset_data_changed();
+        }
+        let loaded_id = loaded.then(|| session.id().to_owned());
+        let loaded_version = session.get::<u64>(VERSION_KEY).unwrap_or_default();
+
+        // A loaded session keeps its expiry, and its cookie is not sent again, until the
+        // remaining lifetime drops below the refresh threshold or the session is in its grace
+        // period.
+        let in_grace_period = self.options.grace_period.is_some()
+            && matches!(session.expiry(), Some(expiry) if *expiry <= self.now());
+        let refresh = !loaded
+            || in_grace_period
+            || self.options.refresh_threshold.map_or(true, |threshold| {
+                !matches!(session.expires_in(), Some(remaining) if remaining >= threshold)
+            });
+        let ttl = self.ttl_for(&session);
+        // A placeholder gets its expiry when it is saved.
+        if let (Some(ttl), true, false) = (ttl, refresh, deferred) {
+            session.expire_in(ttl);
+        }
+
+        let snapshot =
+            (self.options.patch_session.is_some() && loaded).then(|| session_data(&session));
+        // The session of an outer handler stays reachable by name while this one is current.
+        let outer_key = depot.remove::<String>(CURRENT_SESSION_KEY).ok();
+        depot.insert(CURRENT_SESSION_KEY, self.depot_key.clone());
+        depot.set_session(session);
+
+        let persist = !matches!(&self.options.persist_methods, Some(methods) if !methods.contains(req.method()));
+        let mut precommitted = false;
+        if self.options.precommit_cookie
+            && !self.options.rotate_every_request
+            && use_cookie
+            && persist
+            && loaded
+            && refresh
+        {
+            if let Some(cookie_value) = cookie_value.clone() {
+                self.send_cookie(
+                    res,
+                    self.build_cookie(secure_cookie, cookie_value, ttl, None),
+                );
+                precommitted = true;
+                depot.insert(WILL_SET_COOKIE_KEY, true);
+            }
+        }
+
+        ctrl.call_next(req, depot, res).await;
+        match outer_key {
+            Some(outer_key) => {
+                depot.insert(CURRENT_SESSION_KEY, outer_key);
+            }
+            None => {
+                depot.delete(CURRENT_SESSION_KEY);
+            }
+        }
+        if ctrl.is_ceased() && !self.options.save_on_cease {
+            return;
+        }
+
+        let mut session = depot
+            .remove::<Session>(&self.depot_key)
+            .expect("session should exist in depot");
+        // The overrides set through `SessionDepotExt` apply to the innermost handler only.
+        let same_site = depot.remove::<SameSite>(SAME_SITE_KEY).ok();
+        if let Ok(volatile_keys) = depot.remove::<Vec<String>>(VOLATILE_KEY) {
+            let changed = session.data_changed();
+            for key in volatile_keys {
+                session.remove(&key);
+            }
+            if !changed {
+                session.reset_data_changed();
+            }
+        }
+        if depot.remove::<bool>(CLEAR_COOKIE_KEY).is_ok() {
+            depot.insert(WILL_SET_COOKIE_KEY, use_cookie);
+            if use_cookie {
+                self.send_cookie(res, self.removal_cookie(secure_cookie, same_site));
+            }
+            return;
+        }
+        if let Ok(pending) = depot.remove::<PendingChanges>(PENDING_KEY) {
+            if res.status_code.unwrap_or(StatusCode::OK) < self.options.tx_rollback_status {
+                pending.apply(&mut session);
+            } else {
+                tracing::debug!("error response, session changes are rolled back");
+            }
+        }
+        let server_error = res
+            .status_code
+            .is_some_and(|status_code| status_code.is_server_error());
+        if self.options.skip_save_on_error && server_error && persist && !session.is_destroyed() {
+            tracing::debug!("server error response, session is not saved");
+        }
+        let persist = persist && !(self.options.skip_save_on_error && server_error);
+        if let (Some(after_handle), false) = (&self.options.after_handle, session.is_destroyed()) {
+            after_handle(req, &mut session);
+        }
+        // Adopted before its ttl is computed, so the expiry jitter depends on the new session id.
+        if deferred && !session.is_destroyed() && session.data_changed() {
+            session = self.adopt(session);
+        }
+        let ttl = self.ttl_for(&session);
+        if let (Some(ttl), None) = (ttl, session.expiry()) {
+            session.expire_in(ttl);
+        }
+        self.clamp_expiry(&mut session);
+        let rotate = self.options.rotate_every_request
+            && use_cookie
+            && persist
+            && loaded_id.as_deref() == Some(session.id());
+        // A `SameSite` override must reach the client even when the cookie of a loaded session
+        // would not be sent again, and so must a cookie signed with a fallback key, so the
+        // incoming cookie value is reissued, signed with the current key.
+        let reissue = cookie_value.filter(|_| {
+            (same_site.is_some() || signed_by_fallback)
+                && use_cookie
+                && loaded_id.as_deref() == Some(session.id())
+        });
+        let skip_empty = !self.options.cookie_on_empty
+            && loaded_id.as_deref() != Some(session.id())
+            && user_keys(&session).is_empty();
+        // Set again below when a cookie is actually sent.
+        depot.insert(WILL_SET_COOKIE_KEY, precommitted);
+        if let (Some(header), Some(expiry), false) = (
+            &self.options.expose_expiry_header,
+            session.expiry(),
+            session.is_destroyed(),
+        ) {
+            let live = loaded_id.as_deref() == Some(session.id())
+                || (persist
+                    && !skip_empty
+                    && (rotate || self.options.save_unchanged || session.data_changed()));
+            if live {
+                res.headers_mut()
+                    .insert(header.clone(), HeaderValue::from(expiry.timestamp()));
+            }
+        }
+        if session.is_destroyed() {
+            if deferred {
+                // A placeholder was never stored, so there is nothing to destroy.
+            } else if let Err(e) = self.destroy(session).await {
+                tracing::error!(error = ?e, "unable to destroy session");
+            }
+            // The removal cookie has the attributes of the session cookie, or browsers may ignore
+            // it. A placeholder never had a cookie to remove.
+            if use_cookie && !deferred {
+                self.send_cookie(res, self.removal_cookie(secure_cookie, same_site));
+                depot.insert(WILL_SET_COOKIE_KEY, true);
+            }
+            if let Some(clear_site_data) = &self.options.clear_site_data {
+                res.headers_mut()
+                    .insert(CLEAR_SITE_DATA, clear_site_data.clone());
+            }
+        } else if persist
+            && !skip_empty
+            && (rotate || self.options.save_unchanged || session.data_changed())
+        {
+            // A session regenerated by a handler replaces the loaded one, whose record and cookie
+            // must not stay valid.
+            let regenerated_id = loaded_id
+                .as_deref()
+                .filter(|loaded_id| !rotate && *loaded_id != session.id())
+                .map(ToOwned::to_owned);
+            if rotate {
+                self.supersede(&mut session).await;
+            }
+            self.sanitize(&mut session);
+            self.run_before_save(&mut session);
+            if let Some(fingerprint) = fingerprint {
+                if let Err(e) = session.insert(BINDING_KEY, fingerprint) {
+                    tracing::error!(error = ?e, "unable to set session binding");
+                }
+            }
+            if self.options.track_version {
+                let base_version = if loaded_id.as_deref() == Some(session.id()) {
+                    loaded_version
+                } else {
+                    0
+                };
+                if let Err(e) = session.insert(VERSION_KEY, base_version + 1) {
+                    tracing::error!(error = ?e, "unable to set session version");
+                }
+            }
+            let send_cookie = use_cookie
+                && (!precommitted || regenerated_id.is_some())
+                && (refresh
+                    || session.data_changed()
+                    || loaded_id.as_deref() != Some(session.id()));
+            let cookie_ttl = if refresh { ttl } else { session.expires_in() };
+            self.extend_for_grace(&mut session);
+            let stored = match (self.options.patch_session, snapshot) {
+                (Some(patch_session), Some(snapshot))
+                    if loaded_id.as_deref() == Some(session.id()) =>
+                {
+                    let patch = SessionPatch::diff(&snapshot, &session);
+                    match self.options.renew_session.filter(|_| patch.is_empty()) {
+                        Some(renew_session) => {
+                            renew_session(&self.store, session)
+                                .instrument(self.store_span("renew"))
+                                .await
+                        }
+                        None => {
+                            patch_session(&self.store, session, patch)
+                                .instrument(self.store_span("patch"))
+                                .await
+                        }
+                    }
+                }
+                _ => {
+                    self.store
+                        .store_session(self.rekey(session))
+                        .instrument(self.store_span("store"))
+                        .await
+                }
+            };
+            match stored {
+                Ok(cookie_value) => {
+                    if let (Some(limiter), Some(client_ip), false) =
+                        (&self.options.new_session_limit, client_ip, loaded)
+                    {
+                        limiter.record(client_ip);
+                    }
+                    if let Some(regenerated_id) = &regenerated_id {
+                        if let Err(e) = self.destroy(session_with_id(regenerated_id)).await {
+                            tracing::error!(error = ?e, "unable to destroy regenerated session");
+                        }
+                    }
+                    let cookie_value = match reissue {
+                        Some(reissue) => cookie_value.or(Some(reissue)),
+                        None => cookie_value.filter(|_| send_cookie),
+                    };
+                    if let Some(cookie_value) = cookie_value {
+                        let cookie =
+                            self.build_cookie(secure_cookie, cookie_value, cookie_ttl, same_site);
+                        let size = cookie.name().len() + cookie.value().len();
+                        match self.options.cookie_size_budget {
+                            Some(budget) if size > budget => {
+                                tracing::warn!(
+                                    size,
+                                    budget,
+                                    "session cookie is too large, not sent"
+                                );
+                                depot.insert(ERROR_KEY, SessionError::TooLarge { size, budget });
+                            }
+                            _ => {
+                                tracing::trace!(size, "session cookie size");
+                                if regenerated_id.is_some() && use_cookie {
+                                    let removal = self.removal_cookie(secure_cookie, same_site);
+                                    self.send_replacing_cookie(res, removal, cookie);
+                                } else {
+                                    self.send_cookie(res, cookie);
+                                }
+                                depot.insert(WILL_SET_COOKIE_KEY, true);
+                            }
+                        }
+                    }
+                }
+                Err(e)
+                    if e.downcast_ref::<SessionError>() == Some(&SessionError::VersionConflict) =>
+                {
+                    tracing::warn!("session was modified concurrently, changes are discarded");
+                    depot.insert(ERROR_KEY, SessionError::VersionConflict);
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "store session error");
+                }
+            }
+        } else if let Some(cookie_value) = reissue {
+            let cookie_ttl = if refresh { ttl } else { session.expires_in() };
+            let cookie = self.build_cookie(secure_cookie, cookie_value, cookie_ttl, same_site);
+            self.send_cookie(res, cookie);
+            depot.insert(WILL_SET_COOKIE_KEY, true);
+        }
+    }
+}
+
+impl<S> SessionHandler<S>
+where
+    S: SessionStore,
+{
+    /// Create new `HandlerBuilder`
+    pub fn builder(store: S, secret: &[u8]) -> HandlerBuilder<S> {
+        HandlerBuilder::new(store, secret)
+    }
+
+    /// Loads a session outside of a request, for example in a background job.
+    ///
+    /// `signed_value` is the signed session cookie value as sent by the client, so it must have
+    /// been obtained from a prior request, for example by saving it along with the user id.
+    /// Returns `None` if the signature doesn't verify or the session doesn't exist or has expired.
+    pub async fn load_by_cookie_value(&self, signed_value: &str) -> Option<Session> {
+        self.refresh_keys().await;
+        let cookie_value = self.verify_signature(signed_value).ok()?;
+        let session = self.load(cookie_value).await?;
+        self.check_expiry(session)
+    }
+
+    /// Saves a session outside of a request, for example after changing a session loaded with
+    /// [`load_by_cookie_value`](Self::load_by_cookie_value) in a background job.
+    ///
+    /// If the store produced a new cookie value, as cookie based stores do, the signed value is
+    /// returned. It can't reach the client outside of a request, so such stores are of limited use
+    /// here.
+    pub async fn save(&self, mut session: Session) -> Result<Option<String>, Error> {
+        self.refresh_keys().await;
+        self.run_before_save(&mut session);
+        self.clamp_expiry(&mut session);
+        self.extend_for_grace(&mut session);
+        let cookie_value = self
+            .store
+            .store_session(self.rekey(session))
+            .instrument(self.store_span("store"))
+            .await
+            .map_err(Error::other)?;
+        Ok(cookie_value.map(|cookie_value| self.sign_value(&cookie_value)))
+    }
+
+    /// Destroys a session outside of a request, for example to log a user out after a password
+    /// change.
+    pub async fn destroy(&self, session: Session) -> Result<(), Error> {
+        if let Some(revocation_list) = &self.options.revocation_list {
+            revocation_list
+                .revoke(session.id(), session.expiry().copied())
+                .await?;
+        }
+        self.store
+            .destroy_session(session)
+            .instrument(self.store_span("destroy"))
+            .await
+            .map_err(Error::other)
+    }
+
+    /// Returns the prefix of the session keys reserved for internal use, see
+    /// [`RESERVED_PREFIX`].
+    #[inline]
+    pub fn reserved_prefix(&self) -> &str {
+        RESERVED_PREFIX
+    }
+
+    /// Returns a snapshot of the settings of the handler, without any secret.
+    pub fn config(&self) -> SessionConfig {
+        SessionConfig {
+            cookie_name: self.options.cookie_name.clone(),
+            cookie_path: self.options.cookie_path.clone(),
+            cookie_domain: self.options.cookie_domain.clone(),
+            same_site: if self.options.omit_same_site {
+                "omitted".into()
+            } else {
+                self.options.same_site_policy.to_string()
+            },
+            host_prefixed: self.options.host_prefixed,
+            session_ttl_secs: self.options.session_ttl.map(|ttl| ttl.as_secs()),
+            refresh_threshold_secs: self
+                .options
+                .refresh_threshold
+                .map(|threshold| threshold.as_secs()),
+            grace_period_secs: self.options.grace_period.map(|grace| grace.as_secs()),
+            max_session_ttl_secs: self.options.max_session_ttl.map(|ttl| ttl.as_secs()),
+            save_unchanged: self.options.save_unchanged,
+            fallback_keys: self.keys().fallback_hmacs.len(),
+            key_provider: self.options.key_provider.is_some(),
+        }
+    }
+
+    /// Removes internal keys left over by options that are no longer enabled, so they don't
+    /// bloat the stored session.
+    fn sanitize(&self, session: &mut Session) {
+        for key in OPTIONAL_KEYS {
+            let enabled = match key {
+                VERSION_KEY => self.options.track_version,
+                BINDING_KEY => self.options.binding.is_some(),
+                LAST_SEEN_KEY => self.options.last_seen_resolution.is_some(),
+                EXPIRES_KEY => self.options.grace_period.is_some(),
+                _ => true,
+            };
+            if !enabled && session.get_raw(key).is_some() {
+                session.remove(key);
+            }
+        }
+    }
+
+    /// Returns the client address, from [`HandlerBuilder::client_ip_header`] if it is set.
+    fn client_ip(&self, req: &Request) -> Option<IpAddr> {
+        self.options
+            .client_ip_header
+            .as_ref()
+            .and_then(|header| req.headers().get_all(header).iter().next_back())
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok())
+            .or_else(|| req.remote_addr().clone().into_std().map(|addr| addr.ip()))
+    }
+
+    fn store_span(&self, operation: &'static str) -> tracing::Span {
+        telemetry::store_span(self.options.store_info.as_ref(), operation)
+    }
+
+    /// Loads the valid session for `cookie_value` from the store.
+    async fn load(&self, cookie_value: String) -> Option<Session> {
+        let session = self
+            .store
+            .load_session(self.store_key(cookie_value))
+            .instrument(self.store_span("load"))
+            .await
+            .ok()
+            .flatten()?;
+        self.accept(session).await
+    }
+
+    /// Returns `session` if it is valid, accepted by `validate_fn`, not created before the
+    /// `not_before` cutoff and not revoked, after running the functions set with
+    /// `on_load_migrate` and `after_load`.
+    async fn accept(&self, session: Session) -> Option<Session> {
+        let session = session.validate()?;
+        if matches!(&self.options.validate_fn, Some(validate) if !validate(&session)) {
+            tracing::debug!("session refused by validate_fn, starting a new session");
+            return None;
+        }
+        let created = session.get(CREATED_KEY);
+        if matches!(&self.options.not_before, Some(not_before) if not_before.refuses(created)) {
+            tracing::debug!("session created before the cutoff, starting a new session");
+            return None;
+        }
+        if let Some(revocation_list) = &self.options.revocation_list {
+            match revocation_list.is_revoked(session.id()).await {
+                Ok(false) => {}
+                Ok(true) => {
+                    tracing::debug!("session is revoked, starting a new session");
+                    return None;
+                }
+                Err(e) => {
+                    tracing::error!(error = ?e, "unable to check session revocation");
+                    return None;
+                }
+            }
+        }
+        let mut session = self.migrate(session)?;
+        if let Some(after_load) = &self.options.after_load {
+            let changed = session.data_changed();
+            after_load(&mut session);
+            if !changed {
+                session.reset_data_changed();
+            }
+        }
+        Some(session)
+    }
+
+    /// Runs the function set with `before_save` on a session about to be saved.
+    fn run_before_save(&self, session: &mut Session) {
+        if let Some(before_save) = &self.options.before_save {
+            before_save(session);
+        }
+    }
+
+    /// Runs the migration set with `on_load_migrate` on a loaded session. Returns `None` if it
+    /// panics.
+    fn migrate(&self, mut session: Session) -> Option<Session> {
+        let Some(migrate) = &self.options.on_load_migrate else {
+            return Some(session);
+        };
+        match panic::catch_unwind(AssertUnwindSafe(|| migrate(&mut session))) {
+            Ok(()) => Some(session),
+            Err(_) => {
+                tracing::error!("session migration panicked, starting a new session");
+                None
+            }
+        }
+    }
+
+    /// Creates a new session, with a cookie value from the configured random source.
+    fn new_session(&self) -> Session {
+        let mut bytes = [0u8; 64];
+        self.options.rand_source.fill_bytes(&mut bytes);
+        let cookie_value = base64::encode(bytes);
+        let id = Session::id_from_cookie_value(&self.store_key(cookie_value.clone()))
+            .expect("generated cookie value should be valid base64");
+        let mut session = session_with_id(&id);
+        session.set_cookie_value(cookie_value);
+        session
+    }
+
+    /// Returns the key under which the store finds the session of `cookie_value`, see
+    /// [`HandlerBuilder::derive_store_key`] and [`HandlerBuilder::app_id`].
+    fn store_key(&self, cookie_value: String) -> String {
+        match &self.store_key {
+            Some(hmac) => {
+                let mut mac = hmac.clone();
+                mac.update(cookie_value.as_bytes());
+                base64::encode(mac.finalize().into_bytes())
+            }
+            None => cookie_value,
+        }
+    }
+
+    /// Gives a session with a new cookie value, such as a regenerated session, the id derived
+    /// from its store key, see [`HandlerBuilder::derive_store_key`].
+    fn rekey(&self, session: Session) -> Session {
+        if self.store_key.is_none() {
+            return session;
+        }
+        // Clones share the data but not the cookie value, which only new sessions have.
+        let template = session.clone();
+        let Some(cookie_value) = session.into_cookie_value() else {
+            return template;
+        };
+        let Ok(id) = Session::id_from_cookie_value(&self.store_key(cookie_value.clone())) else {
+            return template;
+        };
+        let mut session = session_with_id(&id);
+        if let Some(expiry) = template.expiry() {
+            session.set_expiry(*expiry);
+        }
+        for (key, value) in session_data(&template) {
+            session.insert_raw(&key, value);
+        }
+        session.set_cookie_value(cookie_value);
+        session
+    }
+
+    /// Turns a placeholder session into a new session with the same data and expiry.
+    fn adopt(&self, placeholder: Session) -> Session {
+        let mut session = self.new_session();
+        if let Some(expiry) = placeholder.expiry() {
+            session.set_expiry(*expiry);
+        }
+        if let Err(e) = session.insert(CREATED_KEY, Utc::now().timestamp()) {
+            tracing::error!(error = ?e, "unable to set session creation time");
+        }
+        for (key, value) in session_data(&placeholder) {
+            session.insert_raw(&key, value);
+        }
+        session
+    }
+
+    /// Exchanges a one-time session token from the query for a session cookie, and redirects to
+    /// the url without the token, for [`HandlerBuilder::query_token_param`].
+    async fn redeem_query_token(
+        &self,
+        req: &Request,
+        res: &mut Response,
+        param: &str,
+        token: &str,
+        secure: bool,
+    ) {
+        let session = match self.verify_signature(token) {
+            Ok(value) => self.load(value).await,
+            Err(_) => None,
+        };
+        match session {
+            Some(mut session) => {
+                let mut redeemed = session_with_id(session.id());
+                if let Some(expiry) = session.expiry() {
+                    redeemed.set_expiry(*expiry);
+                }
+                session.regenerate();
+                self.run_before_save(&mut session);
+                let ttl = self.ttl_for(&session);
+                if let Some(ttl) = ttl {
+                    session.expire_in(ttl);
+                }
+                self.extend_for_grace(&mut session);
+                match self
+                    .store
+                    .store_session(self.rekey(session))
+                    .instrument(self.store_span("store"))
+                    .await
+                {
+                    Ok(cookie_value) => {
+                        if let Some(cookie_value) = cookie_value {
+                            self.send_cookie(
+                                res,
+                                self.build_cookie(secure, cookie_value, ttl, None),
+                            );
+                        }
+                        if let Err(e) = self.destroy(redeemed).await {
+                            tracing::error!(error = ?e, "unable to destroy redeemed session");
+                        }
+                    }
+                    Err(e) => tracing::error!(error = ?e, "store session error"),
+                }
+            }
+            None => tracing::debug!("invalid session token in query"),
+        }
+        res.render(Redirect::other(without_query_param(req.uri(), param)));
+    }
+
+    /// Gives `session` a new id and marks the record under its previous id as superseded by it,
+    /// for [`HandlerBuilder::rotate_every_request`].
+    async fn supersede(&self, session: &mut Session) {
+        let superseded_id = session.id().to_owned();
+        session.regenerate();
+        let regenerated = std::mem::replace(session, placeholder_session());
+        *session = self.rekey(regenerated);
+        let mut superseded = session_with_id(&superseded_id);
+        if let Some(expiry) = session.expiry() {
+            superseded.set_expiry(*expiry);
+        }
+        if let Err(e) = superseded.insert(SUPERSEDED_BY_KEY, session.id()) {
+            tracing::error!(error = ?e, "unable to mark session as superseded");
+            return;
+        }
+        if let Err(e) = self
+            .store
+            .store_session(superseded)
+            .instrument(self.store_span("store"))
+            .await
+        {
+            tracing::error!(error = ?e, "unable to mark session as superseded");
+        }
+    }
+
+    /// Loads the session for `cookie_value` or creates a new one. The returned
+    /// flag tells whether the session was loaded from the store.
+    #[inline]
+    async fn load_or_create(&self, cookie_value: Option<String>) -> (Session, bool) {
+        let session = match cookie_value {
+            Some(cookie_value) => self.load(cookie_value).await,
+            None => None,
+        };
+
+        match session.and_then(|session| self.check_expiry(session)) {
+            Some(session) => (session, true),
+            None => (self.new_session(), false),
+        }
+    }
+    /// Whether the request host accepts the shared session cookie, see
+    /// [`HandlerBuilder::shared_across_subdomains`].
+    fn host_in_cookie_domain(&self, req: &Request) -> bool {
+        let (true, Some(domain)) = (
+            self.options.shared_across_subdomains,
+            &self.options.cookie_domain,
+        ) else {
+            return true;
+        };
+        let host = req
+            .uri()
+            .host()
+            .map(ToOwned::to_owned)
+            .or_else(|| req.header::<String>(HOST));
+        match host {
+            Some(host) if host_in_domain(&host, domain) => true,
+            host => {
+                tracing::warn!(
+                    host,
+                    domain,
+                    "request host is outside the shared session cookie domain, no cookie is sent"
+                );
+                false
+            }
+        }
+    }
+    /// Builds the session cookie. `same_site` overrides the configured policy for this response.
+    fn build_cookie(
+        &self,
+        secure: bool,
+        cookie_value: String,
+        ttl: Option<Duration>,
+        same_site: Option<SameSite>,
+    ) -> Cookie<'static> {
+        let mut cookie = self.unsigned_cookie(secure, cookie_value, same_site);
+        if let Some(ttl) = ttl.map(|ttl| self.clamp_ttl(ttl)) {
+            cookie.set_expires(Some((std::time::SystemTime::now() + ttl).into()));
+        }
+
+        if !self.options.detached_signature {
+            self.sign_cookie(&mut cookie);
+        }
+
+        cookie
+    }
+    /// Builds a cookie that removes the session cookie, with the same scope and attributes.
+    fn removal_cookie(&self, secure: bool, same_site: Option<SameSite>) -> Cookie<'static> {
+        let mut cookie = self.unsigned_cookie(secure, String::new(), same_site);
+        cookie.make_removal();
+        cookie
+    }
+    fn unsigned_cookie(
+        &self,
+        secure: bool,
+        cookie_value: String,
+        same_site: Option<SameSite>,
+    ) -> Cookie<'static> {
+        let mut cookie = match &self.options.cookie_template {
+            Some(template) => {
+                let mut cookie = template.clone();
+                cookie.set_name(self.options.cookie_name.clone());
+                cookie.set_value(cookie_value);
+                if cookie.secure().is_none() {
+                    cookie.set_secure(secure);
+                }
+                cookie
+            }
+            None => {
+                let mut cookie = Cookie::build((self.options.cookie_name.clone(), cookie_value))
+                    .http_only(true)
+                    .secure(secure)
+                    .path(self.options.cookie_path.clone())
+                    .build();
+                if !self.options.omit_same_site {
+                    cookie.set_same_site(self.options.same_site_policy);
+                }
+                if let Some(cookie_domain) = self.options.cookie_domain.clone() {
+                    cookie.set_domain(cookie_domain)
+                }
+                cookie
+            }
+        };
+
+        if let Some(same_site) = same_site {
+            cookie.set_same_site(same_site);
+        }
+        // Browsers reject `SameSite=None` cookies that are not `Secure`.
+        if cookie.same_site() == Some(SameSite::None) {
+            cookie.set_secure(true);
+        }
+        cookie
+    }
+    /// Reads the cookie called `name` from the cookie source, falling back to a request cookie
+    /// whose name only differs in case with `case_insensitive_cookie_lookup`.
+    fn cookie_value(&self, req: &Request, name: &str) -> Option<String> {
+        self.options
+            .cookie_source
+            .cookie_value(req, name)
+            .or_else(|| {
+                if !self.options.case_insensitive_cookie_lookup {
+                    return None;
+                }
+                let cookie = req
+                    .cookies()
+                    .iter()
+                    .find(|cookie| cookie.name().eq_ignore_ascii_case(name))?;
+                tracing::debug!(
+                    name = cookie.name(),
+                    "session cookie found with another case"
+                );
+                Some(cookie.value().to_owned())
+            })
+    }
+    /// Reads the session value sent by the client and returns it if its signature verifies,
+    /// along with the diagnostics of the verification.
+    fn verified_cookie_value(&self, req: &Request) -> (Option<String>, SessionDiagnostics) {
+        let mut diagnostics = SessionDiagnostics::default();
+        let Some(value) = self.cookie_value(req, &self.options.cookie_name) else {
+            return (None, diagnostics);
+        };
+        diagnostics.cookie_present = true;
+        diagnostics.cookie_len = value.len();
+        let check = if self.options.detached_signature {
+            let Some(signature) = self.cookie_value(req, &self.signature_cookie_name()) else {
+                return (None, diagnostics);
+            };
+            self.check_detached_signature(&value, &signature)
+        } else {
+            self.check_signature(&value)
+        };
+        diagnostics.well_formed = check.well_formed;
+        diagnostics.verified = check.well_formed && check.key.is_some();
+        diagnostics.fallback_key = diagnostics.verified && check.key != Some(0);
+        (check.into_result().ok(), diagnostics)
+    }
+    /// Returns the name of the cookie carrying the detached signature.
+    fn signature_cookie_name(&self) -> String {
+        format!("{}.sig", self.options.cookie_name)
+    }
+    /// Sends a session cookie built with `build_cookie` or `removal_cookie`, along with its
+    /// signature cookie when the signature is detached.
+    fn send_cookie(&self, res: &mut Response, cookie: Cookie<'static>) {
+        self.send_signature_cookie(res, &cookie);
+        self.options.cookie_sink.set_cookie(res, cookie);
+    }
+    /// Sends the cookie of a regenerated session like `send_cookie`, along with `removal`, which
+    /// removes the cookie of the previous session.
+    fn send_replacing_cookie(
+        &self,
+        res: &mut Response,
+        removal: Cookie<'static>,
+        cookie: Cookie<'static>,
+    ) {
+        self.send_signature_cookie(res, &cookie);
+        self.options
+            .cookie_sink
+            .replace_cookie(res, removal, cookie);
+    }
+    fn send_signature_cookie(&self, res: &mut Response, cookie: &Cookie<'static>) {
+        if self.options.detached_signature {
+            let mut signature_cookie = cookie.clone();
+            signature_cookie.set_name(self.signature_cookie_name());
+            if !cookie.value().is_empty() {
+                signature_cookie.set_value(
+                    self.options
+                        .cookie_encoding
+                        .encode(&self.digest(cookie.value()), ""),
+                );
+            }
+            self.options.cookie_sink.set_cookie(res, signature_cookie);
+        }
+    }
+}
+
+impl<S> SessionHandler<S>
+where
+    S: BatchSessionStore,
+{
+    /// Loads the sessions for several signed cookie values at once, for example for an admin
+    /// page listing user sessions. The result has one entry per value, in the same order, which
+    /// is `None` if the value is not correctly signed or the session is not valid.
+    ///
+    /// Stores that support it fetch all the sessions in a single round-trip, see
+    /// [`BatchSessionStore`].
+    pub async fn load_many(&self, signed_values: &[String]) -> Vec<Option<Session>> {
+        let cookie_values = signed_values
+            .iter()
+            .map(|signed_value| self.verify_signature(signed_value).ok())
+            .collect::<Vec<_>>();
+        let sessions = self
+            .store
+            .load_sessions(
+                cookie_values
+                    .iter()
+                    .flatten()
+                    .map(|cookie_value| self.store_key(cookie_value.clone()))
+                    .collect(),
+            )
+            .instrument(self.store_span("load_many"))
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(error = ?e, "unable to load sessions");
+                vec![]
+            });
+        let mut sessions = sessions.into_iter();
+        let mut accepted = Vec::with_capacity(cookie_values.len());
+        for cookie_value in cookie_values {
+            let session = match cookie_value.and_then(|_| sessions.next().flatten()) {
+                Some(session) => self.accept(session).await,
+                None => None,
+            };
+            accepted.push(session);
+        }
+        accepted
+    }
+}
+
+impl<S> SessionHandler<S>
+where
+    S: UserSessionStore,
+{
+    /// Destroys all the sessions of `user_id` except the current one, whose signed cookie value
+    /// is `keep_cookie_value`, for a "sign out all other sessions" feature. Returns how many
+    /// sessions were destroyed.
+    ///
+    /// Sessions are matched on the value stored under [`USER_ID_KEY`]. If `keep_cookie_value`
+    /// is not correctly signed, an error is returned and no session is destroyed.
+    pub async fn destroy_other_sessions(
+        &self,
+        user_id: &str,
+        keep_cookie_value: &str,
+    ) -> Result<usize, Error> {
+        let cookie_value = self.verify_signature(keep_cookie_value)?;
+        let keep_id =
+            Session::id_from_cookie_value(&self.store_key(cookie_value)).map_err(Error::other)?;
+        self.store
+            .destroy_user_sessions(user_id, Some(&keep_id))
+            .instrument(self.store_span("destroy_user_sessions"))
+            .await
+            .map_err(Error::other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::*;
+    use salvo_core::http::Method;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_extra::test_util::SessionTestHarness;
+
+    use super::*;
+
+    #[test]
+    fn test_session_data() {
+        let builder = SessionHandler::builder(
+            async_session::CookieStore,
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_domain("test.domain")
+        .cookie_name("test_cookie")
+        .cookie_path("/abc")
+        .same_site_policy(SameSite::Strict)
+        .session_ttl(Some(Duration::from_secs(30)));
+        assert!(format!("{:?}", builder).contains("test_cookie"));
+
+        let handler = builder.build().unwrap();
+        assert!(format!("{:?}", handler).contains("test_cookie"));
+        assert_eq!(handler.options.cookie_domain, Some("test.domain".into()));
+        assert_eq!(handler.options.cookie_name, "test_cookie");
+        assert_eq!(handler.options.cookie_path, "/abc");
+        assert_eq!(handler.options.same_site_policy, SameSite::Strict);
+        assert_eq!(handler.options.session_ttl, Some(Duration::from_secs(30)));
+    }
+
+    #[tokio::test]
+    async fn test_regenerate_replaces_cookie() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            let session = depot.session_mut().unwrap();
+            session.regenerate();
+            session.insert("user_id", 1).unwrap();
+        }
+        let secret = b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab";
+        let store = MemoryStore::new();
+        let handler = SessionHandler::builder(store.clone(), secret)
+            .cookie_path("/app")
+            .build()
+            .unwrap();
+        let mut session = Session::new();
+        session.insert("theme", "dark").unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
+
+        let router = Router::with_path("app/login").hoop(handler).post(login);
+        let respone = TestClient::post("http://127.0.0.1:5800/app/login")
+            .add_header(COOKIE, format!("salvo.session.id={signed_value}"), true)
+            .send(&Service::new(router))
+            .await;
+        let cookies = respone
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .map(|value| Cookie::parse_encoded(value.to_str().unwrap().to_owned()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(cookies.len(), 2);
+        let (removal, cookie) = (&cookies[0], &cookies[1]);
+        assert_eq!(removal.name(), "salvo.session.id");
+        assert_eq!(removal.value(), "");
+        assert_eq!(removal.path(), Some("/app"));
+        assert!(removal.expires_datetime().unwrap() < cookie::time::OffsetDateTime::now_utc());
+        assert_eq!(cookie.name(), "salvo.session.id");
+        assert_eq!(cookie.path(), Some("/app"));
+        assert_ne!(cookie.value(), signed_value);
+
+        let handler = SessionHandler::builder(store, secret).build().unwrap();
+        assert!(handler.load_by_cookie_value(&signed_value).await.is_none());
+        let session = handler.load_by_cookie_value(cookie.value()).await.unwrap();
+        assert_eq!(session.get::<String>("theme").as_deref(), Some("dark"));
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_cookie_secure_fn() {
+        #[handler]
+        async fn index() -> &'static str {
+            "index"
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_secure_fn(|req| {
+            req.header::<String>("x-forwarded-proto").as_deref() == Some("https")
+        })
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(index));
+
+        for (proto, secure) in [("https", Some(true)), ("http", None)] {
+            let respone = TestClient::get("http://127.0.0.1:5800/")
+                .add_header("x-forwarded-proto", proto, true)
+                .send(&service)
+                .await;
+            let cookie =
+                Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                    .unwrap();
+            assert_eq!(cookie.secure(), secure);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_request_is_secure() {
+        #[handler]
+        async fn secure(depot: &mut Depot) -> String {
+            depot.request_is_secure().to_string()
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(secure));
+
+        let mut respone = TestClient::get("https://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "true");
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "false");
+        assert!(!Depot::new().request_is_secure());
+    }
+
+    #[tokio::test]
+    async fn test_save_on_cease() {
+        #[handler]
+        async fn increment(depot: &mut Depot, ctrl: &mut FlowCtrl) {
+            let session = depot.session_mut().unwrap();
+            let count = session.get::<u32>("count").unwrap_or_default();
+            session.insert("count", count + 1).unwrap();
+            ctrl.cease();
+        }
+        let secret = b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab";
+        for save_on_cease in [false, true] {
+            let session_handler = SessionHandler::builder(MemoryStore::new(), secret)
+                .save_on_cease(save_on_cease)
+                .build()
+                .unwrap();
+            let router = Router::new().hoop(session_handler).get(increment);
+            let respone = TestClient::get("http://127.0.0.1:5800/")
+                .send(&Service::new(router))
+                .await;
+            assert_eq!(respone.headers().contains_key(SET_COOKIE), save_on_cease);
+        }
+    }
+
+    #[test]
+    fn test_cookie_template() {
+        let template = Cookie::build(("template", ""))
+            .path("/app")
+            .domain("example.com")
+            .same_site(SameSite::Strict)
+            .http_only(true)
+            .partitioned(true)
+            .build();
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_template(template)
+        .build()
+        .unwrap();
+        let cookie = handler.build_cookie(true, "value".into(), None, None);
+        assert_eq!(cookie.name(), "salvo.session.id");
+        assert_eq!(handler.verify_signature(cookie.value()).unwrap(), "value");
+        assert_eq!(cookie.path(), Some("/app"));
+        assert_eq!(cookie.domain(), Some("example.com"));
+        assert_eq!(cookie.same_site(), Some(SameSite::Strict));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.partitioned(), Some(true));
+        assert_eq!(cookie.secure(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_set_session_same_site() {
+        #[handler]
+        async fn callback(depot: &mut Depot) {
+            depot.set_session_same_site(SameSite::None);
+        }
+        #[handler]
+        async fn index() {}
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("callback").get(callback))
+            .push(Router::with_path("index").get(index));
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/callback")
+            .send(&service)
+            .await;
+        let cookie =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(cookie.same_site(), Some(SameSite::None));
+        assert_eq!(cookie.secure(), Some(true));
+
+        let respone = TestClient::get("http://127.0.0.1:5800/index")
+            .send(&service)
+            .await;
+        let cookie =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+        assert_ne!(cookie.secure(), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_set_session_same_site_reissues_cookie() {
+        #[handler]
+        async fn start_login(depot: &mut Depot) {
+            depot.set_session_same_site(SameSite::None);
+        }
+        #[handler]
+        async fn visit(depot: &mut Depot) {
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("visited", true)
+                .unwrap();
+        }
+        #[handler]
+        async fn index() {}
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .save_unchanged(false)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").get(start_login))
+            .push(Router::with_path("visit").get(visit))
+            .push(Router::with_path("index").get(index));
+        let mut harness = SessionTestHarness::new(router);
+
+        harness
+            .send(TestClient::get("http://127.0.0.1:5800/visit"))
+            .await;
+        let value = harness
+            .cookie("salvo.session.id")
+            .unwrap()
+            .value()
+            .to_owned();
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/index"))
+            .await;
+        assert!(res.headers().get(SET_COOKIE).is_none());
+
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/login"))
+            .await;
+        let cookie =
+            Cookie::parse_encoded(res.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(cookie.value(), value);
+        assert_eq!(cookie.same_site(), Some(SameSite::None));
+        assert_eq!(cookie.secure(), Some(true));
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        assert_eq!(handler.reserved_prefix(), "__salvo.");
+
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        session.insert(CREATED_KEY, 0).unwrap();
+        session.insert(VERSION_KEY, 3).unwrap();
+        session.insert(BINDING_KEY, "fingerprint").unwrap();
+        handler.sanitize(&mut session);
+        let mut keys = all_keys(&session);
+        keys.sort();
+        assert_eq!(keys, vec![CREATED_KEY, "user_id"]);
+    }
+
+    #[tokio::test]
+    async fn test_underscore_app_key() {
+        #[handler]
+        async fn set(depot: &mut Depot) {
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("_csrf", "token")
+                .unwrap();
+        }
+        #[handler]
+        async fn get(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<String>("_csrf"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_on_empty(false)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("set").post(set))
+            .get(get);
+        let mut harness = SessionTestHarness::new(router);
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/set"))
+            .await;
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), r#"Some("token")"#);
+    }
+
+    #[tokio::test]
+    async fn test_skip_save_on_error() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn fail(depot: &mut Depot, res: &mut Response) {
+            depot.session_mut().unwrap().insert("user_id", 2).unwrap();
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        #[handler]
+        async fn fail_logout(depot: &mut Depot, res: &mut Response) {
+            depot.session_mut().unwrap().destroy();
+            res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        #[handler]
+        async fn user_id(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        // Sessions loaded from a `MemoryStore` share their data with the stored ones, so a
+        // cookie store shows what is saved.
+        let session_handler = SessionHandler::builder(
+            CookieStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .skip_save_on_error(true)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .get(user_id)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("fail").post(fail))
+            .push(Router::with_path("fail_logout").post(fail_logout));
+        let mut harness = SessionTestHarness::new(router);
+
+        let res = harness
+            .send(TestClient::post("http://127.0.0.1:5800/fail"))
+            .await;
+        assert!(res.headers().get(SET_COOKIE).is_none());
+        assert!(harness.cookie("salvo.session.id").is_none());
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/fail"))
+            .await;
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "Some(1)");
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/fail_logout"))
+            .await;
+        assert!(harness.cookie("salvo.session.id").is_none());
+    }
+
+    #[test]
+    fn test_from_key_file() {
+        let key = Key::generate();
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("salvo-session-key-{}", std::process::id()));
+        let signed_with = |path: &Path| {
+            let handler = HandlerBuilder::from_key_file(MemoryStore::new(), path)
+                .unwrap()
+                .build()
+                .unwrap();
+            handler.sign_value("value")
+        };
+        let expected = HandlerBuilder::from_key(MemoryStore::new(), key.clone())
+            .build()
+            .unwrap()
+            .sign_value("value");
+
+        std::fs::write(&path, key.master()).unwrap();
+        assert_eq!(signed_with(&path), expected);
+        std::fs::write(&path, format!("{}\n", base64::encode(key.master()))).unwrap();
+        assert_eq!(signed_with(&path), expected);
+        let pem = format!(
+            "-----BEGIN SESSION KEY-----\n{}\n-----END SESSION KEY-----\n",
+            base64::encode_config(key.master(), base64::URL_SAFE_NO_PAD)
+        );
+        std::fs::write(&path, pem).unwrap();
+        assert_eq!(signed_with(&path), expected);
+
+        std::fs::write(&path, base64::encode([0u8; 32])).unwrap();
+        let error = HandlerBuilder::from_key_file(MemoryStore::new(), &path).unwrap_err();
+        assert!(error.to_string().contains("found 32 bytes"), "{error}");
+        std::fs::write(&path, [0xffu8; 10]).unwrap();
+        assert!(HandlerBuilder::from_key_file(MemoryStore::new(), &path).is_err());
+        std::fs::remove_file(&path).unwrap();
+        assert!(HandlerBuilder::from_key_file(MemoryStore::new(), &path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_skip_if() {
+        #[handler]
+        async fn asset(depot: &mut Depot) -> &'static str {
+            if depot.session().is_some() {
+                "session"
+            } else {
+                "no session"
+            }
+        }
+        let store = RecordingStore::new(MemoryStore::new());
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .skip_if(|req| req.uri().path().starts_with("/assets/"))
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("<**path>").get(asset));
+        let service = Service::new(router);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/assets/app.js")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+        assert_eq!(respone.take_string().await.unwrap(), "no session");
+        assert!(store.operations().is_empty());
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/index.html")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_some());
+        assert_eq!(respone.take_string().await.unwrap(), "session");
+    }
+
+    #[tokio::test]
+    async fn test_omit_same_site() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .omit_same_site(true)
+        .build()
+        .unwrap();
+        assert_eq!(session_handler.config().same_site, "omitted");
+        let router = Router::new().hoop(session_handler).post(login);
+        let respone = TestClient::post("http://127.0.0.1:5800/")
+            .send(&Service::new(router))
+            .await;
+        let set_cookie = respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap();
+        assert!(set_cookie.starts_with("salvo.session.id="));
+        assert!(!set_cookie.to_ascii_lowercase().contains("samesite"));
+    }
+
+    #[tokio::test]
+    async fn test_query_token_param() {
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .query_token_param(Some("token".into()))
+        .build()
+        .unwrap();
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let token = session_handler.save(session).await.unwrap().unwrap();
+        let token = form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>();
+        let service = Service::new(Router::new().hoop(session_handler).get(current_user));
+
+        let respone = TestClient::get(format!("http://127.0.0.1:5800/?a=1&token={token}"))
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::SEE_OTHER));
+        assert_eq!(respone.headers().get(LOCATION).unwrap(), "/?a=1");
+        let cookie = respone.cookie("salvo.session.id").unwrap().clone();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/?a=1")
+            .add_header(COOKIE, format!("salvo.session.id={}", cookie.value()), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+
+        let respone = TestClient::get(format!("http://127.0.0.1:5800/?token={token}"))
+            .send(&service)
+            .await;
+        assert_eq!(respone.headers().get(LOCATION).unwrap(), "/");
+        assert!(respone.cookie("salvo.session.id").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rotate_every_request() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .rotate_every_request(true)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .get(current_user);
+        let service = Service::new(router);
+        let get = |cookie: &Cookie<'static>| {
+            TestClient::get("http://127.0.0.1:5800/").add_header(
+                COOKIE,
+                format!("salvo.session.id={}", cookie.value()),
+                true,
+            )
+        };
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let first = respone.cookie("salvo.session.id").unwrap().clone();
+        let mut respone = get(&first).send(&service).await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+        let second = respone.cookie("salvo.session.id").unwrap().clone();
+        assert_ne!(second.value(), first.value());
+
+        let mut respone = get(&first).send(&service).await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+        let mut respone = get(&second).send(&service).await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+    }
+
+    #[tokio::test]
+    async fn test_session_key_extractor() {
+        #[handler]
+        async fn count_calls(depot: &mut Depot) -> String {
+            let session = depot.session_mut().unwrap();
+            let calls = session.get::<u32>("calls").unwrap_or_default() + 1;
+            session.insert("calls", calls).unwrap();
+            calls.to_string()
+        }
+        let store = MemoryStore::new();
+        let store_key = store.store_session(Session::new()).await.unwrap().unwrap();
+        let session_handler = SessionHandler::builder(
+            store,
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .session_key_extractor(|req| req.header::<String>("x-api-key"))
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(count_calls));
+
+        for expected in ["1", "2"] {
+            let mut respone = TestClient::get("http://127.0.0.1:5800/")
+                .add_header("x-api-key", &store_key, true)
+                .send(&service)
+                .await;
+            assert!(!respone.headers().contains_key(SET_COOKIE));
+            assert_eq!(respone.take_string().await.unwrap(), expected);
+        }
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert!(respone.headers().contains_key(SET_COOKIE));
+    }
+
+    #[tokio::test]
+    async fn test_track_last_seen() {
+        #[handler]
+        async fn index() {}
+        let store = MemoryStore::new();
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .track_last_seen(Duration::from_secs(60))
+        .build()
+        .unwrap();
+        let mut session = Session::new();
+        session
+            .insert(LAST_SEEN_KEY, Utc::now().timestamp() - 120)
+            .unwrap();
+        let signed_value = session_handler.save(session).await.unwrap().unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(index));
+
+        TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, format!("salvo.session.id={signed_value}"), true)
+            .send(&service)
+            .await;
+        let handler = SessionHandler::builder(
+            store,
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let session = handler.load_by_cookie_value(&signed_value).await.unwrap();
+        let last_seen = session.get::<i64>(LAST_SEEN_KEY).unwrap();
+        assert!(Utc::now().timestamp() - last_seen < 60);
+    }
+
+    #[tokio::test]
+    async fn test_destroy_removal_cookie_attributes() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn logout(depot: &mut Depot) {
+            depot.destroy_session();
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .same_site_policy(SameSite::Strict)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("logout").post(logout));
+        let service = Service::new(router);
+
+        let respone = TestClient::post("https://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        let respone = TestClient::post("https://127.0.0.1:5800/logout")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        let removal =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(removal.name(), "salvo.session.id");
+        assert_eq!(removal.value(), "");
+        assert_eq!(removal.same_site(), Some(SameSite::Strict));
+        assert_eq!(removal.secure(), Some(true));
+        assert_eq!(removal.http_only(), Some(true));
+        assert!(removal.expires_datetime().unwrap() < cookie::time::OffsetDateTime::now_utc());
+    }
+
+    #[tokio::test]
+    async fn test_session_will_set_cookie() {
+        #[handler]
+        async fn report(
+            depot: &mut Depot,
+            req: &mut Request,
+            res: &mut Response,
+            ctrl: &mut FlowCtrl,
+        ) {
+            ctrl.call_next(req, depot, res).await;
+            res.render(depot.session_will_set_cookie().to_string());
+        }
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn visit(depot: &mut Depot) {
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("visited", true)
+                .unwrap();
+        }
+        #[handler]
+        async fn index() {}
+        let router = |builder: HandlerBuilder<MemoryStore>| {
+            Router::new()
+                .hoop(report)
+                .hoop(builder.save_unchanged(false).build().unwrap())
+                .push(Router::with_path("login").post(login))
+                .push(Router::with_path("visit").post(visit))
+                .get(index)
+        };
+        let builder = || {
+            SessionHandler::builder(
+                MemoryStore::new(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+        };
+        let service = Service::new(router(builder()));
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+        assert_eq!(respone.take_string().await.unwrap(), "false");
+
+        let mut respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        assert_eq!(respone.take_string().await.unwrap(), "true");
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie.clone(), true)
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+        assert_eq!(respone.take_string().await.unwrap(), "false");
+
+        // The memory store keeps the cookie value of a loaded session, so saving its changes
+        // sends no cookie.
+        let mut respone = TestClient::post("http://127.0.0.1:5800/visit")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+        assert_eq!(respone.take_string().await.unwrap(), "false");
+
+        let service = Service::new(router(builder().cookie_size_budget(16)));
+        let mut respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+        assert_eq!(respone.take_string().await.unwrap(), "false");
+    }
+
+    #[tokio::test]
+    async fn test_cookie_size_budget() {
+        #[handler]
+        async fn report(
+            depot: &mut Depot,
+            req: &mut Request,
+            res: &mut Response,
+            ctrl: &mut FlowCtrl,
+        ) {
+            ctrl.call_next(req, depot, res).await;
+            if let Some(e) = depot.session_error() {
+                res.render(e.to_string());
+            }
+        }
+        #[handler]
+        async fn write(req: &mut Request, depot: &mut Depot) {
+            let len = req.query::<usize>("len").unwrap();
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("data", "x".repeat(len))
+                .unwrap();
+        }
+        let session_handler = SessionHandler::builder(
+            CookieStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_size_budget(1024)
+        .build()
+        .unwrap();
+        let router = Router::new().hoop(report).hoop(session_handler).get(write);
+        let service = Service::new(router);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/?len=100")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_some());
+        assert_eq!(respone.take_string().await.unwrap(), "");
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/?len=2000")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+        assert!(respone
+            .take_string()
+            .await
+            .unwrap()
+            .starts_with("session cookie of"));
+    }
+
+    #[tokio::test]
+    async fn test_session_set_volatile() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn set_volatile(depot: &mut Depot) {
+            assert!(depot.session_set_volatile("secret", "shh").unwrap());
+        }
+        #[handler]
+        async fn read(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<String>("secret"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("volatile").hoop(set_volatile).get(read))
+            .get(read);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/volatile")
+            .add_header(COOKIE, cookie.clone(), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), r#"Some("shh")"#);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+    }
+
+    #[tokio::test]
+    async fn test_after_handle() {
+        #[handler]
+        async fn index(depot: &mut Depot) -> String {
+            let session = depot.session().unwrap();
+            format!(
+                "{:?} {:?}",
+                session.get::<u32>("requests"),
+                session.get::<String>("last_page")
+            )
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .save_unchanged(false)
+        .after_handle(|req, session| {
+            let requests = session.get::<u32>("requests").unwrap_or_default();
+            session.insert("requests", requests + 1).unwrap();
+            session.insert("last_page", req.uri().path()).unwrap();
+        })
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("<page>").get(index));
+        let service = Service::new(router);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/first")
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None None");
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/second")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(
+            respone.take_string().await.unwrap(),
+            r#"Some(1) Some("/first")"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_fn() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let revoked_before = Arc::new(AtomicU64::new(0));
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .validate_fn({
+            let revoked_before = revoked_before.clone();
+            move |session| {
+                session.get::<u64>(CREATED_KEY).unwrap_or_default()
+                    >= revoked_before.load(Ordering::Relaxed)
+            }
+        })
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .get(current_user);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie.clone(), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
 
-        let mut session = self.load_or_create(cookie_value).await;
+        revoked_before.store(u64::MAX, Ordering::Relaxed);
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+    }
 
-        if let Some(ttl) = self.session_ttl {
-            session.expire_in(ttl);
+    #[tokio::test]
+    async fn test_clear_session_cookie() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
         }
-
-        depot.set_session(session);
-
-        ctrl.call_next(req, depot, res).await;
-        if ctrl.is_ceased() {
-            return;
+        #[handler]
+        async fn logout(depot: &mut Depot) {
+            depot.clear_session_cookie();
         }
+        let store = MemoryStore::new();
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_path("/app")
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("app/login").post(login))
+            .push(Router::with_path("app/logout").post(logout));
+        let service = Service::new(router);
 
-        let session = depot.take_session().expect("session should exist in depot");
-        if session.is_destroyed() {
-            if let Err(e) = self.store.destroy_session(session).await {
-                tracing::error!(error = ?e, "unable to destroy session");
-            }
-            res.remove_cookie(&self.cookie_name);
-        } else if self.save_unchanged || session.data_changed() {
-            match self.store.store_session(session).await {
-                Ok(cookie_value) => {
-                    if let Some(cookie_value) = cookie_value {
-                        let secure_cookie = req.uri().scheme() == Some(&Scheme::HTTPS);
-                        let cookie = self.build_cookie(secure_cookie, cookie_value);
-                        res.add_cookie(cookie);
-                    }
-                }
-                Err(e) => {
-                    tracing::error!(error = ?e, "store session error");
-                }
-            }
-        }
-    }
-}
+        let respone = TestClient::post("http://127.0.0.1:5800/app/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+        let respone = TestClient::post("http://127.0.0.1:5800/app/logout")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        let removal =
+            Cookie::parse_encoded(respone.headers().get(SET_COOKIE).unwrap().to_str().unwrap())
+                .unwrap();
+        assert_eq!(removal.name(), "salvo.session.id");
+        assert_eq!(removal.value(), "");
+        assert_eq!(removal.path(), Some("/app"));
+        assert_eq!(removal.max_age(), Some(cookie::time::Duration::ZERO));
 
-impl<S> SessionHandler<S>
-where
-    S: SessionStore,
-{
-    /// Create new `HandlerBuilder`
-    pub fn builder(store: S, secret: &[u8]) -> HandlerBuilder<S> {
-        HandlerBuilder::new(store, secret)
+        let handler = SessionHandler::builder(
+            store,
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let cookie = Cookie::parse_encoded(cookie.to_str().unwrap()).unwrap();
+        let session = handler.load_by_cookie_value(cookie.value()).await.unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
     }
-    #[inline]
-    async fn load_or_create(&self, cookie_value: Option<String>) -> Session {
-        let session = match cookie_value {
-            Some(cookie_value) => self.store.load_session(cookie_value).await.ok().flatten(),
-            None => None,
-        };
 
-        session
-            .and_then(|session| session.validate())
-            .unwrap_or_default()
-    }
-    // the following is reused verbatim from
-    // https://github.com/SergioBenitez/cookie-rs/blob/master/src/secure/signed.rs#L51-L66
-    /// Given a signed value `str` where the signature is prepended to `value`,
-    /// verifies the signed value and returns it. If there's a problem, returns
-    /// an `Err` with a string describing the issue.
-    fn verify_signature(&self, cookie_value: &str) -> Result<String, Error> {
-        if cookie_value.len() < BASE64_DIGEST_LEN {
-            return Err(Error::Other(
-                "length of value is <= BASE64_DIGEST_LEN".into(),
-            ));
+    #[tokio::test]
+    async fn test_persist_methods() {
+        #[handler]
+        async fn increment(depot: &mut Depot) -> String {
+            let session = depot.session_mut().unwrap();
+            let visits = session.get::<u32>("visits").unwrap_or_default() + 1;
+            session.insert("visits", visits).unwrap();
+            visits.to_string()
+        }
+        #[handler]
+        async fn logout(depot: &mut Depot) {
+            depot.session_mut().unwrap().destroy();
         }
+        let store = MemoryStore::new();
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .persist_methods(vec![Method::POST])
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("logout").get(logout))
+            .get(increment)
+            .post(increment);
+        let service = Service::new(router);
 
-        // Split [MAC | original-value] into its two parts.
-        let (digest_str, value) = cookie_value.split_at(BASE64_DIGEST_LEN);
-        let digest =
-            base64::decode(digest_str).map_err(|_| Error::Other("bad base64 digest".into()))?;
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert!(!respone.headers().contains_key(SET_COOKIE));
+        assert_eq!(store.count().await, 0);
 
-        // Perform the verification.
-        let mut hmac = self.hmac.clone();
-        hmac.update(value.as_bytes());
-        if hmac.verify(&digest).is_ok() {
-            return Ok(value.to_string());
-        }
-        for hmac in &self.fallback_hmacs {
-            let mut hmac = hmac.clone();
-            hmac.update(value.as_bytes());
-            if hmac.verify(&digest).is_ok() {
-                return Ok(value.to_string());
-            }
-        }
-        Err(Error::Other("value did not verify".into()))
+        let respone = TestClient::post("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        assert_eq!(store.count().await, 1);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/logout")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        assert_eq!(store.count().await, 0);
     }
-    fn build_cookie(&self, secure: bool, cookie_value: String) -> Cookie<'static> {
-        let mut cookie = Cookie::build((self.cookie_name.clone(), cookie_value))
-            .http_only(true)
-            .same_site(self.same_site_policy)
-            .secure(secure)
-            .path(self.cookie_path.clone())
-            .build();
 
-        if let Some(ttl) = self.session_ttl {
-            cookie.set_expires(Some((std::time::SystemTime::now() + ttl).into()));
-        }
+    #[test]
+    fn test_session_keys() {
+        let mut depot = Depot::new();
+        assert!(depot.session_keys().is_empty());
 
-        if let Some(cookie_domain) = self.cookie_domain.clone() {
-            cookie.set_domain(cookie_domain)
-        }
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        session.insert("cart", vec![1, 2]).unwrap();
+        session.insert(VERSION_KEY, 1).unwrap();
+        depot.set_session(session);
+        assert_eq!(depot.session_keys(), vec!["cart", "user_id"]);
+    }
 
-        self.sign_cookie(&mut cookie);
+    #[tokio::test]
+    async fn test_session_outside_request() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
 
-        cookie
-    }
-    // The following is reused verbatim from
-    // https://github.com/SergioBenitez/cookie-rs/blob/master/src/secure/signed.rs#L37-46
-    /// signs the cookie's value providing integrity and authenticity.
-    fn sign_cookie(&self, cookie: &mut Cookie<'_>) {
-        // Compute HMAC-SHA256 of the cookie's value.
-        let mut mac = self.hmac.clone();
-        mac.update(cookie.value().as_bytes());
+        let session = handler.load_by_cookie_value(&signed_value).await.unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+        assert!(handler
+            .load_by_cookie_value(&signed_value[1..])
+            .await
+            .is_none());
 
-        // Cookie's new value is [MAC | original-value].
-        let mut new_value = base64::encode(mac.finalize().into_bytes());
-        new_value.push_str(cookie.value());
-        cookie.set_value(new_value);
+        handler.destroy(session).await.unwrap();
+        assert!(handler.load_by_cookie_value(&signed_value).await.is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use salvo_core::http::header::*;
-    use salvo_core::http::Method;
-    use salvo_core::prelude::*;
-    use salvo_core::test::{ResponseExt, TestClient};
+    #[tokio::test]
+    async fn test_on_load_migrate() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .on_load_migrate(|session| {
+            if let Some(name) = session.get::<String>("name") {
+                session.insert("user_name", name).unwrap();
+                session.remove("name");
+            }
+            if session.get::<bool>("corrupt").is_some() {
+                panic!("unable to migrate session");
+            }
+        })
+        .build()
+        .unwrap();
+        let mut session = Session::new();
+        session.insert("name", "alice").unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
+        let session = handler.load_by_cookie_value(&signed_value).await.unwrap();
+        assert_eq!(session.get::<String>("user_name").as_deref(), Some("alice"));
+        assert_eq!(session.get::<String>("name"), None);
 
-    use super::*;
+        let mut session = Session::new();
+        session.insert("corrupt", true).unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
+        assert!(handler.load_by_cookie_value(&signed_value).await.is_none());
+    }
 
     #[test]
-    fn test_session_data() {
-        let builder = SessionHandler::builder(
-            async_session::CookieStore,
+    fn test_is_cookie_store() {
+        assert!(is_cookie_store::<CookieStore>());
+        assert!(!is_cookie_store::<EncryptedStore<CookieStore>>());
+        assert!(!is_cookie_store::<MemoryStore>());
+    }
+
+    #[tokio::test]
+    async fn test_before_save_after_load() {
+        let store = MemoryStore::new();
+        let handler = SessionHandler::builder(
+            store.clone(),
             b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
         )
-        .cookie_domain("test.domain")
-        .cookie_name("test_cookie")
-        .cookie_path("/abc")
-        .same_site_policy(SameSite::Strict)
-        .session_ttl(Some(Duration::from_secs(30)));
-        assert!(format!("{:?}", builder).contains("test_cookie"));
+        .before_save(|session| session.remove("nonce"))
+        .after_load(|session| session.insert("nonce", "fresh").unwrap())
+        .build()
+        .unwrap();
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        session.insert("nonce", "used").unwrap();
+        let signed_value = handler.save(session).await.unwrap().unwrap();
+        let cookie_value = handler.verify_signature(&signed_value).unwrap();
+        let stored = store.load_session(cookie_value).await.unwrap().unwrap();
+        assert_eq!(stored.get::<String>("nonce"), None);
 
-        let handler = builder.build().unwrap();
-        assert!(format!("{:?}", handler).contains("test_cookie"));
-        assert_eq!(handler.cookie_domain, Some("test.domain".into()));
-        assert_eq!(handler.cookie_name, "test_cookie");
-        assert_eq!(handler.cookie_path, "/abc");
-        assert_eq!(handler.same_site_policy, SameSite::Strict);
-        assert_eq!(handler.session_ttl, Some(Duration::from_secs(30)));
+        let session = handler.load_by_cookie_value(&signed_value).await.unwrap();
+        assert_eq!(session.get::<u32>("user_id"), Some(1));
+        assert_eq!(session.get::<String>("nonce").as_deref(), Some("fresh"));
+        assert!(!session.data_changed());
+    }
+
+    #[test]
+    fn test_builder_from_key() {
+        let key = Key::generate();
+        let handler = HandlerBuilder::from_key(MemoryStore::new(), key.clone())
+            .build()
+            .unwrap();
+        let mut cookie = Cookie::new("test", "value");
+        handler.sign_cookie(&mut cookie);
+        assert_eq!(handler.verify_signature(cookie.value()).unwrap(), "value");
+
+        let other = SessionHandler::builder(MemoryStore::new(), key.master())
+            .build()
+            .unwrap();
+        assert!(other.verify_signature(cookie.value()).is_ok());
     }
 
     #[tokio::test]
@@ -542,4 +4032,260 @@ mod tests {
             .await;
         assert_eq!(respone.take_string().await.unwrap(), "home");
     }
+
+    #[tokio::test]
+    async fn test_precommit_cookie() {
+        #[handler]
+        pub async fn login(depot: &mut Depot) {
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("username", "salvo")
+                .unwrap();
+        }
+        #[handler]
+        pub async fn stream() -> &'static str {
+            "streaming"
+        }
+
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .precommit_cookie(true)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").get(login))
+            .push(Router::with_path("stream").get(stream));
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+
+        let respone = TestClient::get("http://127.0.0.1:5800/stream")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.headers().get_all(SET_COOKIE).iter().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_require_valid() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn admin() -> &'static str {
+            "admin"
+        }
+        let store = MemoryStore::new();
+        let session_handler = |require_valid| {
+            SessionHandler::builder(
+                store.clone(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .require_valid(require_valid)
+            .build()
+            .unwrap()
+        };
+        let router = Router::new()
+            .push(
+                Router::with_path("login")
+                    .hoop(session_handler(false))
+                    .post(login),
+            )
+            .push(
+                Router::with_path("admin")
+                    .hoop(session_handler(true))
+                    .get(admin),
+            );
+        let mut harness = SessionTestHarness::new(router);
+
+        let res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/admin"))
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::UNAUTHORIZED));
+        assert!(harness.cookie("salvo.session.id").is_none());
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/admin"))
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        assert_eq!(res.take_string().await.unwrap(), "admin");
+    }
+
+    #[tokio::test]
+    async fn test_deferred_anonymous_session() {
+        #[handler]
+        async fn anonymous(depot: &mut Depot) -> String {
+            depot.session().unwrap().id().to_owned()
+        }
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            let session = depot.session().unwrap();
+            format!(
+                "{:?} {}",
+                session.get::<u32>("user_id"),
+                depot.session_age().is_some()
+            )
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .save_unchanged(false)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("anonymous").get(anonymous))
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("me").get(current_user));
+        let mut harness = SessionTestHarness::new(router);
+
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/anonymous"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "");
+        assert!(res.headers().get(SET_COOKIE).is_none());
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        let cookie = harness.cookie("salvo.session.id").unwrap();
+        assert!(cookie.expires().is_some());
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/me"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "Some(1) true");
+    }
+
+    #[tokio::test]
+    async fn test_handler_cookies_survive() {
+        #[handler]
+        async fn login(depot: &mut Depot, res: &mut Response) {
+            res.add_cookie(Cookie::new("theme", "dark"));
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn logout(depot: &mut Depot, res: &mut Response) {
+            res.add_cookie(Cookie::new("goodbye", "1"));
+            depot.destroy_session();
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("logout").post(logout));
+        let mut harness = SessionTestHarness::new(router);
+
+        let res = harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        assert_eq!(res.cookies().delta().count(), 2);
+        assert_eq!(harness.cookie("theme").unwrap().value(), "dark");
+        assert!(harness.cookie("salvo.session.id").is_some());
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/logout"))
+            .await;
+        assert!(harness.cookie("salvo.session.id").is_none());
+        assert_eq!(harness.cookie("theme").unwrap().value(), "dark");
+        assert_eq!(harness.cookie("goodbye").unwrap().value(), "1");
+    }
+
+    #[tokio::test]
+    async fn test_cookie_on_empty() {
+        #[handler]
+        pub async fn anonymous() -> &'static str {
+            "anonymous"
+        }
+        #[handler]
+        pub async fn login(depot: &mut Depot) {
+            depot
+                .session_mut()
+                .unwrap()
+                .insert("username", "salvo")
+                .unwrap();
+        }
+
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .cookie_on_empty(false)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .get(anonymous)
+            .push(Router::with_path("login").get(login));
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_none());
+
+        let respone = TestClient::get("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        assert!(respone.headers().get(SET_COOKIE).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_multiple_session_handlers() {
+        #[handler]
+        async fn visit(depot: &mut Depot) -> String {
+            fn increment(session: &mut Session) -> u32 {
+                let count = session.get::<u32>("count").unwrap_or_default() + 1;
+                session.insert("count", count).unwrap();
+                count
+            }
+            let account = increment(depot.named_session_mut("account").unwrap());
+            let checkout = increment(depot.session_mut().unwrap());
+            format!("{account} {checkout}")
+        }
+        let session_handler = |cookie_name| {
+            SessionHandler::builder(
+                MemoryStore::new(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .cookie_name(cookie_name)
+            .build()
+            .unwrap()
+        };
+        let router = Router::new()
+            .hoop(session_handler("account"))
+            .hoop(session_handler("checkout"))
+            .get(visit);
+        let mut harness = SessionTestHarness::new(router);
+
+        harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert!(harness.cookie("account").is_some());
+        assert!(harness.cookie("checkout").is_some());
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "2 2");
+    }
 }
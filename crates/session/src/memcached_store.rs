@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+use async_memcached::{Client, Error as MemcachedError, Status};
+use async_session::{async_trait, serde_json, Error, Result, Session, SessionStore};
+use tokio::sync::Mutex;
+
+use crate::{BatchSessionStore, PatchableStore, StoreInfo, StoreTelemetry};
+
+/// Items expiring further out than this are given to memcached as an absolute unix timestamp,
+/// because it reads larger relative expirations as timestamps.
+const MAX_RELATIVE_EXPIRY: u64 = 60 * 60 * 24 * 30;
+
+/// Session store backed by Memcached.
+///
+/// Sessions are serialized as JSON and stored under their id, which is a digest of the cookie
+/// value, with the session expiry as the item expiry. Sessions without an expiry are stored
+/// without one and may still be evicted by memcached when it runs out of memory.
+///
+/// Memcached has no way to list keys, so [`clear_store`](SessionStore::clear_store) is not
+/// supported and returns an error.
+#[derive(Clone)]
+pub struct MemcachedStore {
+    client: Arc<Mutex<Client>>,
+    prefix: String,
+}
+
+impl Debug for MemcachedStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemcachedStore")
+            .field("prefix", &self.prefix)
+            .finish()
+    }
+}
+
+impl MemcachedStore {
+    /// Create a new `MemcachedStore` from a connected client.
+    ///
+    /// The store uses this single connection for every request, so session reads and writes
+    /// wait for each other, and it doesn't reconnect: once the connection is broken, every
+    /// operation fails until the store is created again with a new client.
+    #[inline]
+    pub fn new(client: Client) -> Self {
+        Self {
+            client: Arc::new(Mutex::new(client)),
+            prefix: String::new(),
+        }
+    }
+
+    /// Sets a prefix for the keys of stored sessions, for example to share a memcached
+    /// instance between applications.
+    #[inline]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, id: &str) -> String {
+        format!("{}{id}", self.prefix)
+    }
+}
+
+/// Returns the memcached item expiry for the session, `0` meaning no expiry.
+fn item_ttl(session: &Session) -> i64 {
+    let Some(expiry) = session.expiry() else {
+        return 0;
+    };
+    match session.expires_in() {
+        Some(expires_in) if expires_in.as_secs() > MAX_RELATIVE_EXPIRY => expiry.timestamp(),
+        // Round up so a session expiring within the second is still stored.
+        Some(expires_in) => expires_in.as_secs() as i64 + 1,
+        // A negative expiry makes memcached drop the item right away.
+        None => -1,
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemcachedStore {
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        let id = Session::id_from_cookie_value(&cookie_value)?;
+        tracing::trace!(id, "loading session");
+        let value = self.client.lock().await.get(self.key(&id)).await?;
+        match value {
+            Some(value) => {
+                let session: Session = serde_json::from_slice(&value.data)?;
+                Ok(session.validate())
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        tracing::trace!(id = session.id(), "storing session");
+        let data = serde_json::to_string(&session)?;
+        self.client
+            .lock()
+            .await
+            .set(
+                self.key(session.id()),
+                &data,
+                Some(item_ttl(&session)),
+                None,
+            )
+            .await?;
+        session.reset_data_changed();
+        Ok(session.into_cookie_value())
+    }
+
+    async fn destroy_session(&self, session: Session) -> Result {
+        tracing::trace!(id = session.id(), "destroying session");
+        match self
+            .client
+            .lock()
+            .await
+            .delete(self.key(session.id()))
+            .await
+        {
+            Ok(()) | Err(MemcachedError::Protocol(Status::NotFound)) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn clear_store(&self) -> Result {
+        Err(Error::msg(
+            "clearing a memcached session store is not supported",
+        ))
+    }
+}
+
+#[async_trait]
+impl BatchSessionStore for MemcachedStore {
+    /// Loads all the sessions with a single memcached `get`.
+    async fn load_sessions(&self, cookie_values: Vec<String>) -> Result<Vec<Option<Session>>> {
+        let keys = cookie_values
+            .iter()
+            .map(|cookie_value| Ok(self.key(&Session::id_from_cookie_value(cookie_value)?)))
+            .collect::<Result<Vec<_>>>()?;
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        tracing::trace!(count = keys.len(), "loading sessions");
+        let values = match self.client.lock().await.get_many(&keys).await {
+            Ok(values) => values,
+            Err(MemcachedError::Protocol(Status::NotFound)) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let mut found = HashMap::with_capacity(values.len());
+        for value in values {
+            found.insert(value.key, value.data);
+        }
+        keys.into_iter()
+            .map(|key| match found.remove(key.as_bytes()) {
+                Some(data) => Ok(serde_json::from_slice::<Session>(&data)?.validate()),
+                None => Ok(None),
+            })
+            .collect()
+    }
+}
+
+impl PatchableStore for MemcachedStore {}
+
+impl StoreTelemetry for MemcachedStore {
+    fn store_telemetry(&self) -> StoreInfo {
+        StoreInfo::new("memcached")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_session::chrono::{self, Utc};
+
+    use super::*;
+
+    #[test]
+    fn test_item_ttl() {
+        let mut session = Session::new();
+        assert_eq!(item_ttl(&session), 0);
+
+        session.expire_in(Duration::from_secs(60));
+        assert!((60..=61).contains(&item_ttl(&session)));
+
+        session.expire_in(Duration::from_secs(MAX_RELATIVE_EXPIRY + 60));
+        assert_eq!(item_ttl(&session), session.expiry().unwrap().timestamp());
+
+        session.set_expiry(Utc::now() - chrono::Duration::seconds(1));
+        assert_eq!(item_ttl(&session), -1);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of tracked addresses above which elapsed windows are pruned.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Counts new sessions created per client address in fixed time windows.
+#[derive(Debug)]
+pub(crate) struct NewSessionLimiter {
+    max_count: usize,
+    window: Duration,
+    windows: Mutex<HashMap<IpAddr, Window>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    started_at: Instant,
+    count: usize,
+}
+
+impl NewSessionLimiter {
+    pub(crate) fn new(max_count: usize, window: Duration) -> Self {
+        Self {
+            max_count,
+            window,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns `true` if `ip` has already created the maximum number of sessions in the current
+    /// window.
+    pub(crate) fn is_exceeded(&self, ip: IpAddr) -> bool {
+        let windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        windows.get(&ip).is_some_and(|window| {
+            window.started_at.elapsed() < self.window && window.count >= self.max_count
+        })
+    }
+
+    /// Records a new session created by `ip`.
+    pub(crate) fn record(&self, ip: IpAddr) {
+        let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        if windows.len() >= PRUNE_THRESHOLD {
+            windows.retain(|_, window| window.started_at.elapsed() < self.window);
+        }
+        let window = windows.entry(ip).or_insert_with(|| Window {
+            started_at: Instant::now(),
+            count: 0,
+        });
+        if window.started_at.elapsed() >= self.window {
+            window.started_at = Instant::now();
+            window.count = 0;
+        }
+        window.count += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use cookie::Cookie;
+    use salvo_core::http::header::COOKIE;
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+    use crate::{MemoryStore, SessionHandler};
+
+    #[test]
+    fn test_new_session_limiter() {
+        let limiter = NewSessionLimiter::new(2, Duration::from_millis(50));
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+
+        limiter.record(ip);
+        assert!(!limiter.is_exceeded(ip));
+        limiter.record(ip);
+        assert!(limiter.is_exceeded(ip));
+        assert!(!limiter.is_exceeded(other_ip));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(!limiter.is_exceeded(ip));
+        limiter.record(ip);
+        assert!(!limiter.is_exceeded(ip));
+    }
+
+    #[tokio::test]
+    async fn test_max_new_sessions_per_ip() {
+        #[handler]
+        async fn index() -> &'static str {
+            "index"
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .max_new_sessions_per_ip(1, Duration::from_secs(60))
+        .build()
+        .unwrap();
+        let service = Service::new(Router::new().hoop(session_handler).get(index));
+        let request = |cookie: Option<&Cookie<'static>>| {
+            let mut req = TestClient::get("http://127.0.0.1:5800/");
+            if let Some(cookie) = cookie {
+                req = req.add_header(COOKIE, cookie.stripped().to_string(), true);
+            }
+            let mut req = req.build();
+            *req.remote_addr_mut() = "10.0.0.1:40000"
+                .parse::<std::net::SocketAddr>()
+                .unwrap()
+                .into();
+            req
+        };
+
+        let respone = service.handle(request(None)).await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+        let cookie = respone.cookies().get("salvo.session.id").unwrap().clone();
+
+        let respone = service.handle(request(None)).await;
+        assert_eq!(respone.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+
+        let respone = service.handle(request(Some(&cookie))).await;
+        assert_eq!(respone.status_code, Some(StatusCode::OK));
+    }
+}
@@ -0,0 +1,64 @@
+use salvo_core::Router;
+use salvo_oapi::security::{ApiKey, ApiKeyValue, SecurityRequirement, SecurityScheme};
+use salvo_oapi::RouterExt;
+
+use crate::{RequireSession, SessionHandler, SessionStore};
+
+/// Name under which the session cookie security scheme is expected in the OpenAPI components.
+pub const SESSION_SECURITY_SCHEME: &str = "session_cookie";
+
+impl<S: SessionStore> SessionHandler<S> {
+    /// Returns the OpenAPI security scheme describing the session cookie of this handler, to be
+    /// registered as [`SESSION_SECURITY_SCHEME`] with `OpenApi::add_security_scheme`.
+    pub fn oapi_security_scheme(&self) -> SecurityScheme {
+        SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new(self.options.cookie_name.clone())))
+    }
+}
+
+impl RequireSession {
+    /// Returns the OpenAPI security requirement of the routes guarded by this handler.
+    pub fn oapi_security_requirement(&self) -> SecurityRequirement {
+        SecurityRequirement::new(SESSION_SECURITY_SCHEME, Vec::<String>::new())
+    }
+}
+
+/// Extends [`Router`] to guard routes with [`RequireSession`].
+pub trait SessionRouterExt {
+    /// Adds `guard` as a hoop and marks the endpoints of the router and its descendants as
+    /// requiring the [`SESSION_SECURITY_SCHEME`] security scheme in the generated OpenAPI
+    /// document.
+    fn require_session(self, guard: RequireSession) -> Self;
+}
+
+impl SessionRouterExt for Router {
+    fn require_session(self, guard: RequireSession) -> Self {
+        let requirement = guard.oapi_security_requirement();
+        self.hoop(guard).oapi_security(requirement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStore;
+
+    #[test]
+    fn test_oapi_security() {
+        let handler = SessionHandler::builder(MemoryStore::new(), &[0; 64])
+            .cookie_name("sid")
+            .build()
+            .unwrap();
+        assert_eq!(
+            handler.oapi_security_scheme(),
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("sid")))
+        );
+
+        let guard = RequireSession::default();
+        assert_eq!(
+            guard.oapi_security_requirement(),
+            SecurityRequirement::new(SESSION_SECURITY_SCHEME, Vec::<String>::new())
+        );
+        let router = Router::new().require_session(guard);
+        assert_eq!(router.hoops().len(), 1);
+    }
+}
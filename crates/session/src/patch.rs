@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use async_session::serde_json::{self, Value};
+#[cfg(feature = "cookie-store")]
+use async_session::CookieStore;
+#[cfg(feature = "memory-store")]
+use async_session::MemoryStore;
+use async_session::{async_trait, Result, Session, SessionStore};
+
+/// Changes made to a session during a request, passed to [`PatchableStore::patch_session`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SessionPatch {
+    /// Keys that were added or changed, with their new serialized value.
+    pub changed: HashMap<String, String>,
+    /// Keys that were removed.
+    pub removed: Vec<String>,
+}
+
+impl SessionPatch {
+    /// Returns the changes between the data of a session when it was loaded, `before`, and
+    /// `session`.
+    pub(crate) fn diff(before: &HashMap<String, String>, session: &Session) -> Self {
+        let after = session_data(session);
+        let removed = before
+            .keys()
+            .filter(|key| !after.contains_key(*key))
+            .cloned()
+            .collect();
+        let changed = after
+            .into_iter()
+            .filter(|(key, value)| before.get(key) != Some(value))
+            .collect();
+        Self { changed, removed }
+    }
+
+    /// Whether the session data is unchanged.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Session stores that can update single fields of a stored session.
+///
+/// With [`HandlerBuilder::patch_writes`](crate::HandlerBuilder::patch_writes), the handler
+/// compares the session with its state when it was loaded and, if it is the same session, calls
+/// [`patch_session`](Self::patch_session) with the changed fields instead of rewriting the whole
+/// session. New and regenerated sessions are still written with
+/// [`store_session`](SessionStore::store_session).
+///
+/// The default implementation writes the whole session. Stores that keep sessions as hashes or
+/// rows, where fields can be updated separately, override it.
+///
+/// Support is best-effort: this crate has no Redis or SQL store, and none of its stores can
+/// update single fields, so the memory, cookie, sharded memory, memcached, encrypted and
+/// recording stores all use the default full write, and `CachingStore` passes the patch to the
+/// store it wraps. Only stores from other crates that override
+/// [`patch_session`](Self::patch_session) write less.
+#[async_trait]
+pub trait PatchableStore: SessionStore {
+    /// Applies `patch` to the stored `session`. The expiry of `session` must be stored too, as
+    /// it may have been extended even if the patch is empty.
+    async fn patch_session(&self, session: Session, patch: SessionPatch) -> Result<Option<String>> {
+        let _ = patch;
+        self.store_session(session).await
+    }
+
+    /// Stores the new expiry of `session`, whose data is unchanged since it was loaded.
+    ///
+    /// With [`HandlerBuilder::patch_writes`](crate::HandlerBuilder::patch_writes), the handler
+    /// calls this instead of [`patch_session`](Self::patch_session) when only the expiry of a
+    /// session changed, for example with
+    /// [`save_unchanged`](crate::HandlerBuilder::save_unchanged). The default implementation
+    /// writes the whole session. Stores that keep the expiry apart from the data, such as a
+    /// Redis key ttl or an `expires` column, override it to only update the expiry.
+    ///
+    /// Support is best-effort: this crate has no Redis or SQL store, and all of its stores use
+    /// the default full write, with `CachingStore` passing the call to the store it wraps. Only
+    /// stores from other crates that override it write less.
+    async fn renew_session(&self, session: Session) -> Result<Option<String>> {
+        self.store_session(session).await
+    }
+}
+
+#[cfg(feature = "memory-store")]
+impl PatchableStore for MemoryStore {}
+#[cfg(feature = "cookie-store")]
+impl PatchableStore for CookieStore {}
+
+/// Calls [`PatchableStore::patch_session`] on the store of a handler, which is not known to be
+/// patchable when the handler runs.
+pub(crate) type PatchFn<S> =
+    for<'a> fn(
+        &'a S,
+        Session,
+        SessionPatch,
+    ) -> Pin<Box<dyn Future<Output = Result<Option<String>>> + Send + 'a>>;
+
+pub(crate) fn patch_session<S: PatchableStore>(
+    store: &S,
+    session: Session,
+    patch: SessionPatch,
+) -> Pin<Box<dyn Future<Output = Result<Option<String>>> + Send + '_>> {
+    store.patch_session(session, patch)
+}
+
+/// Calls [`PatchableStore::renew_session`] on the store of a handler, see [`PatchFn`].
+pub(crate) type RenewFn<S> =
+    for<'a> fn(&'a S, Session) -> Pin<Box<dyn Future<Output = Result<Option<String>>> + Send + 'a>>;
+
+pub(crate) fn renew_session<S: PatchableStore>(
+    store: &S,
+    session: Session,
+) -> Pin<Box<dyn Future<Output = Result<Option<String>>> + Send + '_>> {
+    store.renew_session(session)
+}
+
+/// Returns the serialized data of the session by key.
+pub(crate) fn session_data(session: &Session) -> HashMap<String, String> {
+    // `Session` doesn't expose its data, but serializes it as a map.
+    match serde_json::to_value(session) {
+        Ok(Value::Object(mut fields)) => match fields.remove("data") {
+            Some(Value::Object(data)) => data
+                .into_iter()
+                .filter_map(|(key, value)| match value {
+                    Value::String(value) => Some((key, value)),
+                    _ => None,
+                })
+                .collect(),
+            _ => HashMap::new(),
+        },
+        _ => HashMap::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use salvo_core::http::header::{COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+    use crate::{SessionDepotExt, SessionHandler};
+
+    #[test]
+    fn test_session_patch_diff() {
+        let mut session = Session::new();
+        session.insert("kept", 1).unwrap();
+        session.insert("changed", 1).unwrap();
+        session.insert("removed", 1).unwrap();
+        let before = session_data(&session);
+
+        session.insert("changed", 2).unwrap();
+        session.insert("added", 3).unwrap();
+        session.remove("removed");
+        let patch = SessionPatch::diff(&before, &session);
+        assert_eq!(patch.removed, vec!["removed".to_owned()]);
+        assert_eq!(patch.changed.len(), 2);
+        assert_eq!(patch.changed["changed"], "2");
+        assert_eq!(patch.changed["added"], "3");
+
+        let patch = SessionPatch::diff(&session_data(&session), &session);
+        assert!(patch.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_patch_writes() {
+        #[derive(Clone, Debug)]
+        struct PatchingStore {
+            inner: MemoryStore,
+            patches: Arc<std::sync::Mutex<Vec<SessionPatch>>>,
+            renewals: Arc<std::sync::Mutex<usize>>,
+        }
+        #[async_trait]
+        impl SessionStore for PatchingStore {
+            async fn load_session(
+                &self,
+                cookie_value: String,
+            ) -> async_session::Result<Option<Session>> {
+                self.inner.load_session(cookie_value).await
+            }
+            async fn store_session(
+                &self,
+                session: Session,
+            ) -> async_session::Result<Option<String>> {
+                self.inner.store_session(session).await
+            }
+            async fn destroy_session(&self, session: Session) -> async_session::Result {
+                self.inner.destroy_session(session).await
+            }
+            async fn clear_store(&self) -> async_session::Result {
+                self.inner.clear_store().await
+            }
+        }
+        #[async_trait]
+        impl PatchableStore for PatchingStore {
+            async fn patch_session(
+                &self,
+                session: Session,
+                patch: SessionPatch,
+            ) -> async_session::Result<Option<String>> {
+                self.patches.lock().unwrap().push(patch);
+                self.inner.store_session(session).await
+            }
+            async fn renew_session(
+                &self,
+                session: Session,
+            ) -> async_session::Result<Option<String>> {
+                *self.renewals.lock().unwrap() += 1;
+                self.inner.store_session(session).await
+            }
+        }
+        #[handler]
+        async fn increment(depot: &mut Depot) {
+            let session = depot.session_mut().unwrap();
+            let visits = session.get::<u32>("visits").unwrap_or_default() + 1;
+            session.insert("visits", visits).unwrap();
+            session.remove("first");
+            if visits == 1 {
+                session.insert("first", true).unwrap();
+            }
+        }
+        #[handler]
+        async fn show() {}
+        let store = PatchingStore {
+            inner: MemoryStore::new(),
+            patches: Default::default(),
+            renewals: Default::default(),
+        };
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .save_unchanged(true)
+        .patch_writes()
+        .build()
+        .unwrap();
+        let service = Service::new(
+            Router::new()
+                .hoop(session_handler)
+                .post(increment)
+                .get(show),
+        );
+
+        let respone = TestClient::post("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        assert!(store.patches.lock().unwrap().is_empty());
+
+        TestClient::post("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie.clone(), true)
+            .send(&service)
+            .await;
+        let patches = store.patches.lock().unwrap().clone();
+        assert_eq!(patches.len(), 1);
+        assert_eq!(
+            patches[0].changed.keys().collect::<Vec<_>>(),
+            vec!["visits"]
+        );
+        assert_eq!(patches[0].removed, vec!["first".to_owned()]);
+
+        TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(store.patches.lock().unwrap().len(), 1);
+        assert_eq!(*store.renewals.lock().unwrap(), 1);
+    }
+}
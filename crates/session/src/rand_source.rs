@@ -0,0 +1,107 @@
+use std::sync::Mutex;
+
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+/// Source of the random bytes of new session cookie values, see
+/// [`HandlerBuilder::rand_source`](crate::HandlerBuilder::rand_source).
+///
+/// Implement it to draw entropy from a hardware or certified RNG. Any [`RngCore`] behind a
+/// [`Mutex`] is a `RandSource` too.
+pub trait RandSource: Send + Sync + 'static {
+    /// Fills `dest` with random bytes.
+    fn fill_bytes(&self, dest: &mut [u8]);
+}
+
+/// The random number generator of the operating system, the default [`RandSource`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OsRandSource;
+
+impl RandSource for OsRandSource {
+    #[inline]
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        OsRng.fill_bytes(dest);
+    }
+}
+
+impl<R> RandSource for Mutex<R>
+where
+    R: RngCore + Send + 'static,
+{
+    #[inline]
+    fn fill_bytes(&self, dest: &mut [u8]) {
+        self.lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .fill_bytes(dest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_session::base64;
+    use salvo_core::http::header::SET_COOKIE;
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+    use crate::{MemoryStore, SessionDepotExt, SessionHandler};
+
+    #[test]
+    fn test_rand_source() {
+        struct ZeroSource;
+        impl RandSource for ZeroSource {
+            fn fill_bytes(&self, dest: &mut [u8]) {
+                dest.fill(0);
+            }
+        }
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .rand_source(ZeroSource)
+        .build()
+        .unwrap();
+        let cookie_value = handler.new_session().into_cookie_value().unwrap();
+        assert_eq!(cookie_value, base64::encode([0u8; 64]));
+    }
+
+    #[tokio::test]
+    async fn test_with_rng() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        let build_service = || {
+            let session_handler = SessionHandler::builder(
+                MemoryStore::new(),
+                b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+            )
+            .with_rng(StdRng::seed_from_u64(7))
+            .build()
+            .unwrap();
+            Service::new(Router::new().hoop(session_handler).post(login))
+        };
+
+        let first = TestClient::post("http://127.0.0.1:5800/")
+            .send(&build_service())
+            .await;
+        let second = TestClient::post("http://127.0.0.1:5800/")
+            .send(&build_service())
+            .await;
+        let cookie = first.headers().get(SET_COOKIE).unwrap();
+        assert_eq!(Some(cookie), second.headers().get(SET_COOKIE));
+
+        let service = build_service();
+        let other = TestClient::post("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert_eq!(Some(cookie), other.headers().get(SET_COOKIE));
+        let other = TestClient::post("http://127.0.0.1:5800/")
+            .send(&service)
+            .await;
+        assert_ne!(Some(cookie), other.headers().get(SET_COOKIE));
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+
+use async_session::{async_trait, Result, Session, SessionStore};
+
+use crate::{BatchSessionStore, PatchableStore};
+
+/// An operation recorded by a [`RecordingStore`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StoreOperation {
+    /// A session was loaded from its cookie value.
+    Load,
+    /// The session with this id was stored.
+    Store(String),
+    /// The session with this id was destroyed.
+    Destroy(String),
+    /// The store was cleared.
+    Clear,
+}
+
+/// Wraps a [`SessionStore`] and records every call made to it, to test what the session
+/// handler does with the store.
+///
+/// Clones share the same log, so a clone can be given to the handler and the original kept to
+/// assert, for example, that a read-only request didn't write to the store.
+#[derive(Clone, Debug)]
+pub struct RecordingStore<S> {
+    inner: S,
+    operations: Arc<Mutex<Vec<StoreOperation>>>,
+}
+
+impl<S> RecordingStore<S>
+where
+    S: SessionStore,
+{
+    /// Create a new `RecordingStore` recording the calls made to `inner`.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            operations: Arc::default(),
+        }
+    }
+
+    /// Returns a reference to the inner store.
+    #[inline]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns the operations recorded so far, oldest first.
+    pub fn operations(&self) -> Vec<StoreOperation> {
+        self.log().clone()
+    }
+
+    /// Returns the operations recorded so far, oldest first, and clears the log.
+    pub fn drain(&self) -> Vec<StoreOperation> {
+        std::mem::take(&mut *self.log())
+    }
+
+    /// Returns how many sessions were loaded.
+    pub fn loads(&self) -> usize {
+        self.count(|operation| matches!(operation, StoreOperation::Load))
+    }
+
+    /// Returns how many sessions were stored.
+    pub fn stores(&self) -> usize {
+        self.count(|operation| matches!(operation, StoreOperation::Store(_)))
+    }
+
+    /// Returns how many sessions were destroyed.
+    pub fn destroys(&self) -> usize {
+        self.count(|operation| matches!(operation, StoreOperation::Destroy(_)))
+    }
+
+    fn count(&self, predicate: impl Fn(&StoreOperation) -> bool) -> usize {
+        self.log()
+            .iter()
+            .filter(|operation| predicate(operation))
+            .count()
+    }
+
+    fn log(&self) -> std::sync::MutexGuard<'_, Vec<StoreOperation>> {
+        self.operations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn record(&self, operation: StoreOperation) {
+        self.log().push(operation);
+    }
+}
+
+#[async_trait]
+impl<S> SessionStore for RecordingStore<S>
+where
+    S: SessionStore,
+{
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        self.record(StoreOperation::Load);
+        self.inner.load_session(cookie_value).await
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        self.record(StoreOperation::Store(session.id().to_owned()));
+        self.inner.store_session(session).await
+    }
+
+    async fn destroy_session(&self, session: Session) -> Result {
+        self.record(StoreOperation::Destroy(session.id().to_owned()));
+        self.inner.destroy_session(session).await
+    }
+
+    async fn clear_store(&self) -> Result {
+        self.record(StoreOperation::Clear);
+        self.inner.clear_store().await
+    }
+}
+
+impl<S: SessionStore> BatchSessionStore for RecordingStore<S> {}
+impl<S: SessionStore> PatchableStore for RecordingStore<S> {}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::{COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::TestClient;
+
+    use super::*;
+    use crate::{MemoryStore, SessionDepotExt, SessionHandler};
+
+    #[tokio::test]
+    async fn test_recording_store() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn read(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let store = RecordingStore::new(MemoryStore::new());
+        let session_handler = SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .save_unchanged(false)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .get(read);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        assert_eq!(store.stores(), 1);
+        store.drain();
+
+        TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(store.operations(), vec![StoreOperation::Load]);
+        assert_eq!(store.stores(), 0);
+        assert_eq!(store.destroys(), 0);
+    }
+}
@@ -0,0 +1,269 @@
+use salvo_core::http::StatusCode;
+use salvo_core::writing::Redirect;
+use salvo_core::{async_trait, Depot, FlowCtrl, Handler, Request, Response};
+
+use crate::{SessionDepotExt, RETURN_TO_KEY};
+
+/// Returns `next` if it is a path on the same origin, which is safe to redirect to.
+///
+/// Absolute urls, scheme-relative urls like `//evil.com` and paths containing backslashes or
+/// control characters are rejected to prevent open redirects.
+pub fn local_return_to(next: &str) -> Option<&str> {
+    let is_local = next.starts_with('/')
+        && !next.starts_with("//")
+        && !next.contains('\\')
+        && !next.chars().any(|c| c.is_control());
+    is_local.then_some(next)
+}
+
+/// Middleware that rejects requests without an authenticated session.
+///
+/// A session is considered authenticated when it contains the configured key, `user_id` by
+/// default. Otherwise the request is answered with `401 Unauthorized`, or redirected when
+/// [`redirect_to`](Self::redirect_to) or [`login_redirect`](Self::login_redirect) is set, and
+/// the rest of the handlers are skipped.
+///
+/// It must be added after [`SessionHandler`](crate::SessionHandler).
+///
+/// # Example
+///
+/// ```
+/// use salvo_core::prelude::*;
+/// use salvo_session::{MemoryStore, RequireSession, SessionHandler};
+///
+/// #[handler]
+/// async fn profile() -> &'static str {
+///     "profile"
+/// }
+///
+/// let session_handler = SessionHandler::builder(
+///     MemoryStore::new(),
+///     b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+/// )
+/// .build()
+/// .unwrap();
+/// let router = Router::new().hoop(session_handler).push(
+///     Router::with_path("profile")
+///         .hoop(RequireSession::new("user_id"))
+///         .get(profile),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct RequireSession {
+    key: String,
+    status_code: StatusCode,
+    redirect_to: Option<String>,
+    return_to: bool,
+}
+impl Default for RequireSession {
+    fn default() -> Self {
+        Self::new("user_id")
+    }
+}
+
+impl RequireSession {
+    /// Create a new `RequireSession` that checks for `key` in the session.
+    #[inline]
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            status_code: StatusCode::UNAUTHORIZED,
+            redirect_to: None,
+            return_to: false,
+        }
+    }
+
+    /// Sets the session key that marks a session as authenticated.
+    #[inline]
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Sets the status code used to reject requests. Defaults to `401 Unauthorized`.
+    #[inline]
+    pub fn status_code(mut self, status_code: StatusCode) -> Self {
+        self.status_code = status_code;
+        self
+    }
+
+    /// Redirects rejected requests to `uri` (for example a login page) with
+    /// `303 See Other` instead of responding with the status code.
+    #[inline]
+    pub fn redirect_to(mut self, uri: impl Into<String>) -> Self {
+        self.redirect_to = Some(uri.into());
+        self
+    }
+
+    /// Redirects rejected requests to the login page at `path`, preserving the requested url.
+    ///
+    /// The original path and query are appended to the login url as a `next` parameter and
+    /// remembered in the session, so the login handler can send the user back with
+    /// [`SessionDepotExt::login_return_to`] once authenticated.
+    #[inline]
+    pub fn login_redirect(mut self, path: impl Into<String>) -> Self {
+        self.redirect_to = Some(path.into());
+        self.return_to = true;
+        self
+    }
+
+    fn is_authenticated(&self, depot: &Depot) -> bool {
+        depot
+            .session()
+            .map(|session| session.get_raw(&self.key).is_some())
+            .unwrap_or(false)
+    }
+}
+
+#[async_trait]
+impl Handler for RequireSession {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        if self.is_authenticated(depot) {
+            return;
+        }
+        match &self.redirect_to {
+            Some(uri) if self.return_to => {
+                let next = req
+                    .uri()
+                    .path_and_query()
+                    .map(|p| p.as_str())
+                    .unwrap_or("/");
+                if let Some(session) = depot.session_mut() {
+                    if let Err(e) = session.insert(RETURN_TO_KEY, next) {
+                        tracing::error!(error = ?e, "save return to url failed");
+                    }
+                }
+                let separator = if uri.contains('?') { '&' } else { '?' };
+                let query = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("next", next)
+                    .finish();
+                res.render(Redirect::other(format!("{uri}{separator}{query}")));
+            }
+            Some(uri) => res.render(Redirect::other(uri)),
+            None => {
+                res.status_code(self.status_code);
+            }
+        }
+        ctrl.skip_rest();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::{COOKIE, LOCATION, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+    use crate::{MemoryStore, SessionHandler};
+
+    #[handler]
+    async fn login(depot: &mut Depot) {
+        depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+    }
+    #[handler]
+    async fn profile() -> &'static str {
+        "profile"
+    }
+
+    fn service(guard: RequireSession) -> Service {
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").get(login))
+            .push(Router::with_path("profile").hoop(guard).get(profile));
+        Service::new(router)
+    }
+
+    #[tokio::test]
+    async fn test_require_session() {
+        let service = service(RequireSession::new("user_id"));
+
+        let respone = TestClient::get("http://127.0.0.1:5800/profile")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::UNAUTHORIZED));
+
+        let respone = TestClient::get("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/profile")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "profile");
+    }
+
+    #[test]
+    fn test_local_return_to() {
+        assert_eq!(local_return_to("/profile?tab=1"), Some("/profile?tab=1"));
+        assert_eq!(local_return_to("https://evil.com"), None);
+        assert_eq!(local_return_to("//evil.com"), None);
+        assert_eq!(local_return_to("/\\evil.com"), None);
+        assert_eq!(local_return_to("profile"), None);
+    }
+
+    #[tokio::test]
+    async fn test_require_session_login_redirect() {
+        #[handler]
+        async fn do_login(depot: &mut Depot, res: &mut Response) {
+            let next = depot.login_return_to().unwrap_or_else(|| "/".into());
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+            res.render(Redirect::other(next));
+        }
+
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(do_login))
+            .push(
+                Router::with_path("profile")
+                    .hoop(RequireSession::new("user_id").login_redirect("/login"))
+                    .get(profile),
+            );
+        let service = Service::new(router);
+
+        let respone = TestClient::get("http://127.0.0.1:5800/profile?tab=1")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::SEE_OTHER));
+        assert_eq!(
+            respone.headers().get(LOCATION).unwrap(),
+            "/login?next=%2Fprofile%3Ftab%3D1"
+        );
+        let cookie = respone.headers().get(SET_COOKIE).unwrap();
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.headers().get(LOCATION).unwrap(), "/profile?tab=1");
+    }
+
+    #[tokio::test]
+    async fn test_require_session_redirect() {
+        let service = service(RequireSession::new("user_id").redirect_to("/login"));
+        let respone = TestClient::get("http://127.0.0.1:5800/profile")
+            .send(&service)
+            .await;
+        assert_eq!(respone.status_code, Some(StatusCode::SEE_OTHER));
+        assert_eq!(respone.headers().get(LOCATION).unwrap(), "/login");
+    }
+}
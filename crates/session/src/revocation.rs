@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_session::chrono::{DateTime, Utc};
+use salvo_core::{async_trait, Error};
+
+/// Ids of destroyed sessions that must no longer be accepted, set with
+/// [`HandlerBuilder::revocation_list`](crate::HandlerBuilder::revocation_list).
+///
+/// With [`CookieStore`](crate::CookieStore), destroying a session only removes the cookie from
+/// the client: a copy of the cookie stays valid until it expires. A revocation list remembers
+/// the ids of destroyed sessions until their expiry, so such copies are refused. It works with
+/// other stores too, but they already forget destroyed sessions.
+///
+/// The crate provides [`MemoryRevocationList`], which is not shared between processes.
+#[async_trait]
+pub trait RevocationList: Send + Sync + 'static {
+    /// Revokes the session `id` until `until`, or forever if `None`.
+    async fn revoke(&self, id: &str, until: Option<DateTime<Utc>>) -> Result<(), Error>;
+    /// Whether the session `id` is revoked.
+    async fn is_revoked(&self, id: &str) -> Result<bool, Error>;
+}
+
+/// In-memory [`RevocationList`]. Entries are dropped once they expire.
+#[derive(Debug, Default)]
+pub struct MemoryRevocationList {
+    revoked: Mutex<HashMap<String, Option<DateTime<Utc>>>>,
+}
+
+impl MemoryRevocationList {
+    /// Create a new `MemoryRevocationList`.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Returns the number of revoked sessions, including expired entries that have not been
+    /// dropped yet.
+    pub fn len(&self) -> usize {
+        self.revoked.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+
+    /// Whether no session is revoked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl RevocationList for MemoryRevocationList {
+    async fn revoke(&self, id: &str, until: Option<DateTime<Utc>>) -> Result<(), Error> {
+        let mut revoked = self.revoked.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Utc::now();
+        revoked.retain(|_, until| !matches!(until, Some(until) if *until <= now));
+        revoked.insert(id.to_owned(), until);
+        Ok(())
+    }
+
+    async fn is_revoked(&self, id: &str) -> Result<bool, Error> {
+        let revoked = self.revoked.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(match revoked.get(id) {
+            Some(Some(until)) => *until > Utc::now(),
+            Some(None) => true,
+            None => false,
+        })
+    }
+}
+
+/// Shared cutoff time before which sessions are refused, set with
+/// [`HandlerBuilder::not_before`](crate::HandlerBuilder::not_before).
+///
+/// Bumping it logs every user out at once, for example after a breach, without going through the
+/// store: sessions whose [`CREATED_KEY`](crate::CREATED_KEY) is before the cutoff, or that have
+/// none, are treated as invalid and a new session is started. Clones share the same cutoff, so
+/// keep one to change it while the handler runs. The cutoff is not persisted, nor shared between
+/// processes.
+#[derive(Clone, Debug)]
+pub struct NotBefore {
+    // Unix timestamp in seconds, `i64::MIN` when unset.
+    cutoff: Arc<AtomicI64>,
+}
+
+impl Default for NotBefore {
+    fn default() -> Self {
+        Self {
+            cutoff: Arc::new(AtomicI64::new(i64::MIN)),
+        }
+    }
+}
+
+impl NotBefore {
+    /// Create a new `NotBefore` without a cutoff, which refuses no session.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Refuses the sessions created before `time`.
+    ///
+    /// Creation times are stored in whole seconds, so `time` is rounded up to the next second:
+    /// sessions created within the same second as `time` are refused too.
+    pub fn set(&self, time: SystemTime) {
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let rounded_up = since_epoch.as_secs() + u64::from(since_epoch.subsec_nanos() > 0);
+        let cutoff = i64::try_from(rounded_up).unwrap_or(i64::MAX);
+        self.cutoff.store(cutoff, Ordering::Relaxed);
+    }
+
+    /// Refuses all the sessions created until now.
+    #[inline]
+    pub fn set_now(&self) {
+        self.set(SystemTime::now());
+    }
+
+    /// Removes the cutoff, so no session is refused anymore.
+    #[inline]
+    pub fn clear(&self) {
+        self.cutoff.store(i64::MIN, Ordering::Relaxed);
+    }
+
+    /// Returns the cutoff time, if set.
+    pub fn get(&self) -> Option<SystemTime> {
+        match self.cutoff.load(Ordering::Relaxed) {
+            i64::MIN => None,
+            cutoff => Some(UNIX_EPOCH + Duration::from_secs(u64::try_from(cutoff).unwrap_or(0))),
+        }
+    }
+
+    /// Whether a session created at the unix timestamp `created` is refused.
+    pub(crate) fn refuses(&self, created: Option<i64>) -> bool {
+        match self.cutoff.load(Ordering::Relaxed) {
+            i64::MIN => false,
+            cutoff => !matches!(created, Some(created) if created >= cutoff),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_session::chrono::Duration;
+    use salvo_core::http::header::{COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+    use salvo_extra::test_util::SessionTestHarness;
+
+    use super::*;
+    use crate::{CookieStore, MemoryStore, SessionDepotExt, SessionHandler};
+
+    #[tokio::test]
+    async fn test_memory_revocation_list() {
+        let list = MemoryRevocationList::new();
+        list.revoke("expired", Some(Utc::now() - Duration::seconds(1)))
+            .await
+            .unwrap();
+        list.revoke("forever", None).await.unwrap();
+        list.revoke("later", Some(Utc::now() + Duration::hours(1)))
+            .await
+            .unwrap();
+        assert!(!list.is_revoked("expired").await.unwrap());
+        assert!(list.is_revoked("forever").await.unwrap());
+        assert!(list.is_revoked("later").await.unwrap());
+        assert!(!list.is_revoked("other").await.unwrap());
+        assert_eq!(list.len(), 2);
+    }
+
+    #[test]
+    fn test_not_before() {
+        let not_before = NotBefore::new();
+        assert!(!not_before.refuses(None));
+        assert_eq!(not_before.get(), None);
+
+        not_before.set(UNIX_EPOCH + std::time::Duration::from_millis(100_500));
+        assert_eq!(
+            not_before.get(),
+            Some(UNIX_EPOCH + std::time::Duration::from_secs(101))
+        );
+        assert!(not_before.clone().refuses(Some(100)));
+        assert!(not_before.refuses(None));
+        assert!(!not_before.refuses(Some(101)));
+
+        not_before.clear();
+        assert!(!not_before.refuses(Some(100)));
+    }
+
+    #[tokio::test]
+    async fn test_handler_not_before() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let not_before = NotBefore::new();
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .not_before(not_before.clone())
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .get(current_user);
+        let mut harness = SessionTestHarness::new(router);
+
+        harness
+            .send(TestClient::post("http://127.0.0.1:5800/login"))
+            .await;
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "Some(1)");
+
+        not_before.set_now();
+        let mut res = harness
+            .send(TestClient::get("http://127.0.0.1:5800/"))
+            .await;
+        assert_eq!(res.take_string().await.unwrap(), "None");
+    }
+
+    #[tokio::test]
+    async fn test_revocation_list() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn logout(depot: &mut Depot) {
+            depot.session_mut().unwrap().destroy();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let session_handler = SessionHandler::builder(
+            CookieStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .revocation_list(MemoryRevocationList::new())
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .push(Router::with_path("logout").post(logout))
+            .get(current_user);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let cookie = respone.headers().get(SET_COOKIE).unwrap().clone();
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie.clone(), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+
+        TestClient::post("http://127.0.0.1:5800/logout")
+            .add_header(COOKIE, cookie.clone(), true)
+            .send(&service)
+            .await;
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, cookie, true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+    }
+}
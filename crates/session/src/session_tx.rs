@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use async_session::serde::de::DeserializeOwned;
+use async_session::serde::Serialize;
+use async_session::serde_json;
+use async_session::Session;
+use salvo_core::Depot;
+
+use crate::SessionDepotExt;
+
+/// Key for the staged changes in depot.
+pub(crate) const PENDING_KEY: &str = "::salvo::session::pending";
+
+/// Changes staged with a [`SessionTx`], by key. `None` removes the key.
+#[derive(Debug, Default)]
+pub(crate) struct PendingChanges(pub(crate) HashMap<String, Option<String>>);
+
+impl PendingChanges {
+    /// Applies the changes to `session`.
+    pub(crate) fn apply(self, session: &mut Session) {
+        for (key, value) in self.0 {
+            match value {
+                Some(value) => session.insert_raw(&key, value),
+                None => session.remove(&key),
+            }
+        }
+    }
+}
+
+/// Transactional access to the session, returned by [`SessionDepotExt::session_tx`].
+///
+/// Changes made through a `SessionTx` are staged and only applied to the session when the
+/// request ends with a successful response, that is a status below `400 Bad Request` unless
+/// configured otherwise with [`HandlerBuilder::tx_rollback_status`]. For error responses they
+/// are rolled back, so a handler failing midway doesn't persist half-updated session state.
+/// Staged changes are visible through [`get`](Self::get) right away.
+#[derive(Debug)]
+pub struct SessionTx<'a> {
+    depot: &'a mut Depot,
+}
+
+impl<'a> SessionTx<'a> {
+    pub(crate) fn new(depot: &'a mut Depot) -> Self {
+        Self { depot }
+    }
+
+    fn pending(&mut self) -> &mut PendingChanges {
+        if self.depot.get::<PendingChanges>(PENDING_KEY).is_err() {
+            self.depot.insert(PENDING_KEY, PendingChanges::default());
+        }
+        self.depot
+            .get_mut::<PendingChanges>(PENDING_KEY)
+            .expect("pending changes should exist in depot")
+    }
+
+    /// Stages inserting `value` under `key`.
+    pub fn insert(&mut self, key: &str, value: impl Serialize) -> Result<(), serde_json::Error> {
+        let value = serde_json::to_string(&value)?;
+        self.pending().0.insert(key.to_owned(), Some(value));
+        Ok(())
+    }
+
+    /// Stages removing `key`.
+    pub fn remove(&mut self, key: &str) {
+        self.pending().0.insert(key.to_owned(), None);
+    }
+
+    /// Get the value of `key`, including staged changes.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let staged = self
+            .depot
+            .get::<PendingChanges>(PENDING_KEY)
+            .ok()
+            .and_then(|pending| pending.0.get(key));
+        match staged {
+            Some(value) => serde_json::from_str(value.as_deref()?).ok(),
+            None => self.depot.session()?.get(key),
+        }
+    }
+
+    /// Discards all staged changes.
+    pub fn rollback(&mut self) {
+        self.depot.remove::<PendingChanges>(PENDING_KEY).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo_core::http::header::{COOKIE, SET_COOKIE};
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use super::*;
+    use crate::{MemoryStore, SessionHandler};
+
+    #[test]
+    fn test_session_tx() {
+        let mut depot = Depot::new();
+        let mut session = Session::new();
+        session.insert("a", 1).unwrap();
+        session.insert("b", 2).unwrap();
+        depot.set_session(session);
+
+        let mut tx = depot.session_tx();
+        tx.insert("a", 10).unwrap();
+        tx.remove("b");
+        assert_eq!(tx.get::<u32>("a"), Some(10));
+        assert_eq!(tx.get::<u32>("b"), None);
+        assert_eq!(depot.session().unwrap().get::<u32>("a"), Some(1));
+
+        let pending = depot.remove::<PendingChanges>(PENDING_KEY).unwrap();
+        let mut session = depot.take_session().unwrap();
+        pending.apply(&mut session);
+        assert_eq!(session.get::<u32>("a"), Some(10));
+        assert_eq!(session.get::<u32>("b"), None);
+    }
+
+    #[tokio::test]
+    async fn test_session_tx_rollback() {
+        #[handler]
+        async fn update(req: &mut Request, depot: &mut Depot, res: &mut Response) {
+            depot.session_tx().insert("step", 1).unwrap();
+            if req.query::<bool>("fail").unwrap_or_default() {
+                res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+        #[handler]
+        async fn step(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("step"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("update").post(update))
+            .push(Router::with_path("step").get(step));
+        let service = Service::new(router);
+
+        for (fail, expected) in [(true, "None"), (false, "Some(1)")] {
+            let respone = TestClient::post(format!("http://127.0.0.1:5800/update?fail={fail}"))
+                .send(&service)
+                .await;
+            let cookie = respone.headers().get(SET_COOKIE).unwrap();
+            let mut respone = TestClient::get("http://127.0.0.1:5800/step")
+                .add_header(COOKIE, cookie, true)
+                .send(&service)
+                .await;
+            assert_eq!(respone.take_string().await.unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_session::chrono::Utc;
+use async_session::{async_trait, Result, Session, SessionStore};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+use crate::{
+    detach, BatchSessionStore, IdleSessionStore, PatchableStore, SessionError, StoreInfo,
+    StoreTelemetry, UserSessionStore, LAST_SEEN_KEY, USER_ID_KEY, VERSION_KEY,
+};
+
+/// In-memory session store backed by a sharded [`DashMap`].
+///
+/// It is a drop-in replacement for [`MemoryStore`](crate::MemoryStore): sessions live in process
+/// memory and are lost on restart, but concurrent requests only contend on the shard that holds
+/// their session instead of a single store-wide lock. Expired sessions are evicted when they are
+/// accessed, and [`cleanup`](Self::cleanup) can be called periodically to drop the rest.
+///
+/// Writes are conditional on the session version when
+/// [`track_version`](crate::HandlerBuilder::track_version) is enabled: storing a session whose
+/// version is not newer than the stored one fails with [`SessionError::VersionConflict`]. Loaded
+/// sessions don't share their data with the stored ones, so the changes of a refused write are
+/// discarded.
+#[derive(Default, Debug, Clone)]
+pub struct ShardedMemoryStore {
+    inner: Arc<DashMap<String, StoredSession>>,
+}
+
+#[derive(Debug)]
+struct StoredSession {
+    session: Session,
+    // Kept apart from the session data, which is shared with loaded copies.
+    version: Option<u64>,
+}
+
+impl ShardedMemoryStore {
+    /// Create a new `ShardedMemoryStore`.
+    #[inline]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a new `ShardedMemoryStore` with the given shard amount, which must be a power of two.
+    #[inline]
+    pub fn with_shard_amount(shard_amount: usize) -> Self {
+        Self {
+            inner: Arc::new(DashMap::with_shard_amount(shard_amount)),
+        }
+    }
+
+    /// Removes all expired sessions from the store.
+    pub async fn cleanup(&self) -> Result {
+        self.inner.retain(|_, stored| !stored.session.is_expired());
+        Ok(())
+    }
+
+    /// Returns the number of sessions in the store, including expired ones that have not been
+    /// evicted yet.
+    pub async fn count(&self) -> usize {
+        self.inner.len()
+    }
+}
+
+#[async_trait]
+impl SessionStore for ShardedMemoryStore {
+    async fn load_session(&self, cookie_value: String) -> Result<Option<Session>> {
+        let id = Session::id_from_cookie_value(&cookie_value)?;
+        tracing::trace!(id, "loading session");
+        // Loaded sessions are detached so that changes are only kept once they are stored,
+        // which a version conflict refuses.
+        let session = self
+            .inner
+            .get(&id)
+            .and_then(|stored| detach(&stored.session));
+        match session {
+            Some(session) if session.is_expired() => {
+                self.inner
+                    .remove_if(&id, |_, stored| stored.session.is_expired());
+                Ok(None)
+            }
+            session => Ok(session),
+        }
+    }
+
+    async fn store_session(&self, session: Session) -> Result<Option<String>> {
+        tracing::trace!(id = session.id(), "storing session");
+        let version = session.get::<u64>(VERSION_KEY);
+        let stored = StoredSession {
+            session: session.clone(),
+            version,
+        };
+        match self.inner.entry(session.id().to_owned()) {
+            Entry::Occupied(mut entry) => {
+                if let (Some(version), Some(current)) = (version, entry.get().version) {
+                    if version <= current {
+                        return Err(SessionError::VersionConflict.into());
+                    }
+                }
+                entry.insert(stored);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(stored);
+            }
+        }
+        session.reset_data_changed();
+        Ok(session.into_cookie_value())
+    }
+
+    async fn destroy_session(&self, session: Session) -> Result {
+        tracing::trace!(id = session.id(), "destroying session");
+        self.inner.remove(session.id());
+        Ok(())
+    }
+
+    async fn clear_store(&self) -> Result {
+        self.inner.clear();
+        Ok(())
+    }
+}
+
+impl BatchSessionStore for ShardedMemoryStore {}
+impl PatchableStore for ShardedMemoryStore {}
+
+#[async_trait]
+impl UserSessionStore for ShardedMemoryStore {
+    async fn destroy_user_sessions(&self, user_id: &str, keep_id: Option<&str>) -> Result<usize> {
+        let mut destroyed = 0;
+        self.inner.retain(|id, stored| {
+            let keep = Some(id.as_str()) == keep_id
+                || stored.session.get::<String>(USER_ID_KEY).as_deref() != Some(user_id);
+            if !keep {
+                destroyed += 1;
+            }
+            keep
+        });
+        tracing::trace!(user_id, destroyed, "destroyed user sessions");
+        Ok(destroyed)
+    }
+}
+
+#[async_trait]
+impl IdleSessionStore for ShardedMemoryStore {
+    async fn cleanup_idle(&self, idle: Duration) -> Result<usize> {
+        let idle = i64::try_from(idle.as_secs()).unwrap_or(i64::MAX);
+        let now = Utc::now().timestamp();
+        let mut destroyed = 0;
+        self.inner.retain(|_, stored| {
+            let keep = !matches!(
+                stored.session.get::<i64>(LAST_SEEN_KEY),
+                Some(last_seen) if now.saturating_sub(last_seen) > idle
+            );
+            if !keep {
+                destroyed += 1;
+            }
+            keep
+        });
+        tracing::trace!(destroyed, "destroyed idle sessions");
+        Ok(destroyed)
+    }
+}
+
+impl StoreTelemetry for ShardedMemoryStore {
+    fn store_telemetry(&self) -> StoreInfo {
+        StoreInfo::new("memory")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sharded_memory_store() {
+        let store = ShardedMemoryStore::new();
+        let mut session = Session::new();
+        session.insert("key", "value").unwrap();
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+
+        let session = store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.get::<String>("key").unwrap(), "value");
+        assert_eq!(store.count().await, 1);
+
+        store.destroy_session(session).await.unwrap();
+        assert!(store.load_session(cookie_value).await.unwrap().is_none());
+        assert_eq!(store.count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_sharded_memory_store_version_conflict() {
+        let store = ShardedMemoryStore::new();
+        let cookie_value = store.store_session(Session::new()).await.unwrap().unwrap();
+
+        let mut first = store
+            .load_session(cookie_value.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let mut second = store.load_session(cookie_value).await.unwrap().unwrap();
+        second.insert(VERSION_KEY, 1).unwrap();
+        store.store_session(second).await.unwrap();
+
+        first.insert(VERSION_KEY, 1).unwrap();
+        let err = store.store_session(first).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<SessionError>(),
+            Some(&SessionError::VersionConflict)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_version_conflict_retry() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        use salvo_core::http::StatusCode;
+        use salvo_core::prelude::*;
+        use salvo_core::test::{ResponseExt, TestClient};
+
+        use salvo_extra::test_util::SessionTestHarness;
+
+        use crate::SessionDepotExt;
+
+        struct Conflict;
+        #[async_trait]
+        impl Handler for Conflict {
+            async fn handle(
+                &self,
+                req: &mut Request,
+                depot: &mut Depot,
+                res: &mut Response,
+                ctrl: &mut FlowCtrl,
+            ) {
+                ctrl.call_next(req, depot, res).await;
+                if depot.session_error() == Some(&SessionError::VersionConflict) {
+                    res.status_code(StatusCode::CONFLICT);
+                }
+            }
+        }
+
+        struct Increment {
+            store: ShardedMemoryStore,
+            // Writes the session behind the request's back once, like a concurrent request.
+            race: Arc<AtomicBool>,
+        }
+        #[async_trait]
+        impl Handler for Increment {
+            async fn handle(
+                &self,
+                _req: &mut Request,
+                depot: &mut Depot,
+                res: &mut Response,
+                _ctrl: &mut FlowCtrl,
+            ) {
+                let session = depot.session_mut().unwrap();
+                if self.race.swap(false, Ordering::SeqCst) {
+                    let mut other = detach(session).unwrap();
+                    let version = other.get::<u64>(VERSION_KEY).unwrap_or_default();
+                    other.insert(VERSION_KEY, version + 1).unwrap();
+                    self.store.store_session(other).await.unwrap();
+                }
+                let count = session.get::<u32>("count").unwrap_or_default() + 1;
+                session.insert("count", count).unwrap();
+                res.render(count.to_string());
+            }
+        }
+
+        let store = ShardedMemoryStore::new();
+        let session_handler = crate::SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .track_version(true)
+        .build()
+        .unwrap();
+        let increment = Increment {
+            store,
+            race: Arc::new(AtomicBool::new(false)),
+        };
+        let race = increment.race.clone();
+        let router = Router::with_hoop(Conflict)
+            .hoop(session_handler)
+            .push(Router::with_path("count").post(increment));
+        let mut harness = SessionTestHarness::new(router);
+        let url = "http://127.0.0.1:5800/count";
+
+        let mut res = harness.send(TestClient::post(url)).await;
+        assert_eq!(res.take_string().await.unwrap(), "1");
+
+        race.store(true, Ordering::SeqCst);
+        let res = harness.send(TestClient::post(url)).await;
+        assert_eq!(res.status_code, Some(StatusCode::CONFLICT));
+
+        let mut res = harness.send(TestClient::post(url)).await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        assert_eq!(res.take_string().await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_destroy_other_sessions() {
+        let store = ShardedMemoryStore::new();
+        let handler = crate::SessionHandler::builder(
+            store.clone(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let mut signed_values = vec![];
+        for user_id in ["alice", "alice", "alice", "bob"] {
+            let mut session = Session::new();
+            session.insert(USER_ID_KEY, user_id).unwrap();
+            signed_values.push(handler.save(session).await.unwrap().unwrap());
+        }
+
+        let destroyed = handler
+            .destroy_other_sessions("alice", &signed_values[0])
+            .await
+            .unwrap();
+        assert_eq!(destroyed, 2);
+        assert!(handler
+            .load_by_cookie_value(&signed_values[0])
+            .await
+            .is_some());
+        assert!(handler
+            .load_by_cookie_value(&signed_values[1])
+            .await
+            .is_none());
+        assert!(handler
+            .load_by_cookie_value(&signed_values[3])
+            .await
+            .is_some());
+        assert!(handler
+            .destroy_other_sessions("bob", "invalid")
+            .await
+            .is_err());
+        assert_eq!(store.count().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sharded_memory_store_evicts_expired() {
+        let store = ShardedMemoryStore::new();
+        let mut session = Session::new();
+        session.expire_in(Duration::from_millis(10));
+        let cookie_value = store.store_session(session).await.unwrap().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(store.load_session(cookie_value).await.unwrap().is_none());
+        assert_eq!(store.count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_idle() {
+        let store = ShardedMemoryStore::new();
+        for last_seen in [
+            Some(Utc::now().timestamp() - 31 * 24 * 60 * 60),
+            Some(Utc::now().timestamp()),
+            None,
+        ] {
+            let mut session = Session::new();
+            if let Some(last_seen) = last_seen {
+                session.insert(LAST_SEEN_KEY, last_seen).unwrap();
+            }
+            store.store_session(session).await.unwrap();
+        }
+
+        let destroyed = store
+            .cleanup_idle(Duration::from_secs(30 * 24 * 60 * 60))
+            .await
+            .unwrap();
+        assert_eq!(destroyed, 1);
+        assert_eq!(store.count().await, 2);
+    }
+}
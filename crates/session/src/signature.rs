@@ -0,0 +1,246 @@
+use std::iter;
+use std::sync::Arc;
+
+use async_session::hmac::Mac;
+use cookie::Cookie;
+use salvo_core::Error;
+
+use crate::cookie_encoding::DIGEST_LEN;
+use crate::key_provider::KeySet;
+use crate::{SessionHandler, SessionStore};
+
+/// Outcome of the verification of a signed session value.
+pub(crate) struct SignatureCheck {
+    value: String,
+    pub(crate) well_formed: bool,
+    /// Index of the key that verified the signature, `0` being the current key and the
+    /// following ones the fallback keys.
+    pub(crate) key: Option<usize>,
+}
+
+impl SignatureCheck {
+    pub(crate) fn into_result(self) -> Result<String, Error> {
+        if self.well_formed && self.key.is_some() {
+            Ok(self.value)
+        } else {
+            Err(Error::Other("value did not verify".into()))
+        }
+    }
+}
+
+impl<S> SessionHandler<S>
+where
+    S: SessionStore,
+{
+    // the following is adapted from
+    // https://github.com/SergioBenitez/cookie-rs/blob/master/src/secure/signed.rs#L51-L66
+    /// Given a signed value `str` where the signature is prepended to `value`,
+    /// verifies the signed value and returns it. If there's a problem, returns
+    /// an `Err` with a string describing the issue.
+    ///
+    /// Malformed values go through the same steps as forged ones, with a placeholder digest,
+    /// and every key is tried, so the time taken doesn't reveal why verification failed.
+    pub(crate) fn verify_signature(&self, cookie_value: &str) -> Result<String, Error> {
+        self.check_signature(cookie_value).into_result()
+    }
+    pub(crate) fn check_signature(&self, cookie_value: &str) -> SignatureCheck {
+        // Split [MAC | original-value] into its two parts.
+        let decoded = self.options.cookie_encoding.decode(cookie_value);
+        self.check_digest(decoded, cookie_value)
+    }
+    /// Checks a session value against the signature sent in the `<name>.sig` cookie.
+    pub(crate) fn check_detached_signature(&self, value: &str, signature: &str) -> SignatureCheck {
+        let decoded = self
+            .options
+            .cookie_encoding
+            .decode(signature)
+            .filter(|(_, rest)| rest.is_empty())
+            .map(|(digest, _)| (digest, value.to_owned()));
+        self.check_digest(decoded, value)
+    }
+    /// Checks the `digest` and `value` split from a cookie, or `fallback` if the cookie was
+    /// malformed, in which case the verification runs anyway and fails.
+    fn check_digest(&self, decoded: Option<(Vec<u8>, String)>, fallback: &str) -> SignatureCheck {
+        let well_formed = decoded.is_some();
+        let (digest, value) = decoded.unwrap_or_else(|| (vec![0; DIGEST_LEN], fallback.to_owned()));
+
+        // Perform the verification.
+        let keys = self.keys();
+        let mut key = None;
+        for (index, hmac) in iter::once(&keys.hmac)
+            .chain(&keys.fallback_hmacs)
+            .enumerate()
+        {
+            let mut hmac = hmac.clone();
+            hmac.update(value.as_bytes());
+            let verified = hmac.verify(&digest).is_ok();
+            key = key.or(verified.then_some(index));
+        }
+        SignatureCheck {
+            value,
+            well_formed,
+            key,
+        }
+    }
+    // The following is reused verbatim from
+    // https://github.com/SergioBenitez/cookie-rs/blob/master/src/secure/signed.rs#L37-46
+    /// signs the cookie's value providing integrity and authenticity.
+    pub(crate) fn sign_cookie(&self, cookie: &mut Cookie<'_>) {
+        let new_value = self.sign_value(cookie.value());
+        cookie.set_value(new_value);
+    }
+    pub(crate) fn keys(&self) -> Arc<KeySet> {
+        self.keys.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+    /// Refreshes the signing keys from the [`KeyProvider`](crate::KeyProvider), if one is set.
+    pub(crate) async fn refresh_keys(&self) {
+        if let Some(key_provider) = &self.options.key_provider {
+            key_provider.refresh(&self.keys).await;
+        }
+    }
+    pub(crate) fn sign_value(&self, value: &str) -> String {
+        // The signed value is [MAC | original-value].
+        self.options
+            .cookie_encoding
+            .encode(&self.digest(value), value)
+    }
+    /// Computes the HMAC-SHA256 of `value` with the current key.
+    pub(crate) fn digest(&self, value: &str) -> Vec<u8> {
+        let mut mac = self.keys().hmac.clone();
+        mac.update(value.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use cookie::Key;
+    use salvo_core::http::header::COOKIE;
+    use salvo_core::prelude::*;
+    use salvo_core::test::{ResponseExt, TestClient};
+
+    use crate::{HandlerBuilder, MemoryStore, Session, SessionDepotExt, SessionHandler};
+
+    #[test]
+    fn test_verify_signature() {
+        let handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .build()
+        .unwrap();
+        let signed_value = handler.sign_value("value");
+        assert_eq!(handler.verify_signature(&signed_value).unwrap(), "value");
+
+        let forged = format!("{}other", &signed_value[..44]);
+        let bad_base64 = format!("{}value", "!".repeat(44));
+        let multibyte = format!("{}évalue", "a".repeat(43));
+        for value in ["", "short", &forged, &bad_base64, &multibyte] {
+            let err = handler.verify_signature(value).unwrap_err();
+            assert_eq!(err.to_string(), "value did not verify");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_detached_signature() {
+        #[handler]
+        async fn login(depot: &mut Depot) {
+            depot.session_mut().unwrap().insert("user_id", 1).unwrap();
+        }
+        #[handler]
+        async fn current_user(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let session_handler = SessionHandler::builder(
+            MemoryStore::new(),
+            b"secretabsecretabsecretabsecretabsecretabsecretabsecretabsecretab",
+        )
+        .detached_signature(true)
+        .build()
+        .unwrap();
+        let router = Router::new()
+            .hoop(session_handler)
+            .push(Router::with_path("login").post(login))
+            .get(current_user);
+        let service = Service::new(router);
+
+        let respone = TestClient::post("http://127.0.0.1:5800/login")
+            .send(&service)
+            .await;
+        let value = respone.cookie("salvo.session.id").unwrap().value();
+        let signature = respone.cookie("salvo.session.id.sig").unwrap().value();
+        assert_eq!(signature.len(), 44);
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(
+                COOKIE,
+                format!("salvo.session.id={value}; salvo.session.id.sig={signature}"),
+                true,
+            )
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, format!("salvo.session.id={value}"), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(
+                COOKIE,
+                format!("salvo.session.id={value}x; salvo.session.id.sig={signature}"),
+                true,
+            )
+            .send(&service)
+            .await;
+        assert_eq!(respone.take_string().await.unwrap(), "None");
+    }
+
+    #[tokio::test]
+    async fn test_fallback_key_resigns() {
+        #[handler]
+        async fn user_id(depot: &mut Depot) -> String {
+            format!("{:?}", depot.session().unwrap().get::<u32>("user_id"))
+        }
+        let store = MemoryStore::new();
+        let old_key = Key::generate();
+        let old_handler = HandlerBuilder::from_key(store.clone(), old_key.clone())
+            .build()
+            .unwrap();
+        let mut session = Session::new();
+        session.insert("user_id", 1).unwrap();
+        let old_value = old_handler.save(session).await.unwrap().unwrap();
+        let session_handler = HandlerBuilder::from_key(store, Key::generate())
+            .add_fallback_key(old_key)
+            .refresh_threshold(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        assert!(session_handler.verify_signature(&old_value).is_ok());
+        let service = Service::new(Router::new().hoop(session_handler).get(user_id));
+
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, format!("salvo.session.id={old_value}"), true)
+            .send(&service)
+            .await;
+        let cookie = respone
+            .cookies()
+            .delta()
+            .find(|cookie| cookie.name() == "salvo.session.id")
+            .unwrap()
+            .clone();
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+        let new_value = cookie.value().to_owned();
+        assert_ne!(new_value, old_value);
+        assert!(old_handler.verify_signature(&new_value).is_err());
+
+        // Signed with the current key, the cookie isn't sent again.
+        let mut respone = TestClient::get("http://127.0.0.1:5800/")
+            .add_header(COOKIE, format!("salvo.session.id={new_value}"), true)
+            .send(&service)
+            .await;
+        assert_eq!(respone.cookies().delta().count(), 0);
+        assert_eq!(respone.take_string().await.unwrap(), "Some(1)");
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::{self, Debug, Formatter};
+use std::iter;
+
+use async_session::base64;
+use async_session::hmac::{Hmac, Mac, NewMac};
+use async_session::sha2::Sha256;
+use cookie::Key;
+
+use crate::cookie_encoding::DIGEST_LEN;
+
+/// Prefix of the signed data, so a token can never pass for a session cookie signed with the
+/// same key.
+const CONTEXT: &[u8] = b"salvo.session.signer\0";
+
+/// Signs small application tokens, such as unsubscribe links or download tokens, with the key
+/// of the session handler.
+///
+/// Tokens are the HMAC-SHA256 signature followed by the data, encoded with URL-safe base64
+/// without padding, so they fit in urls. The data is not encrypted. As with session cookies,
+/// new tokens are signed with the current key and tokens signed with a fallback key still
+/// verify, so the key can be rotated.
+#[derive(Clone)]
+pub struct Signer {
+    hmac: Hmac<Sha256>,
+    fallback_hmacs: Vec<Hmac<Sha256>>,
+}
+
+impl Debug for Signer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signer").field("key", &"..").finish()
+    }
+}
+
+fn hmac(key: &Key) -> Hmac<Sha256> {
+    Hmac::new_from_slice(key.signing()).expect("hmac should accept keys of any length")
+}
+
+impl Signer {
+    /// Create a new `Signer` signing with `key`.
+    pub fn new(key: &Key) -> Self {
+        Self {
+            hmac: hmac(key),
+            fallback_hmacs: vec![],
+        }
+    }
+
+    /// Sets fallbacks.
+    #[inline]
+    pub fn fallback_keys(mut self, keys: Vec<impl Into<Key>>) -> Self {
+        self.fallback_hmacs = keys.into_iter().map(|key| hmac(&key.into())).collect();
+        self
+    }
+
+    /// Add fallback secret.
+    #[inline]
+    pub fn add_fallback_key(mut self, key: impl Into<Key>) -> Self {
+        self.fallback_hmacs.push(hmac(&key.into()));
+        self
+    }
+
+    /// Signs `data` and returns the token.
+    pub fn sign(&self, data: &[u8]) -> String {
+        let mut mac = self.hmac.clone();
+        mac.update(CONTEXT);
+        mac.update(data);
+        let digest = mac.finalize().into_bytes();
+        base64::encode_config([&digest[..], data].concat(), base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Returns the data of `token` if its signature verifies with the current key or a fallback
+    /// key.
+    pub fn verify(&self, token: &str) -> Option<Vec<u8>> {
+        let mut bytes = base64::decode_config(token, base64::URL_SAFE_NO_PAD).ok()?;
+        if bytes.len() < DIGEST_LEN {
+            return None;
+        }
+        let data = bytes.split_off(DIGEST_LEN);
+        iter::once(&self.hmac)
+            .chain(&self.fallback_hmacs)
+            .any(|hmac| {
+                let mut mac = hmac.clone();
+                mac.update(CONTEXT);
+                mac.update(&data);
+                mac.verify(&bytes).is_ok()
+            })
+            .then_some(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signer() {
+        let old_key = Key::generate();
+        let signer = Signer::new(&old_key);
+        let token = signer.sign(b"unsubscribe:42");
+        assert_eq!(signer.verify(&token), Some(b"unsubscribe:42".to_vec()));
+        assert!(!token.contains(['+', '/', '=']));
+
+        let mut tampered = base64::decode_config(&token, base64::URL_SAFE_NO_PAD).unwrap();
+        *tampered.last_mut().unwrap() = b'3';
+        let tampered = base64::encode_config(tampered, base64::URL_SAFE_NO_PAD);
+        assert_eq!(signer.verify(&tampered), None);
+        assert_eq!(signer.verify("short"), None);
+
+        let rotated = Signer::new(&Key::generate());
+        assert_eq!(rotated.verify(&token), None);
+        let rotated = rotated.add_fallback_key(old_key);
+        assert_eq!(rotated.verify(&token), Some(b"unsubscribe:42".to_vec()));
+    }
+}
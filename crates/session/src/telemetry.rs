@@ -0,0 +1,104 @@
+use std::borrow::Cow;
+
+#[cfg(feature = "cookie-store")]
+use async_session::CookieStore;
+#[cfg(feature = "memory-store")]
+use async_session::MemoryStore;
+use async_session::SessionStore;
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::CachingStore;
+
+/// Describes a session store for tracing, following the OpenTelemetry database conventions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoreInfo {
+    /// The database system, such as `redis` or `memcached`, reported as `db.system`.
+    pub system: Cow<'static, str>,
+    /// The host name of the store server, reported as `net.peer.name`.
+    pub peer_name: Option<String>,
+}
+
+impl StoreInfo {
+    /// Create a new `StoreInfo` for a store backed by `system`.
+    #[inline]
+    pub fn new(system: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            system: system.into(),
+            peer_name: None,
+        }
+    }
+
+    /// Sets the host name of the store server.
+    #[inline]
+    pub fn peer_name(mut self, peer_name: impl Into<String>) -> Self {
+        self.peer_name = Some(peer_name.into());
+        self
+    }
+}
+
+/// Session stores that can describe themselves for tracing.
+///
+/// Enable it on a handler with
+/// [`HandlerBuilder::store_telemetry`](crate::HandlerBuilder::store_telemetry) to annotate the
+/// spans of store calls with [`StoreInfo`].
+///
+/// Support is best-effort: this crate has no Redis or SQL store, so it is implemented by
+/// `MemoryStore`, `CookieStore`, `ShardedMemoryStore` and `MemcachedStore`, and passed through
+/// by `CachingStore` and `EncryptedStore`. Stores from other crates must implement it
+/// themselves, and their store calls are traced without these attributes until they do.
+pub trait StoreTelemetry {
+    /// Returns the description of the store.
+    fn store_telemetry(&self) -> StoreInfo;
+}
+
+#[cfg(feature = "memory-store")]
+impl StoreTelemetry for MemoryStore {
+    fn store_telemetry(&self) -> StoreInfo {
+        StoreInfo::new("memory")
+    }
+}
+#[cfg(feature = "cookie-store")]
+impl StoreTelemetry for CookieStore {
+    fn store_telemetry(&self) -> StoreInfo {
+        StoreInfo::new("cookie")
+    }
+}
+impl<S: SessionStore + StoreTelemetry> StoreTelemetry for CachingStore<S> {
+    fn store_telemetry(&self) -> StoreInfo {
+        self.inner().store_telemetry()
+    }
+}
+
+/// Returns the span of a store call, annotated with `info` if it is known.
+pub(crate) fn store_span(info: Option<&StoreInfo>, operation: &'static str) -> Span {
+    let span = tracing::debug_span!(
+        "session_store",
+        db.system = Empty,
+        db.operation = operation,
+        net.peer.name = Empty,
+    );
+    if let Some(info) = info {
+        span.record("db.system", &*info.system);
+        if let Some(peer_name) = &info.peer_name {
+            span.record("net.peer.name", peer_name.as_str());
+        }
+    }
+    span
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_store_telemetry() {
+        assert_eq!(MemoryStore::new().store_telemetry().system, "memory");
+        let store = CachingStore::new(CookieStore::new(), 10, Duration::from_secs(1));
+        assert_eq!(store.store_telemetry(), StoreInfo::new("cookie"));
+        let info = StoreInfo::new("redis").peer_name("cache.internal");
+        assert_eq!(info.peer_name.as_deref(), Some("cache.internal"));
+    }
+}
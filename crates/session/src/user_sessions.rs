@@ -0,0 +1,19 @@
+use async_session::{async_trait, Result, SessionStore};
+
+/// Session stores that can find sessions by the user they belong to.
+///
+/// Sessions are indexed by the string stored under [`USER_ID_KEY`](crate::USER_ID_KEY), which
+/// the application sets when a user logs in. It enables
+/// [`SessionHandler::destroy_other_sessions`](crate::SessionHandler::destroy_other_sessions), to
+/// sign a user out everywhere else.
+///
+/// Support is best-effort: this crate has no Redis or SQL store, so only `ShardedMemoryStore`
+/// implements it, by scanning its sessions, and `CachingStore` passes it through. A persistent
+/// store from another crate must implement it itself, typically with a secondary index from
+/// user ids to session ids that is kept up to date on every write.
+#[async_trait]
+pub trait UserSessionStore: SessionStore {
+    /// Destroys the sessions of `user_id`, except the one with id `keep_id`, and returns how many
+    /// were destroyed.
+    async fn destroy_user_sessions(&self, user_id: &str, keep_id: Option<&str>) -> Result<usize>;
+}